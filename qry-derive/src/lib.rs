@@ -0,0 +1,141 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+	parse_macro_input, Attribute, DeriveInput, Error, FnArg, ImplItem, ItemImpl, Lit, Meta,
+	NestedMeta, Pat, Result,
+};
+
+// reads the name out of #[qry(name = "...")], a bare #[qry] keeps the rust name
+fn qry_name(attrs: &[Attribute]) -> Result<Option<String>> {
+	let mut name = None;
+	for attr in attrs.iter().filter(|a| a.path.is_ident("qry")) {
+		match attr.parse_meta()? {
+			Meta::Path(_) => (),
+			Meta::List(list) => {
+				for nested in list.nested {
+					match nested {
+						NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match nv.lit {
+							Lit::Str(s) => name = Some(s.value()),
+							other => return Err(Error::new_spanned(other, "expected a string")),
+						},
+						other => return Err(Error::new_spanned(other, "unknown qry attribute")),
+					}
+				}
+			}
+			other => {
+				return Err(Error::new_spanned(
+					other,
+					"expected #[qry] or #[qry(name = \"...\")]",
+				))
+			}
+		}
+	}
+	Ok(name)
+}
+
+#[proc_macro_derive(NativeType, attributes(qry))]
+pub fn derive_native_type(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	let ident = &input.ident;
+	let name = match qry_name(&input.attrs) {
+		Ok(name) => name.unwrap_or_else(|| ident.to_string()),
+		Err(err) => return err.to_compile_error().into(),
+	};
+
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	let expanded = quote! {
+		impl #impl_generics qry_runtime::NativeType for #ident #ty_generics #where_clause {
+			fn name() -> &'static str {
+				#name
+			}
+		}
+	};
+	expanded.into()
+}
+
+// exposes the #[qry] methods of an impl block as qry methods dispatching on
+// the type, registered by the generated define_qry_type
+#[proc_macro_attribute]
+pub fn qry_methods(_: TokenStream, item: TokenStream) -> TokenStream {
+	let mut item_impl = parse_macro_input!(item as ItemImpl);
+	match expand_methods(&mut item_impl) {
+		Ok(expanded) => expanded.into(),
+		Err(err) => err.to_compile_error().into(),
+	}
+}
+
+fn expand_methods(item_impl: &mut ItemImpl) -> Result<TokenStream2> {
+	if let Some((_, path, _)) = &item_impl.trait_ {
+		return Err(Error::new_spanned(
+			path,
+			"qry_methods only works on inherent impls",
+		));
+	}
+
+	let self_ty = item_impl.self_ty.clone();
+	let mut registrations = Vec::new();
+
+	for item in &mut item_impl.items {
+		let method = match item {
+			ImplItem::Method(method) if method.attrs.iter().any(|a| a.path.is_ident("qry")) => method,
+			_ => continue,
+		};
+		let name = qry_name(&method.attrs)?.unwrap_or_else(|| method.sig.ident.to_string());
+		method.attrs.retain(|a| !a.path.is_ident("qry"));
+
+		let mut inputs = method.sig.inputs.iter();
+		match inputs.next() {
+			Some(FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_none() => (),
+			_ => {
+				return Err(Error::new_spanned(
+					&method.sig,
+					"qry methods must take &self",
+				))
+			}
+		}
+
+		// args are converted with FromValue, so they have to be owned types
+		let mut params = Vec::new();
+		let mut args = Vec::new();
+		let mut dispatch_names = vec!["self".to_string()];
+		for (i, input) in inputs.enumerate() {
+			match input {
+				FnArg::Typed(pat_type) => {
+					let arg = format_ident!("arg{}", i);
+					let ty = &pat_type.ty;
+					params.push(quote!(#arg: #ty));
+					args.push(arg);
+					dispatch_names.push(match &*pat_type.pat {
+						Pat::Ident(pat) => pat.ident.to_string(),
+						_ => format!("arg{}", i),
+					});
+				}
+				FnArg::Receiver(r) => return Err(Error::new_spanned(r, "unexpected receiver")),
+			}
+		}
+
+		let ident = &method.sig.ident;
+		registrations.push(quote! {
+			env
+				.method_for_impl(methods, #name, &[#(#dispatch_names),*])
+				.register_fn(|this: qry_runtime::Shared<#self_ty>, #(#params),*| this.#ident(#(#args),*));
+		});
+	}
+
+	let (impl_generics, _, where_clause) = item_impl.generics.split_for_impl();
+	Ok(quote! {
+		#item_impl
+
+		impl #impl_generics #self_ty #where_clause {
+			pub fn define_qry_type(
+				env: &qry_runtime::Environment,
+				methods: &qry_runtime::RuntimeMethods,
+			) -> qry_runtime::Type {
+				let native_type = env.define_native_type::<Self>(methods);
+				#(#registrations)*
+				native_type
+			}
+		}
+	})
+}
@@ -1,4 +1,5 @@
 pub mod parser;
+mod printer;
 pub mod syntax;
 
 pub use parser::*;
@@ -1,5 +1,5 @@
 use super::syntax::*;
-use std::rc::Rc;
+use std::sync::Arc;
 
 fn unop(target: RawSyntaxNode, op: UnaryOperator) -> SyntaxTree<RawSyntaxNode> {
 	SyntaxTree::UnaryOp {
@@ -16,13 +16,65 @@ fn binop(lhs: RawSyntaxNode, rhs: RawSyntaxNode, op: BinaryOperator) -> SyntaxTr
 	}
 }
 
+// without an explicit import list the last segment of the path is the name
+// being imported, e.g. `use core::Int` or `use mylib::helpers`
+fn import_path(mut path: Vec<String>, import: Option<Import>) -> SyntaxTree<RawSyntaxNode> {
+	match import {
+		Some(import) => SyntaxTree::Use { from: path, import },
+		None => {
+			let name = path.pop().unwrap();
+			SyntaxTree::Use {
+				from: path,
+				import: Import::Named(vec![name]),
+			}
+		}
+	}
+}
+
+// a doc comment goes on the function it's in front of, whether that's
+// declared, exported or assigned, and is dropped in front of anything else
+fn with_doc(mut node: RawSyntaxNode, doc: String) -> RawSyntaxNode {
+	fn doc_slot(node: &mut RawSyntaxNode) -> Option<&mut Option<String>> {
+		match &mut node.syntax {
+			SyntaxTree::Function { doc, .. } => Some(doc),
+			SyntaxTree::Export(target) => doc_slot(target),
+			SyntaxTree::BinaryOp {
+				op: BinaryOperator::LAssign,
+				rhs,
+				..
+			} => doc_slot(rhs),
+			_ => None,
+		}
+	}
+
+	if let Some(slot) = doc_slot(&mut node) {
+		*slot = Some(doc);
+	}
+	node
+}
+
 peg::parser! {
 	grammar parser() for str {
+		// three slashes make a doc comment, unless there are more
+		rule comment()
+			= "//" !("/" !"/") (!"\n" [_])*
+
 		rule _()
-			= [' ' | '\n' | '\t' | '\r']*
+			= ([' ' | '\n' | '\t' | '\r'] / comment() / stray_doc_comment())*
 
 		rule __()
-			= [' ' | '\n' | '\t' | '\r']+
+			= ([' ' | '\n' | '\t' | '\r'] / comment() / stray_doc_comment())+
+
+		// with nothing after it to document, such as at the end of a block, a
+		// doc comment is only a comment
+		rule stray_doc_comment()
+			= doc_comment() !(_ operation())
+
+		rule doc_line() -> &'input str
+			= "///" " "? s:$((!"\n" [_])*) { s.trim_end() }
+
+		rule doc_comment() -> String
+			= lines:doc_line() ++ _ { lines.join("\n") }
 
 		rule ident() -> String
 			= s:$(['a'..='z' | 'A'..='Z' | '_'] ['a'..='z' | 'A'..='Z' | '_' | '0'..='9']*) { s.to_string() }
@@ -36,9 +88,6 @@ peg::parser! {
 		rule import_named() -> Import
 			= "::{" _ names:ident() ** ("," _) _ "}" { Import::Named(names) }
 
-		rule import_lib() -> Import
-			= name:ident() { Import::Named(vec![name]) }
-
 		rule fn_named_prefix() -> FunctionHeader<RawSyntaxNode>
 			= "fn" __ name:ident() { FunctionHeader::Function(Some(name)) }
 
@@ -58,12 +107,21 @@ peg::parser! {
 		rule string_contents() -> String
 			= s:$(!"\"" [_])* { s.iter().fold("".to_string(), |acc, val| format!("{}{}", acc, val)) }
 
+		rule int_literal() -> SyntaxTree<RawSyntaxNode>
+			= n:$(['0'..='9']+) {? n.parse().map(SyntaxTree::Int).or(Err("integer literal in range")) }
+
 		rule switch_case() -> SwitchCase<RawSyntaxNode>
 			= expr:expr() __ "=>" __ returns:expr() { SwitchCase { expr, returns } }
 
-		rule expr() -> RawSyntaxNode = precedence!{
+		rule expr() -> RawSyntaxNode
+			= doc:doc_comment() _ node:operation() { with_doc(node, doc) }
+			/ operation()
+
+		rule operation() -> RawSyntaxNode = precedence!{
 			start_pos:position!() syntax:@ end_pos:position!() { RawSyntaxNode { start_pos, end_pos, syntax } }
 			--
+			"pub" __ target:@ { SyntaxTree::Export(Box::new(target)) }
+			--
 			lhs:@ __ "<-" __ rhs:(@) { binop(lhs, rhs, BinaryOperator::LAssign) }
 			--
 			lhs:(@) __ "->" __ rhs:@ { binop(lhs, rhs, BinaryOperator::RAssign) }
@@ -86,14 +144,15 @@ peg::parser! {
 			lhs:(@) __ "-" __ rhs:@ { binop(lhs, rhs, BinaryOperator::Sub) }
 			--
 			lhs:(@) __ "*" __ rhs:@ { binop(lhs, rhs, BinaryOperator::Mul) }
-			lhs:(@) __ "/" __ rhs:@ { binop(lhs, rhs, BinaryOperator::Div) }
+			lhs:(@) __ "/" !"/" __ rhs:@ { binop(lhs, rhs, BinaryOperator::Div) }
 			--
 			header:(fn_named_prefix() / fn_anon_prefix() / fn_method_impl()) _ "(" _ params:param_def() ** (_ "," _) ")" _ "->" _ return_type:expr() _ "{" _ body:expr() ** _  _ "}"  {
 				SyntaxTree::Function {
 					header,
 					params,
 					body,
-					return_type: Box::new(return_type)
+					return_type: Box::new(return_type),
+					doc: None,
 				}
 			}
 			--
@@ -115,14 +174,17 @@ peg::parser! {
 			--
 			lhs:(@) "::" rhs:@ { binop(lhs, rhs, BinaryOperator::Access) }
 			--
-			"use" __ from:ident() ** "::" import:(import_named() / import_wildcard() / import_lib()) { SyntaxTree::Use { from, import } }
-			"use" __ import:import_lib() { SyntaxTree::Use { from: vec![], import } }
+			"use" __ path:ident() ++ "::" import:(import_named() / import_wildcard())? { import_path(path, import) }
+			--
+			"interface" __ name:ident() _ "{" _ methods:expr() ** _ _ "}" { SyntaxTree::Interface { name, methods } }
+			--
+			"type" __ name:ident() _ "=" _ target:expr() { SyntaxTree::TypeAlias { name, target: Box::new(target) } }
 			--
 			"switch" _ target:expr() _ "{" _ cases:switch_case() ** _ _ "}" { SyntaxTree::Switch { target: Box::new(target), cases } }
 			--
 			n:
 			$(['0'..='9']+ "." ['0'..='9']*) { SyntaxTree::Float(n.parse().unwrap()) }
-			n:$(['0'..='9']+) { SyntaxTree::Int(n.parse().unwrap()) }
+			n:int_literal() { n }
 			"\"" s:string_contents() "\"" { SyntaxTree::String(s) }
 			b:$("true" / "false") { SyntaxTree::Bool(b == "true") }
 			"null" { SyntaxTree::Null }
@@ -144,7 +206,7 @@ struct RawSyntaxNode {
 }
 
 struct SourceLocationMapper {
-	filename: Rc<str>,
+	filename: Arc<str>,
 	linebreak_offsets: Vec<usize>,
 }
 
@@ -170,6 +232,7 @@ impl SourceLocationMapper {
 			SyntaxTree::Null => SyntaxTree::Null,
 			SyntaxTree::Ident(n) => SyntaxTree::Ident(n.clone()),
 			SyntaxTree::Interpolate(expr) => SyntaxTree::Interpolate(self.map(expr)),
+			SyntaxTree::Export(target) => SyntaxTree::Export(self.map(target)),
 			SyntaxTree::BinaryOp { op, lhs, rhs } => SyntaxTree::BinaryOp {
 				op: *op,
 				lhs: self.map(lhs),
@@ -188,6 +251,7 @@ impl SourceLocationMapper {
 				params,
 				return_type,
 				body,
+				doc,
 			} => SyntaxTree::Function {
 				header: match header {
 					FunctionHeader::Function(n) => FunctionHeader::Function(n.clone()),
@@ -204,6 +268,7 @@ impl SourceLocationMapper {
 					.collect(),
 				return_type: self.map(return_type),
 				body: body.iter().map(|e| *self.map(e)).collect(),
+				doc: doc.clone(),
 			},
 			SyntaxTree::Switch { target, cases } => SyntaxTree::Switch {
 				target: self.map(target),
@@ -223,6 +288,14 @@ impl SourceLocationMapper {
 				target: self.map(target),
 				type_args: type_args.iter().map(|k| *self.map(k)).collect(),
 			},
+			SyntaxTree::TypeAlias { name, target } => SyntaxTree::TypeAlias {
+				name: name.clone(),
+				target: self.map(target),
+			},
+			SyntaxTree::Interface { name, methods } => SyntaxTree::Interface {
+				name: name.clone(),
+				methods: methods.iter().map(|m| *self.map(m)).collect(),
+			},
 		};
 
 		let line = match self.linebreak_offsets.binary_search(&node.start_pos) {
@@ -0,0 +1,285 @@
+use super::syntax::*;
+use std::fmt::{self, Display, Formatter};
+
+// levels from the precedence! table in the parser, lowest first
+fn precedence(syntax: &Syntax) -> u8 {
+	use BinaryOperator::*;
+	match syntax {
+		SyntaxTree::Export(_) => 1,
+		SyntaxTree::BinaryOp { op, .. } => match op {
+			LAssign => 2,
+			RAssign => 3,
+			Or => 4,
+			And => 5,
+			Equal | NotEqual => 7,
+			Gt | Gte | Lt | Lte => 8,
+			Add | Sub => 9,
+			Mul | Div => 10,
+			Pipe => 12,
+			Access => 17,
+		},
+		SyntaxTree::UnaryOp {
+			op: UnaryOperator::Negate,
+			..
+		} => 6,
+		SyntaxTree::Function { .. } => 11,
+		SyntaxTree::UnaryOp {
+			op: UnaryOperator::Minus,
+			..
+		} => 13,
+		SyntaxTree::Call { .. } => POSTFIX_LEVEL,
+		SyntaxTree::Index { .. } => 15,
+		SyntaxTree::GenericInstantiation { .. } => 16,
+		SyntaxTree::Use { .. } => 18,
+		SyntaxTree::Interface { .. } => 19,
+		SyntaxTree::TypeAlias { .. } => 20,
+		SyntaxTree::Switch { .. } => 21,
+		_ => 22,
+	}
+}
+
+const POSTFIX_LEVEL: u8 = 14;
+
+// the doc comment to print in front of a node, mirroring where the parser
+// attaches them
+fn doc_comment(syntax: &Syntax) -> Option<&str> {
+	match syntax {
+		SyntaxTree::Function { doc, .. } => doc.as_deref(),
+		SyntaxTree::Export(target) => doc_comment(&target.syntax),
+		SyntaxTree::BinaryOp {
+			op: BinaryOperator::LAssign,
+			rhs,
+			..
+		} => doc_comment(&rhs.syntax),
+		_ => None,
+	}
+}
+
+fn binop_symbol(op: BinaryOperator) -> &'static str {
+	use BinaryOperator::*;
+	match op {
+		Add => "+",
+		Sub => "-",
+		Mul => "*",
+		Div => "/",
+		LAssign => "<-",
+		RAssign => "->",
+		Access => "::",
+		Equal => "==",
+		NotEqual => "!=",
+		Lt => "<",
+		Lte => "<=",
+		Gt => ">",
+		Gte => ">=",
+		Pipe => "|>",
+		And => "&",
+		Or => "|",
+	}
+}
+
+// prints syntax back out as source that parses to the same tree, with
+// parentheses only where precedence needs them
+struct Printer<'a, 'b> {
+	f: &'a mut Formatter<'b>,
+	indent: usize,
+}
+
+impl Printer<'_, '_> {
+	fn newline(&mut self) -> fmt::Result {
+		writeln!(self.f)?;
+		write!(self.f, "{}", "\t".repeat(self.indent))
+	}
+
+	// operands below min_level would bind differently, so get parenthesised.
+	// Postfix operators chain in any order, so their targets only need to be
+	// at the call level. Doc comments can't go in front of an operand, they're
+	// printed before the whole expression instead
+	fn operand(&mut self, node: &SyntaxNode, min_level: u8) -> fmt::Result {
+		match precedence(&node.syntax) < min_level {
+			true => {
+				write!(self.f, "(")?;
+				self.syntax(node)?;
+				write!(self.f, ")")
+			}
+			false => self.syntax(node),
+		}
+	}
+
+	fn list(&mut self, nodes: &[SyntaxNode]) -> fmt::Result {
+		for (i, node) in nodes.iter().enumerate() {
+			if i > 0 {
+				write!(self.f, ", ")?;
+			}
+			self.node(node)?;
+		}
+		Ok(())
+	}
+
+	fn block(&mut self, nodes: &[SyntaxNode]) -> fmt::Result {
+		write!(self.f, "{{")?;
+		self.indent += 1;
+		for node in nodes {
+			self.newline()?;
+			self.node(node)?;
+		}
+		self.indent -= 1;
+		self.newline()?;
+		write!(self.f, "}}")
+	}
+
+	fn node(&mut self, node: &SyntaxNode) -> fmt::Result {
+		if let Some(doc) = doc_comment(&node.syntax) {
+			for line in doc.lines() {
+				match line {
+					"" => write!(self.f, "///")?,
+					line => write!(self.f, "/// {}", line)?,
+				}
+				self.newline()?;
+			}
+		}
+		self.syntax(node)
+	}
+
+	fn syntax(&mut self, node: &SyntaxNode) -> fmt::Result {
+		let level = precedence(&node.syntax);
+		match &node.syntax {
+			SyntaxTree::Null => write!(self.f, "null"),
+			SyntaxTree::Int(n) => write!(self.f, "{}", n),
+			SyntaxTree::Float(n) => match n.to_string() {
+				s if s.contains('.') => write!(self.f, "{}", s),
+				s => write!(self.f, "{}.0", s),
+			},
+			SyntaxTree::Bool(b) => write!(self.f, "{}", b),
+			SyntaxTree::String(s) => write!(self.f, "\"{}\"", s),
+			SyntaxTree::Ident(name) => write!(self.f, "{}", name),
+			SyntaxTree::Interpolate(expr) => {
+				write!(self.f, "{{{{ ")?;
+				self.node(expr)?;
+				write!(self.f, " }}}}")
+			}
+			SyntaxTree::Export(target) => {
+				write!(self.f, "pub ")?;
+				self.operand(target, level + 1)
+			}
+			SyntaxTree::Use { from, import } => {
+				write!(self.f, "use ")?;
+				match import {
+					Import::Named(names) if names.len() == 1 => {
+						for segment in from {
+							write!(self.f, "{}::", segment)?;
+						}
+						write!(self.f, "{}", names[0])
+					}
+					Import::Named(names) => write!(self.f, "{}::{{{}}}", from.join("::"), names.join(", ")),
+					Import::Wildcard => write!(self.f, "{}::*", from.join("::")),
+				}
+			}
+			SyntaxTree::BinaryOp { op, lhs, rhs } => {
+				// assignment is the only right associative operator
+				let (lhs_level, rhs_level) = match op {
+					BinaryOperator::LAssign => (level + 1, level),
+					_ => (level, level + 1),
+				};
+				self.operand(lhs, lhs_level)?;
+				match op {
+					BinaryOperator::Access => write!(self.f, "::")?,
+					_ => write!(self.f, " {} ", binop_symbol(*op))?,
+				}
+				self.operand(rhs, rhs_level)
+			}
+			SyntaxTree::UnaryOp { op, target } => {
+				match op {
+					UnaryOperator::Negate => write!(self.f, "!")?,
+					UnaryOperator::Minus => write!(self.f, "-")?,
+				}
+				self.operand(target, level + 1)
+			}
+			SyntaxTree::Function {
+				header,
+				params,
+				return_type,
+				body,
+				..
+			} => {
+				match header {
+					FunctionHeader::Function(Some(name)) => write!(self.f, "fn {}", name)?,
+					FunctionHeader::Function(None) => write!(self.f, "fn")?,
+					FunctionHeader::MethodImpl { impl_for } => {
+						write!(self.f, "impl ")?;
+						self.node(impl_for)?;
+					}
+				}
+				write!(self.f, "(")?;
+				for (i, param) in params.iter().enumerate() {
+					if i > 0 {
+						write!(self.f, ", ")?;
+					}
+					write!(self.f, "{}: ", param.name)?;
+					self.node(&param.param_type)?;
+				}
+				write!(self.f, ") -> ")?;
+				self.node(return_type)?;
+				write!(self.f, " ")?;
+				self.block(body)
+			}
+			SyntaxTree::Call {
+				target,
+				positional_args,
+				named_args,
+			} => {
+				self.operand(target, POSTFIX_LEVEL)?;
+				write!(self.f, "(")?;
+				self.list(positional_args)?;
+				for (i, (name, arg)) in named_args.iter().enumerate() {
+					if i > 0 || !positional_args.is_empty() {
+						write!(self.f, ", ")?;
+					}
+					write!(self.f, "{} = ", name)?;
+					self.node(arg)?;
+				}
+				write!(self.f, ")")
+			}
+			SyntaxTree::Switch { target, cases } => {
+				write!(self.f, "switch ")?;
+				self.node(target)?;
+				write!(self.f, " {{")?;
+				self.indent += 1;
+				for case in cases {
+					self.newline()?;
+					self.node(&case.expr)?;
+					write!(self.f, " => ")?;
+					self.node(&case.returns)?;
+				}
+				self.indent -= 1;
+				self.newline()?;
+				write!(self.f, "}}")
+			}
+			SyntaxTree::Index { target, keys } => {
+				self.operand(target, POSTFIX_LEVEL)?;
+				write!(self.f, "[")?;
+				self.list(keys)?;
+				write!(self.f, "]")
+			}
+			SyntaxTree::GenericInstantiation { target, type_args } => {
+				self.operand(target, POSTFIX_LEVEL)?;
+				write!(self.f, "<")?;
+				self.list(type_args)?;
+				write!(self.f, ">")
+			}
+			SyntaxTree::TypeAlias { name, target } => {
+				write!(self.f, "type {} = ", name)?;
+				self.node(target)
+			}
+			SyntaxTree::Interface { name, methods } => {
+				write!(self.f, "interface {} ", name)?;
+				self.block(methods)
+			}
+		}
+	}
+}
+
+impl Display for SyntaxNode {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		Printer { f, indent: 0 }.node(self)
+	}
+}
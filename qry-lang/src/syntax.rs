@@ -1,5 +1,5 @@
 use std::panic::Location;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOperator {
@@ -69,6 +69,7 @@ pub enum SyntaxTree<T> {
 	String(String),
 	Ident(String),
 	Interpolate(Box<T>),
+	Export(Box<T>),
 	Use {
 		from: Vec<String>,
 		import: Import,
@@ -87,6 +88,8 @@ pub enum SyntaxTree<T> {
 		params: Vec<ParameterDef<T>>,
 		return_type: Box<T>,
 		body: Vec<T>,
+		// from the `///` comment lines before the declaration
+		doc: Option<String>,
 	},
 	Call {
 		target: Box<T>,
@@ -105,12 +108,21 @@ pub enum SyntaxTree<T> {
 		target: Box<T>,
 		type_args: Vec<T>,
 	},
+	TypeAlias {
+		name: String,
+		target: Box<T>,
+	},
+	Interface {
+		name: String,
+		methods: Vec<T>,
+	},
 }
 
+// Arc so syntax trees can be shared with runtimes on other threads
 #[derive(Debug, Clone, PartialEq)]
 pub enum SourceLocation {
-	User { line: usize, file: Rc<str> },
-	Native { line: usize, file: Rc<str> },
+	User { line: usize, file: Arc<str> },
+	Native { line: usize, file: Arc<str> },
 	Unknown,
 }
 
@@ -129,4 +141,23 @@ pub struct SyntaxNode {
 	pub location: SourceLocation,
 }
 
+impl SyntaxNode {
+	// the name a definition binds, which is what `pub` exports
+	pub fn defined_name(&self) -> Option<&str> {
+		match &self.syntax {
+			SyntaxTree::BinaryOp { op, lhs, rhs } => match (op, &lhs.syntax, &rhs.syntax) {
+				(BinaryOperator::LAssign, SyntaxTree::Ident(name), _) => Some(name),
+				(BinaryOperator::RAssign, _, SyntaxTree::Ident(name)) => Some(name),
+				_ => None,
+			},
+			SyntaxTree::Function {
+				header: FunctionHeader::Function(Some(name)),
+				..
+			} => Some(name),
+			SyntaxTree::TypeAlias { name, .. } | SyntaxTree::Interface { name, .. } => Some(name),
+			_ => None,
+		}
+	}
+}
+
 pub type Syntax = SyntaxTree<SyntaxNode>;
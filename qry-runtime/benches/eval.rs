@@ -0,0 +1,51 @@
+#![feature(test)]
+
+extern crate test;
+
+use qry_lang::{parse, SyntaxNode};
+use qry_runtime::{compile, eval_multi, run_compiled, EvalContext};
+use test::Bencher;
+
+fn bench_program() -> Vec<SyntaxNode> {
+	let mut src = String::from(
+		"fn step(a: Int, b: Int) -> Int {
+			c <- a * 2
+			d <- c + b
+			switch d {
+				0 => 1
+				1 => 2
+			}
+			d - a
+		}
+		fn chain(a: Int) -> Int { step(step(a, 1), step(a, 2)) }
+		total <- 0
+		",
+	);
+
+	for i in 0..200 {
+		src.push_str(&format!("total <- total + chain({})\n", i));
+	}
+
+	parse(&src, "bench").unwrap()
+}
+
+#[bench]
+fn bench_tree_walker(b: &mut Bencher) {
+	let program = bench_program();
+	let ctx = EvalContext::new_with_stdlib();
+	b.iter(|| eval_multi(&ctx, &program).unwrap());
+}
+
+#[bench]
+fn bench_compiled(b: &mut Bencher) {
+	let program = bench_program();
+	let ctx = EvalContext::new_with_stdlib();
+	b.iter(|| run_compiled(&ctx, &compile(&program)).unwrap());
+}
+
+#[bench]
+fn bench_compiled_cached(b: &mut Bencher) {
+	let compiled = compile(&bench_program());
+	let ctx = EvalContext::new_with_stdlib();
+	b.iter(|| run_compiled(&ctx, &compiled).unwrap());
+}
@@ -1,15 +1,23 @@
-use super::{Callable, EvalContext, EvalResult, Signature, Value};
+use super::{Callable, EvalContext, EvalResult, Shared, Signature, Value};
 use qry_lang::SourceLocation;
 use std::fmt::Debug;
-use std::rc::Rc;
 
 pub type BuiltinFunc = fn(&EvalContext, &[Value], &[(&str, Value)]) -> EvalResult<Value>;
 
+// lets hosts register builtins that capture their own state, like a
+// connection pool or config
+#[cfg(not(feature = "sync"))]
+pub type BuiltinClosure =
+	Shared<dyn Fn(&EvalContext, &[Value], &[(&str, Value)]) -> EvalResult<Value>>;
+#[cfg(feature = "sync")]
+pub type BuiltinClosure =
+	Shared<dyn Fn(&EvalContext, &[Value], &[(&str, Value)]) -> EvalResult<Value> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct Builtin {
 	name: String,
 	signature: Signature,
-	func: BuiltinFunc,
+	func: BuiltinClosure,
 	location: SourceLocation,
 }
 
@@ -19,8 +27,17 @@ impl Builtin {
 		signature: Signature,
 		location: SourceLocation,
 		func: BuiltinFunc,
-	) -> Rc<Builtin> {
-		Rc::new(Builtin {
+	) -> Shared<Builtin> {
+		Self::new_closure(name, signature, location, Shared::new(func))
+	}
+
+	pub fn new_closure(
+		name: &str,
+		signature: Signature,
+		location: SourceLocation,
+		func: BuiltinClosure,
+	) -> Shared<Builtin> {
+		Shared::new(Builtin {
 			name: name.into(),
 			signature,
 			func,
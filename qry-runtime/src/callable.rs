@@ -1,4 +1,6 @@
-use super::{eval, EvalContext, EvalResult, Type, Value};
+use super::{
+	eval, Environment, EvalContext, EvalEvent, EvalResult, MaybeSync, Shared, StackFrame, Type, Value,
+};
 use qry_lang::{SourceLocation, SyntaxNode};
 
 #[derive(Debug, Clone)]
@@ -13,6 +15,8 @@ pub struct Signature {
 	pub params: Vec<Parameter>,
 	pub trailing_type: Option<Type>,
 	pub named_trailing_type: Option<Type>,
+	// what the callable does, for help and generated reference pages
+	pub doc: Option<String>,
 }
 
 impl Signature {
@@ -22,6 +26,7 @@ impl Signature {
 			params: Vec::new(),
 			trailing_type: None,
 			named_trailing_type: None,
+			doc: None,
 		}
 	}
 
@@ -45,9 +50,28 @@ impl Signature {
 		ret.named_trailing_type = Some(named_trailing_type.clone());
 		ret
 	}
+
+	pub fn with_doc(&self, doc: &str) -> Self {
+		let mut ret = self.clone();
+		ret.doc = Some(doc.into());
+		ret
+	}
+}
+
+impl std::fmt::Display for Signature {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		let param_string = self
+			.params
+			.iter()
+			.map(|p| format!("{}: {}", p.name, p.param_type))
+			.collect::<Vec<_>>()
+			.join(", ");
+
+		write!(f, "({}) -> {}", param_string, self.return_type)
+	}
 }
 
-pub trait Callable {
+pub trait Callable: MaybeSync {
 	fn signature(&self) -> &Signature;
 	fn source_location(&self) -> &SourceLocation;
 	fn name(&self) -> &str;
@@ -57,6 +81,15 @@ pub trait Callable {
 		args: &[Value],
 		named_trailing: &[(&str, Value)],
 	) -> EvalResult<Value>;
+
+	fn doc(&self) -> Option<String> {
+		self.signature().doc.clone()
+	}
+
+	// the environment a closure keeps alive, which the cycle collector follows
+	fn captured_env(&self) -> Option<&Shared<Environment>> {
+		None
+	}
 }
 
 impl std::fmt::Debug for dyn Callable {
@@ -67,19 +100,25 @@ impl std::fmt::Debug for dyn Callable {
 	}
 }
 
-fn typecheck_val(ctx: &EvalContext, val: Value, expected_type: &Type) -> EvalResult<Value> {
-	if expected_type == &Type::Any || expected_type == &val.runtime_type() {
-		Ok(val)
-	} else {
-		Err(ctx.exception(
-			&SourceLocation::Unknown,
-			format!(
-				"typecheck failed: expected {}, got {}",
-				expected_type.name(),
-				val.runtime_type().name()
-			),
-		))
+pub fn typecheck_val(ctx: &EvalContext, val: Value, expected_type: &Type) -> EvalResult<Value> {
+	let actual_type = val.runtime_type();
+	if expected_type.accepts(&actual_type) {
+		return Ok(val);
 	}
+
+	let message = match expected_type {
+		Type::Interface(interface) => format!(
+			"typecheck failed: {} does not implement {}, missing impls: {}",
+			actual_type,
+			interface.name,
+			interface.missing_impls(&actual_type).join(", ")
+		),
+		_ => format!(
+			"typecheck failed: expected {}, got {}",
+			expected_type, actual_type
+		),
+	};
+	Err(ctx.exception(&SourceLocation::Unknown, message))
 }
 
 fn eval_arg(ctx: &EvalContext, param_type: &Type, expr: &SyntaxNode) -> EvalResult<Value> {
@@ -89,37 +128,72 @@ fn eval_arg(ctx: &EvalContext, param_type: &Type, expr: &SyntaxNode) -> EvalResu
 	}
 }
 
-pub fn eval_callable(
+pub fn check_arity(
 	ctx: &EvalContext,
-	callable: &impl Callable,
-	positional: &[SyntaxNode],
-	named_trailing: &[(&str, SyntaxNode)],
-) -> EvalResult<Value> {
-	let sig = callable.signature();
-	let num_supplied = positional.len();
+	sig: &Signature,
+	num_positional: usize,
+	num_named: usize,
+) -> EvalResult<()> {
 	let num_expected_min = sig.params.len();
-	if num_supplied < num_expected_min
-		|| (sig.trailing_type.is_none() && num_supplied > num_expected_min)
+	if num_positional < num_expected_min
+		|| (sig.trailing_type.is_none() && num_positional > num_expected_min)
 	{
 		return Err(ctx.exception(&SourceLocation::Unknown, "arg count mismatch"));
 	}
 
-	if !named_trailing.is_empty() && sig.named_trailing_type.is_none() {
+	if num_named > 0 && sig.named_trailing_type.is_none() {
 		return Err(ctx.exception(&SourceLocation::Unknown, "too many args"));
 	}
 
+	Ok(())
+}
+
+pub fn positional_param_type(sig: &Signature, index: usize) -> &Type {
+	if index < sig.params.len() {
+		&sig.params[index].param_type
+	} else {
+		sig.trailing_type.as_ref().unwrap()
+	}
+}
+
+pub fn call_with_args<C: Callable + ?Sized>(
+	ctx: &EvalContext,
+	callable: &C,
+	call_site: &SourceLocation,
+	args: &[Value],
+	named_args: &[(&str, Value)],
+) -> EvalResult<Value> {
+	ctx.check_call_depth(call_site)?;
+	let _stackframe = ctx.with_stack_frame(StackFrame::new(callable, call_site, args));
+	let _profile = ctx.profile(callable.name());
+
+	let name = callable.name();
+	ctx.emit(EvalEvent::CallEnter {
+		name,
+		location: call_site,
+		args,
+	});
+	let ret = callable
+		.call(ctx, args, named_args)
+		.and_then(|ret| typecheck_val(ctx, ret, &callable.signature().return_type));
+	ctx.emit(EvalEvent::CallExit { name, result: &ret });
+	ret
+}
+
+pub fn eval_callable(
+	ctx: &EvalContext,
+	callable: &impl Callable,
+	call_site: &SourceLocation,
+	positional: &[SyntaxNode],
+	named_trailing: &[(&str, SyntaxNode)],
+) -> EvalResult<Value> {
+	let sig = callable.signature();
+	check_arity(ctx, sig, positional.len(), named_trailing.len())?;
+
 	let args = positional
 		.iter()
 		.enumerate()
-		.map(|(i, s)| {
-			let param_type = if i < num_expected_min {
-				&sig.params[i].param_type
-			} else {
-				sig.trailing_type.as_ref().unwrap()
-			};
-
-			eval_arg(ctx, param_type, s)
-		})
+		.map(|(i, s)| eval_arg(ctx, positional_param_type(sig, i), s))
 		.collect::<Result<Vec<_>, _>>()?;
 
 	let named_args = named_trailing
@@ -131,8 +205,5 @@ pub fn eval_callable(
 		})
 		.collect::<EvalResult<Vec<_>>>()?;
 
-	let _stackframe = ctx.with_stack_frame(callable.name(), callable.source_location());
-
-	let ret = callable.call(ctx, &args, &named_args)?;
-	typecheck_val(ctx, ret, &sig.return_type)
+	call_with_args(ctx, callable, call_site, &args, &named_args)
 }
@@ -0,0 +1,742 @@
+use super::{
+	collect_assigned_names, identical, is_module_path, location_for_stacktrace,
+	positional_param_type, select_by_specificity, Callable, EvalContext, Interface, Method, Shared,
+	Signature, Type, Value,
+};
+use qry_lang::syntax::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub message: String,
+	pub location: SourceLocation,
+}
+
+impl std::fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+		write!(
+			f,
+			"{} ({})",
+			self.message,
+			location_for_stacktrace(&self.location)
+		)
+	}
+}
+
+#[derive(Debug, Clone)]
+enum Inferred {
+	Unknown,
+	Typed(Type),
+	Known(Value),
+	Function(Signature),
+}
+
+impl Inferred {
+	fn static_type(&self) -> Type {
+		match self {
+			Self::Unknown => Type::Any,
+			Self::Typed(t) if involves_interface(t) => Type::Any,
+			Self::Typed(t) => t.clone(),
+			Self::Known(v) => v.runtime_type(),
+			Self::Function(_) => Type::Function,
+		}
+	}
+
+	fn as_type(&self) -> Option<Type> {
+		match self {
+			Self::Known(Value::Type(t)) => Some(t.clone()),
+			_ => None,
+		}
+	}
+}
+
+type Scope = HashMap<String, Inferred>;
+
+struct Checker<'a> {
+	ctx: &'a EvalContext,
+	diagnostics: Vec<Diagnostic>,
+	declared_impls: Vec<(Shared<Method>, Signature)>,
+}
+
+// interface conformance depends on impls registered while running, so values
+// of those types are treated as Any and checks against them are left to the
+// runtime
+fn involves_interface(t: &Type) -> bool {
+	match t {
+		Type::Interface(_) => true,
+		Type::Union(members) => members.iter().any(involves_interface),
+		_ => false,
+	}
+}
+
+// a union-typed value is allowed through if any of its members could be
+// accepted, since the runtime check sees the concrete type
+fn types_compatible(expected: &Type, actual: &Type) -> bool {
+	match actual {
+		Type::Any => true,
+		_ if involves_interface(expected) => true,
+		Type::Union(members) => members.iter().any(|m| expected.accepts(m)),
+		_ => expected.accepts(actual),
+	}
+}
+
+// a name assigned in switch cases keeps its type only if it has that type
+// whichever case runs, or if none of them do
+fn merge_inferred<'a>(mut branches: impl Iterator<Item = Option<&'a Inferred>>) -> Inferred {
+	let first = match branches.next() {
+		Some(Some(first)) => first,
+		_ => return Inferred::Unknown,
+	};
+	let t = first.static_type();
+	let mut same_value = true;
+	for branch in branches {
+		match branch {
+			Some(inferred) if inferred.static_type() == t => {
+				same_value &= match (first, inferred) {
+					(Inferred::Known(a), Inferred::Known(b)) => identical(a, b),
+					_ => false,
+				};
+			}
+			_ => return Inferred::Unknown,
+		}
+	}
+	match first {
+		Inferred::Known(_) if same_value => first.clone(),
+		_ => Inferred::Typed(t),
+	}
+}
+
+fn type_list(types: &[Type]) -> String {
+	types
+		.iter()
+		.map(|t| t.to_string())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+fn expand_unions(types: &[Type]) -> Vec<Vec<Type>> {
+	let mut expanded = vec![Vec::new()];
+	for t in types {
+		let members = match t {
+			Type::Union(members) => members.clone(),
+			t => vec![t.clone()],
+		};
+
+		expanded = expanded
+			.iter()
+			.flat_map(|prefix| {
+				members.iter().map(move |m| {
+					let mut combination = prefix.clone();
+					combination.push(m.clone());
+					combination
+				})
+			})
+			.collect();
+	}
+	expanded
+}
+
+impl Checker<'_> {
+	fn report<S: Into<String>>(&mut self, location: &SourceLocation, message: S) {
+		self.diagnostics.push(Diagnostic {
+			message: message.into(),
+			location: location.clone(),
+		});
+	}
+
+	fn resolve_method(
+		&mut self,
+		location: &SourceLocation,
+		method: &Shared<Method>,
+		arg_types: &[Type],
+	) -> Inferred {
+		let dispatch_types = &arg_types[..method.signature().params.len().min(arg_types.len())];
+		if dispatch_types.contains(&Type::Any) {
+			return Inferred::Unknown;
+		}
+
+		// later declarations replace existing impls with the same key, as at runtime
+		let mut impls: Vec<(Vec<Type>, Signature)> = Vec::new();
+		let declared = self
+			.declared_impls
+			.iter()
+			.filter(|(m, _)| Shared::ptr_eq(m, method))
+			.map(|(_, sig)| sig.clone());
+		for sig in method.supported_signatures().into_iter().chain(declared) {
+			let param_types = sig
+				.params
+				.iter()
+				.map(|p| p.param_type.clone())
+				.collect::<Vec<_>>();
+			impls.retain(|(p, _)| *p != param_types);
+			impls.push((param_types, sig));
+		}
+
+		// values of a union type dispatch on their concrete type at runtime, so
+		// every combination of members has to resolve
+		let mut return_types = Vec::new();
+		for types in expand_unions(dispatch_types) {
+			let candidates = impls.iter().map(|(p, sig)| (p.as_slice(), sig));
+			match select_by_specificity(candidates, &types) {
+				Ok(sig) => return_types.push(sig.return_type.clone()),
+				Err(ambiguous) if !ambiguous.is_empty() => {
+					self.report(
+						location,
+						format!(
+							"ambiguous call to method '{}' for ({}), candidates: {}",
+							method.name(),
+							type_list(&types),
+							ambiguous
+								.iter()
+								.map(|s| s.to_string())
+								.collect::<Vec<_>>()
+								.join(", ")
+						),
+					);
+					return Inferred::Unknown;
+				}
+				Err(_) if !method.has_default_impl() => {
+					self.report(
+						location,
+						format!(
+							"no impl of method '{}' for ({})",
+							method.name(),
+							type_list(&types)
+						),
+					);
+					return Inferred::Unknown;
+				}
+				Err(_) => return Inferred::Unknown,
+			}
+		}
+
+		Inferred::Typed(Type::union(return_types))
+	}
+
+	fn check_call(
+		&mut self,
+		scope: &mut Scope,
+		location: &SourceLocation,
+		target: &SyntaxNode,
+		positional: &[&SyntaxNode],
+		named: &[(String, SyntaxNode)],
+	) -> Inferred {
+		let target_val = self.expr(scope, target);
+		let sig = match &target_val {
+			Inferred::Known(Value::Builtin(b)) => b.signature().clone(),
+			Inferred::Known(Value::Function(f)) => f.signature().clone(),
+			Inferred::Known(Value::Method(m)) => m.signature().clone(),
+			Inferred::Function(sig) => sig.clone(),
+			// the callee could quote its args, as pipeline steps do with column
+			// expressions, so they're left unchecked
+			Inferred::Unknown => return Inferred::Unknown,
+			other => {
+				match other.static_type() {
+					Type::Any | Type::Function | Type::Builtin | Type::Method => (),
+					t => self.report(location, format!("{} is not callable", t.name())),
+				}
+				return Inferred::Unknown;
+			}
+		};
+
+		let num_expected = sig.params.len();
+		if positional.len() < num_expected
+			|| (sig.trailing_type.is_none() && positional.len() > num_expected)
+		{
+			self.report(
+				location,
+				format!(
+					"arg count mismatch: expected {}, got {}",
+					num_expected,
+					positional.len()
+				),
+			);
+			return Inferred::Unknown;
+		}
+
+		if !named.is_empty() && sig.named_trailing_type.is_none() {
+			self.report(location, "named args are not supported by this callable");
+			return Inferred::Unknown;
+		}
+
+		let mut arg_types = Vec::new();
+		for (i, arg) in positional.iter().enumerate() {
+			arg_types.push(self.check_arg(scope, positional_param_type(&sig, i), arg));
+		}
+
+		for (_, arg) in named {
+			let named_type = sig.named_trailing_type.as_ref().unwrap();
+			self.check_arg(scope, named_type, arg);
+		}
+
+		match target_val {
+			Inferred::Known(Value::Method(m)) => match self.resolve_method(location, &m, &arg_types) {
+				Inferred::Unknown => Inferred::Typed(sig.return_type),
+				resolved => resolved,
+			},
+			_ => Inferred::Typed(sig.return_type),
+		}
+	}
+
+	fn check_arg(&mut self, scope: &mut Scope, param_type: &Type, arg: &SyntaxNode) -> Type {
+		if *param_type == Type::SyntaxPlaceholder {
+			return Type::Syntax;
+		}
+
+		let arg_type = self.expr(scope, arg).static_type();
+		if !types_compatible(param_type, &arg_type) {
+			self.report(
+				&arg.location,
+				format!("type mismatch: expected {}, got {}", param_type, arg_type),
+			);
+		}
+		arg_type
+	}
+
+	fn check_method_call(
+		&mut self,
+		scope: &mut Scope,
+		location: &SourceLocation,
+		method: &Shared<Method>,
+		args: &[&SyntaxNode],
+	) -> Inferred {
+		let arg_types = args
+			.iter()
+			.map(|a| self.expr(scope, a).static_type())
+			.collect::<Vec<_>>();
+
+		self.method_return(location, method, &arg_types)
+	}
+
+	fn method_return(
+		&mut self,
+		location: &SourceLocation,
+		method: &Shared<Method>,
+		arg_types: &[Type],
+	) -> Inferred {
+		match self.resolve_method(location, method, arg_types) {
+			Inferred::Unknown => match &method.signature().return_type {
+				Type::Any => Inferred::Unknown,
+				t => Inferred::Typed(t.clone()),
+			},
+			resolved => resolved,
+		}
+	}
+
+	fn expect_type(&mut self, scope: &mut Scope, node: &SyntaxNode) -> Option<Type> {
+		let inferred = self.expr(scope, node);
+		if let Some(t) = inferred.as_type() {
+			return Some(t);
+		}
+
+		match inferred.static_type() {
+			Type::Any | Type::Type => (),
+			t => self.report(&node.location, format!("expected a type, got {}", t.name())),
+		}
+		None
+	}
+
+	fn function(
+		&mut self,
+		scope: &mut Scope,
+		header: &FunctionHeader<SyntaxNode>,
+		params: &[ParameterDef<SyntaxNode>],
+		return_type: &SyntaxNode,
+		body: &[SyntaxNode],
+	) -> Inferred {
+		let param_types = params
+			.iter()
+			.map(|p| self.expect_type(scope, &p.param_type).unwrap_or(Type::Any))
+			.collect::<Vec<_>>();
+		let return_type = self.expect_type(scope, return_type).unwrap_or(Type::Any);
+
+		let mut sig = Signature::returning(&return_type);
+		for (param, param_type) in params.iter().zip(&param_types) {
+			sig = sig.param(&param.name, param_type);
+		}
+
+		let mut body_scope = scope.clone();
+		for param in &sig.params {
+			body_scope.insert(
+				param.name.clone(),
+				Inferred::Typed(param.param_type.clone()),
+			);
+		}
+
+		let body_type = self.body(&mut body_scope, body).static_type();
+		if !types_compatible(&return_type, &body_type) {
+			let location = body
+				.last()
+				.map(|e| e.location.clone())
+				.unwrap_or(SourceLocation::Unknown);
+			self.report(
+				&location,
+				format!(
+					"return type mismatch: expected {}, got {}",
+					return_type, body_type
+				),
+			);
+		}
+
+		match header {
+			FunctionHeader::Function(Some(name)) => {
+				scope.insert(name.clone(), Inferred::Function(sig.clone()));
+			}
+			FunctionHeader::MethodImpl { impl_for } => match self.expr(scope, impl_for) {
+				Inferred::Known(Value::Method(m)) => self.declared_impls.push((m, sig.clone())),
+				Inferred::Unknown => (),
+				other => self.report(
+					&impl_for.location,
+					format!("expected a method, got {}", other.static_type().name()),
+				),
+			},
+			_ => (),
+		}
+
+		Inferred::Function(sig)
+	}
+
+	fn import(
+		&mut self,
+		scope: &mut Scope,
+		location: &SourceLocation,
+		from: &[String],
+		import: &Import,
+	) {
+		if let Import::Named(names) = import {
+			if let [name] = names.as_slice() {
+				let path = [from, std::slice::from_ref(name)].concat();
+				if is_module_path(self.ctx, &path) && self.ctx.modules.find(&path).is_some() {
+					scope.insert(name.clone(), Inferred::Typed(Type::Library));
+					return;
+				}
+			}
+		}
+
+		if is_module_path(self.ctx, from) {
+			self.import_module(scope, location, from, import);
+			return;
+		}
+
+		let mut lib_env = self.ctx.library_env.clone();
+		for name in from {
+			match lib_env.get(name) {
+				Some(Value::Library(env)) => lib_env = env,
+				_ => {
+					self.report(location, format!("unknown library: {}", name));
+					return;
+				}
+			}
+		}
+
+		match import {
+			Import::Named(names) => {
+				for name in names {
+					match lib_env.get(name) {
+						Some(val) => {
+							scope.insert(name.clone(), Inferred::Known(val));
+						}
+						None => self.report(location, format!("not found: {}", name)),
+					}
+				}
+			}
+			Import::Wildcard => {
+				for (name, val) in lib_env.bindings() {
+					scope.insert(name, Inferred::Known(val));
+				}
+			}
+		}
+	}
+
+	// modules are only parsed here, running them is left to evaluation, so
+	// their exports are known by name but not by type
+	fn import_module(
+		&mut self,
+		scope: &mut Scope,
+		location: &SourceLocation,
+		path: &[String],
+		import: &Import,
+	) {
+		let exports = match self.ctx.modules.exported_names(self.ctx, path) {
+			Some(exports) => exports,
+			None => {
+				self.report(location, format!("unknown library: {}", path.join("::")));
+				return;
+			}
+		};
+
+		let names = match import {
+			Import::Named(names) => names.clone(),
+			Import::Wildcard => exports.clone(),
+		};
+
+		for name in names {
+			if exports.contains(&name) {
+				scope.insert(name, Inferred::Unknown);
+			} else {
+				self.report(location, format!("not found: {}", name));
+			}
+		}
+	}
+
+	fn expr(&mut self, scope: &mut Scope, node: &SyntaxNode) -> Inferred {
+		let location = &node.location;
+		match &node.syntax {
+			Syntax::Null => Inferred::Typed(Type::Null),
+			Syntax::Int(_) => Inferred::Typed(Type::Int),
+			Syntax::Float(_) => Inferred::Typed(Type::Float),
+			Syntax::Bool(_) => Inferred::Typed(Type::Bool),
+			Syntax::String(_) => Inferred::Typed(Type::String),
+			Syntax::Ident(name) => match scope.get(name) {
+				Some(inferred) => inferred.clone(),
+				None => {
+					self.report(location, format!("unknown identifier: {}", name));
+					Inferred::Unknown
+				}
+			},
+			Syntax::Interpolate(_) => {
+				self.report(location, "interpolation not supported in regular code");
+				Inferred::Unknown
+			}
+			Syntax::Export(target) => {
+				if target.defined_name().is_none() {
+					self.report(location, "pub requires a named definition");
+				}
+				self.expr(scope, target)
+			}
+			Syntax::Use { from, import } => {
+				self.import(scope, location, from, import);
+				Inferred::Typed(Type::Null)
+			}
+			Syntax::BinaryOp { op, lhs, rhs } => match op {
+				BinaryOperator::LAssign | BinaryOperator::RAssign => {
+					let (dest, src) = match op {
+						BinaryOperator::LAssign => (lhs, rhs),
+						_ => (rhs, lhs),
+					};
+					let inferred = self.expr(scope, src);
+					match &dest.syntax {
+						Syntax::Ident(name) => {
+							scope.insert(name.clone(), inferred.clone());
+						}
+						_ => self.report(&dest.location, "assignment requires an identifier"),
+					}
+					inferred
+				}
+				BinaryOperator::Access => {
+					let rhs_name = match &rhs.syntax {
+						Syntax::Ident(name) => name,
+						_ => {
+							self.report(&rhs.location, "access operator requires an identifier");
+							return Inferred::Unknown;
+						}
+					};
+
+					match self.expr(scope, lhs) {
+						Inferred::Known(Value::Library(env)) => match env.get(rhs_name) {
+							Some(val) => Inferred::Known(val),
+							None => {
+								self.report(&rhs.location, format!("not found: {}", rhs_name));
+								Inferred::Unknown
+							}
+						},
+						Inferred::Unknown => Inferred::Unknown,
+						other => {
+							match other.static_type() {
+								Type::Any | Type::Library => (),
+								t => self.report(
+									&lhs.location,
+									format!("access operator requires a library, got {}", t.name()),
+								),
+							}
+							Inferred::Unknown
+						}
+					}
+				}
+				BinaryOperator::Pipe => match &rhs.syntax {
+					Syntax::Call {
+						target,
+						positional_args,
+						named_args,
+					} => {
+						let positional = std::iter::once(&**lhs)
+							.chain(positional_args)
+							.collect::<Vec<_>>();
+						self.check_call(scope, &rhs.location, target, &positional, named_args)
+					}
+					_ => {
+						self.report(
+							&rhs.location,
+							"right-hand side of a pipe expression must be a call",
+						);
+						Inferred::Unknown
+					}
+				},
+				BinaryOperator::Or => {
+					let lhs_val = self.expr(scope, lhs);
+					let rhs_val = self.expr(scope, rhs);
+
+					// keep unions of known types precise so they can be used in signatures
+					if let (Some(a), Some(b)) = (lhs_val.as_type(), rhs_val.as_type()) {
+						return Inferred::Known(Value::Type(Type::union(vec![a, b])));
+					}
+
+					let method = self.ctx.methods.binops[op].clone();
+					let arg_types = [lhs_val.static_type(), rhs_val.static_type()];
+					self.method_return(location, &method, &arg_types)
+				}
+				_ => {
+					let method = self.ctx.methods.binops[op].clone();
+					self.check_method_call(scope, location, &method, &[lhs, rhs])
+				}
+			},
+			Syntax::UnaryOp { op, target } => {
+				let method = self.ctx.methods.unops[op].clone();
+				self.check_method_call(scope, location, &method, &[target])
+			}
+			Syntax::Function {
+				header,
+				params,
+				return_type,
+				body,
+				..
+			} => self.function(scope, header, params, return_type, body),
+			Syntax::Call {
+				target,
+				positional_args,
+				named_args,
+			} => {
+				let positional = positional_args.iter().collect::<Vec<_>>();
+				self.check_call(scope, location, target, &positional, named_args)
+			}
+			Syntax::Switch { target, cases } => {
+				self.expr(scope, target);
+				// at most one case runs, so each is checked from the scope before them
+				// and what they assign merged afterwards
+				let mut case_types = Vec::new();
+				let mut case_scopes = Vec::new();
+				let mut assigned = Vec::new();
+				for case in cases {
+					self.expr(scope, &case.expr);
+					let mut case_scope = scope.clone();
+					case_types.push(self.expr(&mut case_scope, &case.returns).static_type());
+					case_scopes.push(case_scope);
+					collect_assigned_names(&case.returns, &mut assigned);
+				}
+				for name in assigned {
+					let merged = merge_inferred(
+						std::iter::once(&*scope)
+							.chain(&case_scopes)
+							.map(|s| s.get(&name)),
+					);
+					scope.insert(name, merged);
+				}
+
+				match case_types.first() {
+					Some(first) if case_types.iter().all(|t| t == first) => Inferred::Typed(first.clone()),
+					_ => Inferred::Unknown,
+				}
+			}
+			Syntax::Index { target, keys } => {
+				let method = self.ctx.methods.index.clone();
+				let args = std::iter::once(&**target).chain(keys).collect::<Vec<_>>();
+				self.check_method_call(scope, location, &method, &args)
+			}
+			Syntax::GenericInstantiation { target, type_args } => {
+				let target_val = self.expr(scope, target);
+				let types = type_args
+					.iter()
+					.map(|a| self.expect_type(scope, a))
+					.collect::<Option<Vec<_>>>();
+
+				match (target_val.as_type(), types) {
+					(Some(Type::Native(d)), Some(types)) => match d.generic_lookup {
+						Some(lookup) => match lookup(self.ctx, &types) {
+							Ok(t) => Inferred::Known(Value::Type(t)),
+							Err(ex) => {
+								self.report(&target.location, ex.message);
+								Inferred::Unknown
+							}
+						},
+						None => {
+							self.report(&target.location, "native type is not generic");
+							Inferred::Unknown
+						}
+					},
+					(Some(_), _) => {
+						self.report(&target.location, "unsupported type for generics");
+						Inferred::Unknown
+					}
+					_ => Inferred::Unknown,
+				}
+			}
+			Syntax::TypeAlias { name, target } => {
+				let inferred = match self.expect_type(scope, target) {
+					Some(t) => Inferred::Known(Value::Type(t)),
+					None => Inferred::Typed(Type::Type),
+				};
+				scope.insert(name.clone(), inferred.clone());
+				inferred
+			}
+			Syntax::Interface { name, methods } => {
+				let mut required = Some(Vec::new());
+				for method in methods {
+					match self.expr(scope, method) {
+						Inferred::Known(Value::Method(m)) => {
+							if let Some(required) = &mut required {
+								required.push(m);
+							}
+						}
+						Inferred::Unknown => required = None,
+						other => {
+							match other.static_type() {
+								Type::Any | Type::Method => (),
+								t => self.report(
+									&method.location,
+									format!("interfaces can only require methods, got {}", t),
+								),
+							}
+							required = None;
+						}
+					}
+				}
+
+				let inferred = match required {
+					Some(methods) => Inferred::Known(Value::Type(Type::Interface(Shared::new(Interface {
+						name: name.clone(),
+						methods,
+					})))),
+					None => Inferred::Typed(Type::Type),
+				};
+				scope.insert(name.clone(), inferred.clone());
+				inferred
+			}
+		}
+	}
+
+	fn body(&mut self, scope: &mut Scope, exprs: &[SyntaxNode]) -> Inferred {
+		let mut ret = Inferred::Typed(Type::Null);
+		for expr in exprs {
+			ret = self.expr(scope, expr);
+		}
+		ret
+	}
+}
+
+pub fn check(ctx: &EvalContext, exprs: &[SyntaxNode]) -> Vec<Diagnostic> {
+	let mut scope = ctx
+		.env
+		.bindings()
+		.into_iter()
+		.map(|(name, val)| (name, Inferred::Known(val)))
+		.collect::<Scope>();
+
+	let mut checker = Checker {
+		ctx,
+		diagnostics: Vec::new(),
+		declared_impls: Vec::new(),
+	};
+
+	checker.body(&mut scope, exprs);
+	checker.diagnostics
+}
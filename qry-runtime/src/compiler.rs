@@ -0,0 +1,518 @@
+use super::{function_name, Shared, Value};
+use qry_lang::syntax::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+	Constant(usize),
+	Pop,
+	Dup,
+	LoadLocal(usize),
+	StoreLocal(usize),
+	LoadName(usize),
+	StoreName(usize),
+	Access(usize),
+	Call(usize),
+	BinaryOp(BinaryOperator),
+	UnaryOp(UnaryOperator),
+	Index(usize),
+	SwitchCase,
+	Jump(usize),
+	JumpIfFalse(usize),
+	Import(usize),
+	Export(usize),
+	ExpectType,
+	MakeFunction(usize),
+	Instantiate(usize),
+	MakeInterface { name: usize, num_methods: usize },
+	Fail(&'static str),
+}
+
+#[derive(Debug)]
+pub struct CallArg {
+	pub syntax: SyntaxNode,
+	pub chunk: Chunk,
+}
+
+#[derive(Debug)]
+pub struct CallSite {
+	pub positional: Vec<CallArg>,
+	pub named: Vec<(String, CallArg)>,
+}
+
+#[derive(Debug)]
+pub struct FunctionPrototype {
+	pub name: String,
+	pub is_method_impl: bool,
+	pub param_names: Vec<String>,
+	pub body: Vec<SyntaxNode>,
+	pub compiled: Shared<CompiledBody>,
+	pub doc: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ImportSpec {
+	pub from: Vec<String>,
+	pub import: Import,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+	pub code: Vec<Op>,
+	pub locations: Vec<SourceLocation>,
+	pub constants: Vec<Value>,
+	pub names: Vec<String>,
+	pub call_sites: Vec<CallSite>,
+	pub imports: Vec<ImportSpec>,
+	pub functions: Vec<FunctionPrototype>,
+}
+
+#[derive(Debug)]
+pub struct CompiledBody {
+	pub chunk: Chunk,
+	pub locals: Vec<String>,
+	pub num_params: usize,
+}
+
+struct Compiler {
+	chunk: Chunk,
+	locals: Option<Shared<HashMap<String, usize>>>,
+}
+
+pub(crate) fn collect_assigned_names(node: &SyntaxNode, names: &mut Vec<String>) {
+	let mut add = |name: &str| {
+		if !names.iter().any(|n| n == name) {
+			names.push(name.to_string());
+		}
+	};
+
+	match &node.syntax {
+		Syntax::BinaryOp { op, lhs, rhs } => {
+			match (op, &lhs.syntax, &rhs.syntax) {
+				(BinaryOperator::LAssign, Syntax::Ident(name), _) => add(name),
+				(BinaryOperator::RAssign, _, Syntax::Ident(name)) => add(name),
+				_ => (),
+			}
+			collect_assigned_names(lhs, names);
+			collect_assigned_names(rhs, names);
+		}
+		Syntax::Function {
+			header,
+			params,
+			return_type,
+			..
+		} => {
+			match header {
+				FunctionHeader::Function(Some(name)) => add(name),
+				FunctionHeader::MethodImpl { impl_for } => collect_assigned_names(impl_for, names),
+				_ => (),
+			}
+			for param in params {
+				collect_assigned_names(&param.param_type, names);
+			}
+			collect_assigned_names(return_type, names);
+		}
+		Syntax::TypeAlias { name, target } => {
+			add(name);
+			collect_assigned_names(target, names);
+		}
+		Syntax::Interface { name, methods } => {
+			add(name);
+			for method in methods {
+				collect_assigned_names(method, names);
+			}
+		}
+		Syntax::UnaryOp { target, .. } | Syntax::Interpolate(target) | Syntax::Export(target) => {
+			collect_assigned_names(target, names)
+		}
+		Syntax::Call {
+			target,
+			positional_args,
+			named_args,
+		} => {
+			collect_assigned_names(target, names);
+			for arg in positional_args {
+				collect_assigned_names(arg, names);
+			}
+			for (_, arg) in named_args {
+				collect_assigned_names(arg, names);
+			}
+		}
+		Syntax::Switch { target, cases } => {
+			collect_assigned_names(target, names);
+			for case in cases {
+				collect_assigned_names(&case.expr, names);
+				collect_assigned_names(&case.returns, names);
+			}
+		}
+		Syntax::Index { target, keys } => {
+			collect_assigned_names(target, names);
+			for key in keys {
+				collect_assigned_names(key, names);
+			}
+		}
+		Syntax::GenericInstantiation { target, type_args } => {
+			collect_assigned_names(target, names);
+			for arg in type_args {
+				collect_assigned_names(arg, names);
+			}
+		}
+		Syntax::Null
+		| Syntax::Int(_)
+		| Syntax::Float(_)
+		| Syntax::Bool(_)
+		| Syntax::String(_)
+		| Syntax::Ident(_)
+		| Syntax::Use { .. } => (),
+	}
+}
+
+impl Compiler {
+	fn new(locals: Option<Shared<HashMap<String, usize>>>) -> Self {
+		Self {
+			chunk: Chunk::default(),
+			locals,
+		}
+	}
+
+	fn emit(&mut self, op: Op, location: &SourceLocation) -> usize {
+		self.chunk.code.push(op);
+		self.chunk.locations.push(location.clone());
+		self.chunk.code.len() - 1
+	}
+
+	fn patch_jump(&mut self, index: usize) {
+		let target = self.chunk.code.len();
+		self.chunk.code[index] = match self.chunk.code[index] {
+			Op::Jump(_) => Op::Jump(target),
+			Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+			op => panic!("not a jump: {:?}", op),
+		};
+	}
+
+	fn constant(&mut self, value: Value, location: &SourceLocation) {
+		self.chunk.constants.push(value);
+		let index = self.chunk.constants.len() - 1;
+		self.emit(Op::Constant(index), location);
+	}
+
+	fn name_index(&mut self, name: &str) -> usize {
+		match self.chunk.names.iter().position(|n| n == name) {
+			Some(index) => index,
+			None => {
+				self.chunk.names.push(name.to_string());
+				self.chunk.names.len() - 1
+			}
+		}
+	}
+
+	fn local_slot(&self, name: &str) -> Option<usize> {
+		self.locals.as_ref().and_then(|l| l.get(name).copied())
+	}
+
+	fn load(&mut self, name: &str, location: &SourceLocation) {
+		match self.local_slot(name) {
+			Some(slot) => self.emit(Op::LoadLocal(slot), location),
+			None => {
+				let index = self.name_index(name);
+				self.emit(Op::LoadName(index), location)
+			}
+		};
+	}
+
+	fn store(&mut self, name: &str, location: &SourceLocation) {
+		match self.local_slot(name) {
+			Some(slot) => self.emit(Op::StoreLocal(slot), location),
+			None => {
+				let index = self.name_index(name);
+				self.emit(Op::StoreName(index), location)
+			}
+		};
+	}
+
+	fn arg(&self, syntax: &SyntaxNode) -> CallArg {
+		let mut compiler = Compiler::new(self.locals.clone());
+		compiler.expr(syntax);
+		CallArg {
+			syntax: syntax.clone(),
+			chunk: compiler.chunk,
+		}
+	}
+
+	fn call(
+		&mut self,
+		location: &SourceLocation,
+		target: &SyntaxNode,
+		piped: Option<&SyntaxNode>,
+		positional_args: &[SyntaxNode],
+		named_args: &[(String, SyntaxNode)],
+	) {
+		self.expr(target);
+
+		let site = CallSite {
+			positional: piped
+				.into_iter()
+				.chain(positional_args)
+				.map(|a| self.arg(a))
+				.collect(),
+			named: named_args
+				.iter()
+				.map(|(n, a)| (n.clone(), self.arg(a)))
+				.collect(),
+		};
+
+		self.chunk.call_sites.push(site);
+		let index = self.chunk.call_sites.len() - 1;
+		self.emit(Op::Call(index), location);
+	}
+
+	fn assign(&mut self, location: &SourceLocation, dest: &SyntaxNode, src: &SyntaxNode) {
+		match &dest.syntax {
+			Syntax::Ident(name) => {
+				self.expr(src);
+				self.store(name, &dest.location);
+			}
+			_ => {
+				self.emit(Op::Fail("assignment requires an identifier"), location);
+			}
+		}
+	}
+
+	fn binop(&mut self, node: &SyntaxNode, lhs: &SyntaxNode, rhs: &SyntaxNode, op: BinaryOperator) {
+		match op {
+			BinaryOperator::LAssign => self.assign(&lhs.location, lhs, rhs),
+			BinaryOperator::RAssign => self.assign(&rhs.location, rhs, lhs),
+			BinaryOperator::Access => match &rhs.syntax {
+				Syntax::Ident(name) => {
+					self.expr(lhs);
+					let index = self.name_index(name);
+					self.emit(Op::Access(index), &rhs.location);
+				}
+				_ => {
+					self.emit(
+						Op::Fail("access operator requires an identifier"),
+						&rhs.location,
+					);
+				}
+			},
+			BinaryOperator::Pipe => match &rhs.syntax {
+				Syntax::Call {
+					target,
+					positional_args,
+					named_args,
+				} => self.call(
+					&rhs.location,
+					target,
+					Some(lhs),
+					positional_args,
+					named_args,
+				),
+				_ => {
+					self.emit(
+						Op::Fail("right-hand side of a pipe expression must be a call"),
+						&rhs.location,
+					);
+				}
+			},
+			_ => {
+				self.expr(lhs);
+				self.expr(rhs);
+				self.emit(Op::BinaryOp(op), &node.location);
+			}
+		}
+	}
+
+	fn function(
+		&mut self,
+		node: &SyntaxNode,
+		header: &FunctionHeader<SyntaxNode>,
+		params: &[ParameterDef<SyntaxNode>],
+		return_type: &SyntaxNode,
+		body: &[SyntaxNode],
+		doc: &Option<String>,
+	) {
+		for param in params {
+			self.expr(&param.param_type);
+			self.emit(Op::ExpectType, &param.param_type.location);
+		}
+
+		self.expr(return_type);
+		self.emit(Op::ExpectType, &return_type.location);
+
+		if let FunctionHeader::MethodImpl { impl_for } = header {
+			self.expr(impl_for);
+		}
+
+		let param_names = params.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+		self.chunk.functions.push(FunctionPrototype {
+			name: function_name(header).into(),
+			is_method_impl: matches!(header, FunctionHeader::MethodImpl { .. }),
+			compiled: Shared::new(compile_function(&param_names, body)),
+			param_names,
+			body: body.to_vec(),
+			doc: doc.clone(),
+		});
+
+		let index = self.chunk.functions.len() - 1;
+		self.emit(Op::MakeFunction(index), &node.location);
+
+		if let FunctionHeader::Function(Some(name)) = header {
+			self.store(name, &node.location);
+		}
+	}
+
+	fn expr(&mut self, node: &SyntaxNode) {
+		let location = &node.location;
+		match &node.syntax {
+			Syntax::Null => self.constant(Value::Null(()), location),
+			Syntax::Int(val) => self.constant(Value::Int(*val), location),
+			Syntax::Float(val) => self.constant(Value::Float(*val), location),
+			Syntax::Bool(val) => self.constant(Value::Bool(*val), location),
+			Syntax::String(val) => self.constant(Value::String(val.clone().into_boxed_str()), location),
+			Syntax::Ident(name) => self.load(name, location),
+			Syntax::Interpolate(_) => {
+				self.emit(
+					Op::Fail("interpolation not supported in regular code"),
+					location,
+				);
+			}
+			Syntax::BinaryOp { lhs, rhs, op } => self.binop(node, lhs, rhs, *op),
+			Syntax::UnaryOp { target, op } => {
+				self.expr(target);
+				self.emit(Op::UnaryOp(*op), location);
+			}
+			Syntax::Export(target) => match target.defined_name() {
+				Some(name) => {
+					self.expr(target);
+					let name_index = self.name_index(name);
+					self.emit(Op::Export(name_index), location);
+				}
+				None => {
+					self.emit(Op::Fail("pub requires a named definition"), location);
+				}
+			},
+			Syntax::Use { from, import } => {
+				self.chunk.imports.push(ImportSpec {
+					from: from.clone(),
+					import: import.clone(),
+				});
+				let index = self.chunk.imports.len() - 1;
+				self.emit(Op::Import(index), location);
+			}
+			Syntax::Function {
+				header,
+				params,
+				return_type,
+				body,
+				doc,
+			} => self.function(node, header, params, return_type, body, doc),
+			Syntax::Call {
+				target,
+				positional_args,
+				named_args,
+			} => self.call(location, target, None, positional_args, named_args),
+			Syntax::Switch { target, cases } => {
+				self.expr(target);
+
+				let mut end_jumps = Vec::new();
+				for case in cases {
+					self.emit(Op::Dup, &case.expr.location);
+					self.expr(&case.expr);
+					self.emit(Op::SwitchCase, &case.expr.location);
+					let next_case = self.emit(Op::JumpIfFalse(0), &case.expr.location);
+					self.emit(Op::Pop, &case.returns.location);
+					self.expr(&case.returns);
+					end_jumps.push(self.emit(Op::Jump(0), &case.returns.location));
+					self.patch_jump(next_case);
+				}
+
+				self.emit(Op::Pop, location);
+				self.constant(Value::Null(()), location);
+
+				for jump in end_jumps {
+					self.patch_jump(jump);
+				}
+			}
+			Syntax::Index { target, keys } => {
+				self.expr(target);
+				for key in keys {
+					self.expr(key);
+				}
+				self.emit(Op::Index(keys.len()), location);
+			}
+			Syntax::GenericInstantiation { target, type_args } => {
+				self.expr(target);
+				for arg in type_args {
+					self.expr(arg);
+					self.emit(Op::ExpectType, &arg.location);
+				}
+				self.emit(Op::Instantiate(type_args.len()), &target.location);
+			}
+			Syntax::TypeAlias { name, target } => {
+				self.expr(target);
+				self.emit(Op::ExpectType, &target.location);
+				self.store(name, location);
+			}
+			Syntax::Interface { name, methods } => {
+				for method in methods {
+					self.expr(method);
+				}
+				let name_index = self.name_index(name);
+				self.emit(
+					Op::MakeInterface {
+						name: name_index,
+						num_methods: methods.len(),
+					},
+					location,
+				);
+				self.store(name, location);
+			}
+		}
+	}
+
+	fn body(&mut self, exprs: &[SyntaxNode]) {
+		if exprs.is_empty() {
+			self.constant(Value::Null(()), &SourceLocation::Unknown);
+		}
+
+		for (i, expr) in exprs.iter().enumerate() {
+			if i > 0 {
+				self.emit(Op::Pop, &expr.location);
+			}
+			self.expr(expr);
+		}
+	}
+}
+
+fn compile_function(param_names: &[String], body: &[SyntaxNode]) -> CompiledBody {
+	let mut locals = param_names.to_vec();
+	for expr in body {
+		collect_assigned_names(expr, &mut locals);
+	}
+
+	let slots = locals
+		.iter()
+		.enumerate()
+		.map(|(i, n)| (n.clone(), i))
+		.collect::<HashMap<_, _>>();
+
+	let mut compiler = Compiler::new(Some(Shared::new(slots)));
+	compiler.body(body);
+
+	CompiledBody {
+		chunk: compiler.chunk,
+		locals,
+		num_params: param_names.len(),
+	}
+}
+
+pub fn compile(exprs: &[SyntaxNode]) -> CompiledBody {
+	let mut compiler = Compiler::new(None);
+	compiler.body(exprs);
+
+	CompiledBody {
+		chunk: compiler.chunk,
+		locals: Vec::new(),
+		num_params: 0,
+	}
+}
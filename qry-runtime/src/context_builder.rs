@@ -0,0 +1,171 @@
+use super::{
+	stdlib, Capability, Debugger, Environment, EvalContext, EvalLimits, ExecutionBudget, Manifest,
+	ModuleLoader, Observer, OutputSink, Profiler, RuntimeMethods, Sandbox, Shared, StdoutSink, Value,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+type LibraryInit = Box<dyn FnOnce(&RuntimeMethods) -> Shared<Environment>>;
+type StdlibInit = fn(&RuntimeMethods) -> Shared<Environment>;
+
+#[derive(Default)]
+pub struct EvalContextBuilder {
+	excluded_libraries: HashSet<String>,
+	host_libraries: Vec<LibraryInit>,
+	sandbox: Sandbox,
+	limits: EvalLimits,
+	module_path: Vec<PathBuf>,
+	packages: Vec<(String, Vec<PathBuf>)>,
+	connections: HashMap<String, String>,
+	debugger: Option<Shared<Debugger>>,
+	profiler: Option<Shared<Profiler>>,
+	observer: Option<Shared<dyn Observer>>,
+	output_sink: Option<Shared<dyn OutputSink>>,
+}
+
+impl EvalContextBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// ops can't be left out since the evaluators dispatch operators through it
+	pub fn without_library(mut self, name: &str) -> Self {
+		self.excluded_libraries.insert(name.to_string());
+		self
+	}
+
+	// host libraries get the runtime methods so they can add impls for their
+	// own native types, like the data library does
+	pub fn with_library<F>(mut self, init: F) -> Self
+	where
+		F: FnOnce(&RuntimeMethods) -> Shared<Environment> + 'static,
+	{
+		self.host_libraries.push(Box::new(init));
+		self
+	}
+
+	pub fn deny(mut self, capability: Capability) -> Self {
+		self.sandbox.denied.insert(capability);
+		self
+	}
+
+	pub fn allow_connections(mut self, targets: &[&str]) -> Self {
+		let allowed = self
+			.sandbox
+			.allowed_connections
+			.get_or_insert_with(Vec::new);
+		allowed.extend(targets.iter().map(|t| t.to_string()));
+		self
+	}
+
+	// the host keeps its own handle to the debugger to set breakpoints
+	pub fn debugger(mut self, debugger: Shared<Debugger>) -> Self {
+		self.debugger = Some(debugger);
+		self
+	}
+
+	pub fn profiler(mut self, profiler: Shared<Profiler>) -> Self {
+		self.profiler = Some(profiler);
+		self
+	}
+
+	pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+		self.observer = Some(Shared::new(observer));
+		self
+	}
+
+	// defaults to StdoutSink
+	pub fn output(mut self, sink: impl OutputSink + 'static) -> Self {
+		self.output_sink = Some(Shared::new(sink));
+		self
+	}
+
+	pub fn limits(mut self, limits: EvalLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	// directories searched, in order, for modules imported with `use`
+	pub fn module_path(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.module_path.push(dir.into());
+		self
+	}
+
+	// a named target scripts can open with data::connect(name)
+	pub fn connection(mut self, name: &str, target: &str) -> Self {
+		self
+			.connections
+			.insert(name.to_string(), target.to_string());
+		self
+	}
+
+	pub fn manifest(mut self, manifest: &Manifest) -> Self {
+		for dir in manifest.search_path() {
+			self = self.module_path(dir);
+		}
+		for package in &manifest.packages {
+			self
+				.packages
+				.push((package.name.clone(), package.search_path.clone()));
+		}
+		for (name, target) in manifest.connection_targets() {
+			self = self.connection(&name, &target);
+		}
+		self
+	}
+
+	pub fn build(self) -> EvalContext {
+		let global_env_ptr = Environment::new("global");
+		let library_env_ptr = Environment::new("libraries");
+
+		let add_lib = |env: Shared<Environment>, add_to_global| {
+			let lib_val = Value::Library(env.clone());
+			library_env_ptr.update(env.name(), lib_val.clone());
+
+			if add_to_global {
+				env.copy_to(&global_env_ptr);
+			}
+
+			global_env_ptr.update(env.name(), lib_val);
+		};
+
+		let (ops_methods, ops_env) = stdlib::ops::create();
+		add_lib(ops_env, false);
+
+		let stdlib_libraries: &[(&str, StdlibInit, bool)] = &[
+			("core", stdlib::core::env, true),
+			("data", stdlib::data::env, false),
+		];
+
+		for (name, init, add_to_global) in stdlib_libraries {
+			if !self.excluded_libraries.contains(*name) {
+				add_lib(init(&ops_methods), *add_to_global);
+			}
+		}
+
+		for init in self.host_libraries {
+			add_lib(init(&ops_methods), false);
+		}
+
+		let prelude = global_env_ptr.child("prelude");
+		let modules = ModuleLoader::new(self.module_path, prelude);
+		for (name, search_path) in self.packages {
+			modules.add_package(&name, search_path);
+		}
+
+		EvalContext {
+			env: global_env_ptr,
+			library_env: library_env_ptr,
+			methods: Shared::new(ops_methods),
+			callstack: Shared::default(),
+			budget: Shared::new(ExecutionBudget::new(self.limits)),
+			sandbox: Shared::new(self.sandbox),
+			modules: Shared::new(modules),
+			connections: Shared::new(self.connections),
+			debugger: self.debugger,
+			profiler: self.profiler,
+			observer: self.observer,
+			output_sink: self.output_sink.unwrap_or_else(|| Shared::new(StdoutSink)),
+		}
+	}
+}
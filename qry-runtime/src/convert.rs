@@ -0,0 +1,206 @@
+use super::{
+	BigInt, BuiltinClosure, Decimal, EvalContext, EvalResult, Exception, List, MaybeSync, NativeType,
+	Shared, Signature, Type, Value,
+};
+use qry_lang::SourceLocation;
+
+// conversions used by typed builtins, so a host function can take an i64
+// instead of unpacking args[0] itself
+pub trait FromValue: Sized {
+	fn param_type() -> Type;
+	fn from_value(ctx: &EvalContext, value: &Value) -> EvalResult<Self>;
+}
+
+pub trait IntoValue {
+	fn return_type() -> Type;
+	fn into_value(self) -> Value;
+}
+
+// what a typed builtin can return: a plain value, a Result with a message
+// that becomes an exception, or an EvalResult from calling back into the
+// runtime
+pub trait IntoReturn {
+	fn return_type() -> Type;
+	fn into_return(self, ctx: &EvalContext) -> EvalResult<Value>;
+}
+
+impl<T: IntoValue> IntoReturn for T {
+	fn return_type() -> Type {
+		T::return_type()
+	}
+
+	fn into_return(self, _: &EvalContext) -> EvalResult<Value> {
+		Ok(self.into_value())
+	}
+}
+
+impl<T: IntoValue> IntoReturn for Result<T, String> {
+	fn return_type() -> Type {
+		T::return_type()
+	}
+
+	fn into_return(self, ctx: &EvalContext) -> EvalResult<Value> {
+		self
+			.map(IntoValue::into_value)
+			.map_err(|message| ctx.exception(&SourceLocation::Unknown, message))
+	}
+}
+
+impl<T: IntoValue> IntoReturn for Result<T, Exception> {
+	fn return_type() -> Type {
+		T::return_type()
+	}
+
+	fn into_return(self, _: &EvalContext) -> EvalResult<Value> {
+		self.map(IntoValue::into_value)
+	}
+}
+
+macro_rules! value_conversions {
+	($rust_type: ty, $qry_type: expr, $variant: ident) => {
+		impl FromValue for $rust_type {
+			fn param_type() -> Type {
+				$qry_type
+			}
+
+			fn from_value(ctx: &EvalContext, value: &Value) -> EvalResult<Self> {
+				match value {
+					Value::$variant(v) => Ok(v.clone().into()),
+					_ => Err(ctx.exception(
+						&SourceLocation::Unknown,
+						format!("expected {}, got {}", $qry_type, value.runtime_type()),
+					)),
+				}
+			}
+		}
+
+		impl IntoValue for $rust_type {
+			fn return_type() -> Type {
+				$qry_type
+			}
+
+			fn into_value(self) -> Value {
+				Value::$variant(self.into())
+			}
+		}
+	};
+}
+
+value_conversions!(i64, Type::Int, Int);
+value_conversions!(f64, Type::Float, Float);
+value_conversions!(bool, Type::Bool, Bool);
+value_conversions!(String, Type::String, String);
+value_conversions!(BigInt, Type::BigInt, BigInt);
+value_conversions!(Decimal, Type::Decimal, Decimal);
+value_conversions!(Vec<Value>, Type::List, List);
+value_conversions!(List, Type::List, List);
+
+impl FromValue for Value {
+	fn param_type() -> Type {
+		Type::Any
+	}
+
+	fn from_value(_: &EvalContext, value: &Value) -> EvalResult<Self> {
+		Ok(value.clone())
+	}
+}
+
+impl IntoValue for Value {
+	fn return_type() -> Type {
+		Type::Any
+	}
+
+	fn into_value(self) -> Value {
+		self
+	}
+}
+
+impl IntoValue for () {
+	fn return_type() -> Type {
+		Type::Null
+	}
+
+	fn into_value(self) -> Value {
+		Value::Null(())
+	}
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+	fn param_type() -> Type {
+		Type::union(vec![T::param_type(), Type::Null])
+	}
+
+	fn from_value(ctx: &EvalContext, value: &Value) -> EvalResult<Self> {
+		match value {
+			Value::Null(_) => Ok(None),
+			_ => T::from_value(ctx, value).map(Some),
+		}
+	}
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+	fn return_type() -> Type {
+		Type::union(vec![T::return_type(), Type::Null])
+	}
+
+	fn into_value(self) -> Value {
+		match self {
+			Some(v) => v.into_value(),
+			None => Value::Null(()),
+		}
+	}
+}
+
+impl<T: 'static + NativeType> FromValue for Shared<T> {
+	fn param_type() -> Type {
+		Type::new_native::<T>()
+	}
+
+	fn from_value(ctx: &EvalContext, value: &Value) -> EvalResult<Self> {
+		value.as_native::<T>(ctx)
+	}
+}
+
+// a Rust function whose signature can be read off its argument and return
+// types. Params are positional, so they're just named by index
+pub trait IntoBuiltin<Args> {
+	fn signature() -> Signature;
+	fn into_closure(self) -> BuiltinClosure;
+}
+
+macro_rules! into_builtin {
+	($($arg: ident),*) => {
+		impl<F, R, $($arg,)*> IntoBuiltin<($($arg,)*)> for F
+		where
+			F: Fn($($arg),*) -> R + MaybeSync + 'static,
+			R: IntoReturn,
+			$($arg: FromValue,)*
+		{
+			fn signature() -> Signature {
+				let param_types: Vec<Type> = vec![$($arg::param_type()),*];
+				param_types
+					.iter()
+					.enumerate()
+					.fold(Signature::returning(&R::return_type()), |sig, (i, t)| {
+						sig.param(&format!("arg{}", i), t)
+					})
+			}
+
+			#[allow(non_snake_case, unused_variables, unused_mut)]
+			fn into_closure(self) -> BuiltinClosure {
+				Shared::new(move |ctx, args, _| {
+					let mut args = args.iter();
+					$(let $arg = $arg::from_value(ctx, args.next().unwrap())?;)*
+					self($($arg),*).into_return(ctx)
+				})
+			}
+		}
+	};
+}
+
+into_builtin!();
+into_builtin!(A);
+into_builtin!(A, B);
+into_builtin!(A, B, C);
+into_builtin!(A, B, C, D);
+into_builtin!(A, B, C, D, E);
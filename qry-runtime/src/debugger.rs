@@ -0,0 +1,187 @@
+use super::{EvalContext, Lock, MaybeSync, Shared};
+use qry_lang::SourceLocation;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugCommand {
+	Continue,
+	StepIn,
+	StepOver,
+	StepOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PauseReason {
+	Entry,
+	Breakpoint,
+	Step,
+	Pause,
+}
+
+impl PauseReason {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Entry => "entry",
+			Self::Breakpoint => "breakpoint",
+			Self::Step => "step",
+			Self::Pause => "pause",
+		}
+	}
+}
+
+// called on the evaluating thread whenever execution pauses, with the context
+// of the code that's about to run. Evaluation carries on once it returns, so
+// this is where a frontend waits for the user
+pub trait DebugHandler: MaybeSync {
+	fn paused(
+		&self,
+		ctx: &EvalContext,
+		location: &SourceLocation,
+		reason: PauseReason,
+	) -> DebugCommand;
+}
+
+// can be sent to another thread (e.g. a protocol reader) to pause at the next
+// line, same as InterruptHandle does for cancelling
+#[derive(Debug, Clone, Default)]
+pub struct PauseHandle {
+	requested: Arc<AtomicBool>,
+}
+
+impl PauseHandle {
+	pub fn pause(&self) {
+		self.requested.store(true, Ordering::SeqCst);
+	}
+
+	fn take(&self) -> bool {
+		self.requested.swap(false, Ordering::SeqCst)
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StepMode {
+	Run,
+	Entry,
+	In,
+	// pause on the next line at or above the given call depth
+	Over(usize),
+	Out(usize),
+}
+
+#[derive(Debug)]
+struct DebugState {
+	mode: StepMode,
+	// the line last seen at each call depth, so a line only pauses when it's
+	// first reached rather than for every node on it, or again on returning
+	// from a call made by it
+	lines: Vec<Option<(Arc<str>, usize)>>,
+	// the handler can evaluate code while paused, which mustn't pause again
+	paused: bool,
+}
+
+pub struct Debugger {
+	handler: Box<dyn DebugHandler>,
+	breakpoints: Lock<HashMap<String, HashSet<usize>>>,
+	state: Lock<DebugState>,
+	pause: PauseHandle,
+}
+
+impl std::fmt::Debug for Debugger {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		f.debug_struct("Debugger")
+			.field("breakpoints", &self.breakpoints)
+			.field("state", &self.state)
+			.finish()
+	}
+}
+
+impl Debugger {
+	pub fn new(handler: impl DebugHandler + 'static) -> Shared<Self> {
+		Shared::new(Debugger {
+			handler: Box::new(handler),
+			breakpoints: Lock::default(),
+			state: Lock::new(DebugState {
+				mode: StepMode::Run,
+				lines: Vec::new(),
+				paused: false,
+			}),
+			pause: PauseHandle::default(),
+		})
+	}
+
+	// replaces the breakpoints for a file, which is matched against the name
+	// the source was parsed with
+	pub fn set_breakpoints(&self, file: &str, lines: &[usize]) {
+		let mut breakpoints = self.breakpoints.borrow_mut();
+		if lines.is_empty() {
+			breakpoints.remove(file);
+		} else {
+			breakpoints.insert(file.to_string(), lines.iter().cloned().collect());
+		}
+	}
+
+	pub fn stop_on_entry(&self) {
+		self.state.borrow_mut().mode = StepMode::Entry;
+	}
+
+	pub fn pause_handle(&self) -> PauseHandle {
+		self.pause.clone()
+	}
+
+	pub(crate) fn begin_run(&self) {
+		self.state.borrow_mut().lines.clear();
+	}
+
+	pub(crate) fn step(&self, ctx: &EvalContext, location: &SourceLocation) {
+		let (file, line) = match location {
+			SourceLocation::User { file, line } => (file, *line),
+			_ => return,
+		};
+		let depth = ctx.callstack.borrow().len();
+
+		let reason = {
+			let mut state = self.state.borrow_mut();
+			if state.paused {
+				return;
+			}
+
+			state.lines.resize(depth + 1, None);
+			let here = Some((file.clone(), line));
+			if state.lines[depth] == here {
+				return;
+			}
+			state.lines[depth] = here;
+
+			let breakpoint = self
+				.breakpoints
+				.borrow()
+				.get(&**file)
+				.map_or(false, |lines| lines.contains(&line));
+
+			let reason = match state.mode {
+				_ if self.pause.take() => PauseReason::Pause,
+				_ if breakpoint => PauseReason::Breakpoint,
+				StepMode::Entry => PauseReason::Entry,
+				StepMode::In => PauseReason::Step,
+				StepMode::Over(d) if depth <= d => PauseReason::Step,
+				StepMode::Out(d) if depth < d => PauseReason::Step,
+				_ => return,
+			};
+			state.paused = true;
+			reason
+		};
+
+		let command = self.handler.paused(ctx, location, reason);
+
+		let mut state = self.state.borrow_mut();
+		state.paused = false;
+		state.mode = match command {
+			DebugCommand::Continue => StepMode::Run,
+			DebugCommand::StepIn => StepMode::In,
+			DebugCommand::StepOver => StepMode::Over(depth),
+			DebugCommand::StepOut => StepMode::Out(depth),
+		};
+	}
+}
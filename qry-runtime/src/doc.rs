@@ -0,0 +1,253 @@
+use super::{Callable, Environment, Signature, Type, Value};
+
+// what help and generated reference pages say about a binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocEntry {
+	pub name: String,
+	// function, builtin, method, interface, type, library or value
+	pub kind: &'static str,
+	// how it's called, one per impl for methods, each with that impl's doc
+	pub signatures: Vec<(String, Option<String>)>,
+	pub doc: Option<String>,
+}
+
+// like a signature's Display, with trailing args written as `...Type` and
+// named ones as `...name = Type`
+fn signature_line(name: &str, signature: &Signature) -> String {
+	let mut params = signature
+		.params
+		.iter()
+		.map(|p| format!("{}: {}", p.name, p.param_type))
+		.collect::<Vec<_>>();
+	if let Some(trailing_type) = &signature.trailing_type {
+		params.push(format!("...{}", trailing_type));
+	}
+	if let Some(named_trailing_type) = &signature.named_trailing_type {
+		params.push(format!("...name = {}", named_trailing_type));
+	}
+	format!(
+		"{}({}) -> {}",
+		name,
+		params.join(", "),
+		signature.return_type
+	)
+}
+
+pub fn doc_entry(name: &str, value: &Value) -> DocEntry {
+	let (kind, signatures, doc) = match value {
+		Value::Function(function) => (
+			"function",
+			vec![(signature_line(name, function.signature()), None)],
+			function.doc(),
+		),
+		Value::Builtin(builtin) => (
+			"builtin",
+			vec![(signature_line(name, builtin.signature()), None)],
+			builtin.doc(),
+		),
+		Value::Method(method) => {
+			let mut impls = method
+				.callables()
+				.iter()
+				.map(|c| (signature_line(name, c.signature()), c.doc()))
+				.collect::<Vec<_>>();
+			impls.sort();
+			("method", impls, method.doc())
+		}
+		Value::Type(Type::Interface(interface)) => (
+			"interface",
+			interface
+				.methods
+				.iter()
+				.map(|m| (m.name().to_string(), m.doc()))
+				.collect(),
+			None,
+		),
+		Value::Type(_) => ("type", Vec::new(), None),
+		Value::Library(_) => ("library", Vec::new(), None),
+		_ => ("value", Vec::new(), None),
+	};
+
+	DocEntry {
+		name: name.to_string(),
+		kind,
+		signatures,
+		doc,
+	}
+}
+
+// everything bound in a library, by name
+pub fn library_docs(env: &Environment) -> Vec<DocEntry> {
+	let mut entries = env
+		.bindings()
+		.iter()
+		.map(|(name, value)| doc_entry(name, value))
+		.collect::<Vec<_>>();
+	entries.sort_by(|a, b| a.name.cmp(&b.name));
+	entries
+}
+
+fn summary(doc: &Option<String>) -> &str {
+	doc.as_deref().and_then(|d| d.lines().next()).unwrap_or("")
+}
+
+fn indented(text: &str, indent: &str) -> String {
+	text
+		.lines()
+		.map(|line| match line {
+			"" => "\n".to_string(),
+			line => format!("{}{}\n", indent, line),
+		})
+		.collect()
+}
+
+// what help prints: how to call the value and what it does, or for a library
+// the first line about each thing in it
+pub fn help_text(name: &str, value: &Value) -> String {
+	let entry = doc_entry(name, value);
+	let mut text = format!("{} {}\n", entry.kind, entry.name);
+	for (signature, doc) in &entry.signatures {
+		text += &indented(signature, "  ");
+		if let Some(doc) = doc {
+			text += &indented(doc, "    ");
+		}
+	}
+	if let Some(doc) = &entry.doc {
+		text += "\n";
+		text += &indented(doc, "  ");
+	}
+
+	if let Value::Library(env) = value {
+		let entries = library_docs(env);
+		let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+		for entry in &entries {
+			let line = format!("{:w$}  {}", entry.name, summary(&entry.doc), w = width);
+			text += &indented(line.trim_end(), "  ");
+		}
+	}
+	text.trim_end().to_string()
+}
+
+pub fn markdown_reference(library: &str, entries: &[DocEntry]) -> String {
+	let mut page = format!("# {}\n", library);
+	for entry in entries {
+		page += &format!("\n## `{}`\n\n*{}*\n", entry.name, entry.kind);
+		if !entry.signatures.is_empty() {
+			page += "\n```\n";
+			for (signature, _) in &entry.signatures {
+				page += &format!("{}\n", signature);
+			}
+			page += "```\n";
+		}
+		if let Some(doc) = &entry.doc {
+			page += &format!("\n{}\n", doc);
+		}
+		let impl_docs = entry
+			.signatures
+			.iter()
+			.filter_map(|(signature, doc)| Some((signature, doc.as_ref()?)))
+			.collect::<Vec<_>>();
+		if !impl_docs.is_empty() {
+			page += "\n";
+			for (signature, doc) in impl_docs {
+				page += &format!("- `{}`: {}\n", signature, doc.replace('\n', " "));
+			}
+		}
+	}
+	page
+}
+
+fn html_escape(text: &str) -> String {
+	text
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+// with `backticked` spans as code, as they are in Markdown
+fn html_text(text: &str) -> String {
+	text
+		.split('`')
+		.enumerate()
+		.map(|(i, span)| match i % 2 {
+			0 => html_escape(span),
+			_ => format!("<code>{}</code>", html_escape(span)),
+		})
+		.collect()
+}
+
+fn html_paragraphs(doc: &str) -> String {
+	doc
+		.split("\n\n")
+		.map(|p| format!("<p>{}</p>\n", html_text(p.trim())))
+		.collect()
+}
+
+pub fn html_reference(library: &str, entries: &[DocEntry]) -> String {
+	let mut page = String::new();
+	for entry in entries {
+		let name = html_escape(&entry.name);
+		page += &format!(
+			"<h2 id=\"{0}\"><code>{0}</code></h2>\n<p><em>{1}</em></p>\n",
+			name,
+			html_escape(entry.kind)
+		);
+		if !entry.signatures.is_empty() {
+			let signatures = entry
+				.signatures
+				.iter()
+				.map(|(signature, _)| html_escape(signature))
+				.collect::<Vec<_>>();
+			page += &format!("<pre><code>{}</code></pre>\n", signatures.join("\n"));
+		}
+		if let Some(doc) = &entry.doc {
+			page += &html_paragraphs(doc);
+		}
+		let impl_docs = entry
+			.signatures
+			.iter()
+			.filter_map(|(signature, doc)| Some((signature, doc.as_ref()?)))
+			.map(|(signature, doc)| {
+				format!(
+					"<li><code>{}</code>: {}</li>\n",
+					html_escape(signature),
+					html_text(doc)
+				)
+			})
+			.collect::<String>();
+		if !impl_docs.is_empty() {
+			page += &format!("<ul>\n{}</ul>\n", impl_docs);
+		}
+	}
+	html_page(library, &page)
+}
+
+fn html_page(title: &str, body: &str) -> String {
+	format!(
+		"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n{1}</body>\n</html>\n",
+		html_escape(title),
+		body
+	)
+}
+
+// links to each library's page, named after the library
+pub fn markdown_index(libraries: &[String]) -> String {
+	let mut page = "# Libraries\n\n".to_string();
+	for library in libraries {
+		page += &format!("- [{0}]({0}.md)\n", library);
+	}
+	page
+}
+
+pub fn html_index(libraries: &[String]) -> String {
+	let mut body = "<ul>\n".to_string();
+	for library in libraries {
+		body += &format!(
+			"<li><a href=\"{0}.html\">{0}</a></li>\n",
+			html_escape(library)
+		);
+	}
+	body += "</ul>\n";
+	html_page("Libraries", &body)
+}
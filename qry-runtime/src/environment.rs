@@ -1,25 +1,32 @@
 use super::{
-	Builtin, BuiltinFunc, Callable, Method, NativeGenericType, NativeType, Signature, Type, Value,
+	gc, register_identity_equality, Builtin, BuiltinClosure, BuiltinFunc, Callable, IntoBuiltin,
+	Lock, Method, NativeGenericType, NativeType, RuntimeMethods, Shared, Signature, Type, Value,
 };
-use std::cell::RefCell;
-use std::collections::HashMap;
+use qry_lang::BinaryOperator;
+use std::collections::{HashMap, HashSet};
 use std::panic::Location;
-use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Environment {
 	name: String,
-	state: RefCell<HashMap<String, Value>>,
+	state: Lock<HashMap<String, Value>>,
+	exports: Lock<HashSet<String>>,
 }
 
 impl Environment {
-	pub fn new(name: &str) -> Rc<Self> {
-		Rc::new(Self {
+	pub fn new(name: &str) -> Shared<Self> {
+		gc::track(Self {
 			name: name.to_string(),
-			state: RefCell::new(HashMap::new()),
+			state: Lock::new(HashMap::new()),
+			exports: Lock::default(),
 		})
 	}
 
+	// a copy of the bindings as they are now, which is what closures capture
+	pub fn snapshot(&self) -> Shared<Self> {
+		gc::track(self.clone())
+	}
+
 	pub fn name(&self) -> &str {
 		&self.name
 	}
@@ -36,33 +43,84 @@ impl Environment {
 		}
 	}
 
+	pub fn bindings(&self) -> Vec<(String, Value)> {
+		self
+			.state
+			.borrow()
+			.iter()
+			.map(|(k, v)| (k.clone(), v.clone()))
+			.collect()
+	}
+
+	pub(crate) fn values(&self) -> Vec<Value> {
+		self.state.borrow().values().cloned().collect()
+	}
+
+	// drops every binding, breaking any cycles running through them
+	pub(crate) fn clear(&self) {
+		let state = std::mem::take(&mut *self.state.borrow_mut());
+		drop(state);
+	}
+
+	pub fn export(&self, name: &str) {
+		self.exports.borrow_mut().insert(name.to_string());
+	}
+
+	// a library holding only the bindings marked with `pub`, which is what
+	// importers of a module get to see
+	pub fn exported(&self, name: &str) -> Shared<Self> {
+		let env = Self::new(name);
+		for export in self.exports.borrow().iter() {
+			if let Some(val) = self.get(export) {
+				env.update(export, val);
+			}
+		}
+		env
+	}
+
 	pub fn copy_to(&self, target: &Environment) {
 		for (k, v) in self.state.borrow().iter() {
 			target.update(k, v.clone());
 		}
 	}
 
-	pub fn child(&self, name: &str) -> Rc<Self> {
+	pub fn child(&self, name: &str) -> Shared<Self> {
 		let env = Self::new(name);
 		self.copy_to(&env);
 		env
 	}
 
-	pub fn define_native_type<T: 'static + NativeType>(&self) -> Type {
-		let native_type = Type::new_native::<T>();
-		self.update(T::name(), Value::Type(native_type.clone()));
-		native_type
+	// native values are only equal to themselves unless an impl of equal for
+	// the type says otherwise
+	pub fn define_native_type<T: 'static + NativeType>(&self, methods: &RuntimeMethods) -> Type {
+		self.define_type(methods, T::name(), Type::new_native::<T>())
 	}
 
-	pub fn define_native_generic_type<T: 'static + NativeGenericType>(&self) -> Type {
-		let native_type = Type::new_native_generic::<T>();
-		self.update(T::name(), Value::Type(native_type.clone()));
+	pub fn define_native_generic_type<T: 'static + NativeGenericType>(
+		&self,
+		methods: &RuntimeMethods,
+	) -> Type {
+		self.define_type(methods, T::name(), Type::new_native_generic::<T>())
+	}
+
+	fn define_type(&self, methods: &RuntimeMethods, name: &str, native_type: Type) -> Type {
+		register_identity_equality(
+			&methods.binops[&BinaryOperator::Equal],
+			&methods.binops[&BinaryOperator::NotEqual],
+			&native_type,
+		);
+		self.update(name, Value::Type(native_type.clone()));
 		native_type
 	}
 
 	#[track_caller]
 	pub fn define_builtin(&self, name: &str, signature: Signature, func: BuiltinFunc) {
-		let builtin = Value::Builtin(Builtin::new(
+		self.define_builtin_closure(name, signature, Shared::new(func));
+	}
+
+	#[track_caller]
+	pub fn define_builtin_closure(&self, name: &str, signature: Signature, func: BuiltinClosure) {
+		let builtin = Value::Builtin(Builtin::new_closure(
 			&format!("{}::{}", self.name, name),
 			signature,
 			Location::caller().into(),
@@ -71,13 +129,38 @@ impl Environment {
 		self.update(name, builtin);
 	}
 
+	// the signature comes from the function's argument and return types
+	#[track_caller]
+	pub fn define_fn<Args, F: IntoBuiltin<Args>>(&self, name: &str, func: F) {
+		self.define_builtin_closure(name, F::signature(), func.into_closure());
+	}
+
+	// where a native type's impl of `name` goes: the runtime or library method
+	// with that name if there is one, otherwise a new method dispatching on
+	// all of the impl's params
+	pub fn method_for_impl(
+		&self,
+		methods: &RuntimeMethods,
+		name: &str,
+		dispatch_param_names: &[&str],
+	) -> Shared<Method> {
+		if let Some(method) = methods.named(name) {
+			return method;
+		}
+
+		match self.get(name) {
+			Some(Value::Method(method)) => method,
+			_ => self.define_method(name, dispatch_param_names, None, None),
+		}
+	}
+
 	pub fn define_method(
 		&self,
 		name: &str,
 		dispatch_param_names: &[&str],
 		fixed_return_type: Option<Type>,
-		default_impl: Option<Rc<dyn Callable>>,
-	) -> Rc<Method> {
+		default_impl: Option<Shared<dyn Callable>>,
+	) -> Shared<Method> {
 		let method = Method::new(name, dispatch_param_names, fixed_return_type, default_impl);
 		self.update(name, Value::Method(method.clone()));
 		method
@@ -0,0 +1,143 @@
+use super::{Callable, EvalContext, EvalResult, List, Method, Shared, Signature, Type, Value};
+use qry_lang::BinaryOperator;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// the same value rather than an equal one: scalars compare by value without
+// promotion, lists item by item, and everything else by reference
+#[allow(clippy::float_cmp)]
+pub fn identical(a: &Value, b: &Value) -> bool {
+	match (a, b) {
+		(Value::Null(_), Value::Null(_)) => true,
+		(Value::Int(a), Value::Int(b)) => a == b,
+		(Value::Float(a), Value::Float(b)) => a == b,
+		(Value::BigInt(a), Value::BigInt(b)) => a == b,
+		(Value::Decimal(a), Value::Decimal(b)) => a == b,
+		(Value::Bool(a), Value::Bool(b)) => a == b,
+		(Value::String(a), Value::String(b)) => a == b,
+		(Value::Type(a), Value::Type(b)) => a == b,
+		(Value::Syntax(a), Value::Syntax(b)) => a == b,
+		(Value::List(a), Value::List(b)) => {
+			a.len() == b.len() && a.iter().zip(b).all(|(a, b)| identical(a, b))
+		}
+		(Value::Function(a), Value::Function(b)) => Shared::ptr_eq(a, b),
+		(Value::Builtin(a), Value::Builtin(b)) => Shared::ptr_eq(a, b),
+		(Value::Method(a), Value::Method(b)) => Shared::ptr_eq(a, b),
+		(Value::Library(a), Value::Library(b)) => Shared::ptr_eq(a, b),
+		(Value::Native(a), Value::Native(b)) => a.same_object(b),
+		_ => false,
+	}
+}
+
+// whether `==` has an impl for the pair: an Int with any number, as it's
+// promoted, and otherwise only values of the same type
+pub fn comparable(a: &Value, b: &Value) -> bool {
+	let number = |v: &Value| {
+		matches!(
+			v,
+			Value::Int(_) | Value::Float(_) | Value::BigInt(_) | Value::Decimal(_)
+		)
+	};
+	match (a, b) {
+		(Value::Int(_), other) | (other, Value::Int(_)) => number(other),
+		_ => a.runtime_type() == b.runtime_type(),
+	}
+}
+
+// for types whose values are only equal to themselves, such as functions and
+// connections
+pub fn register_identity_equality(equal: &Method, not_equal: &Method, value_type: &Type) {
+	let signature = Signature::returning(&Type::Bool)
+		.param("a", value_type)
+		.param("b", value_type);
+	equal.register_builtin(signature.clone(), |_, args, _| {
+		Ok(Value::Bool(identical(&args[0], &args[1])))
+	});
+	not_equal.register_builtin(signature, |_, args, _| {
+		Ok(Value::Bool(!identical(&args[0], &args[1])))
+	});
+}
+
+// what `==` says, so natives with an impl of equal compare by content
+pub fn values_equal(ctx: &EvalContext, a: &Value, b: &Value) -> EvalResult<bool> {
+	ctx.methods.binops[&BinaryOperator::Equal]
+		.call(ctx, &[a.clone(), b.clone()], &[])?
+		.as_bool(ctx)
+}
+
+pub fn hash_value(ctx: &EvalContext, value: &Value) -> EvalResult<i64> {
+	ctx
+		.methods
+		.hash
+		.call(ctx, std::slice::from_ref(value), &[])?
+		.as_int(ctx)
+}
+
+// the hash for values without a more specific impl of hash. Numbers that
+// compare equal after promotion hash alike, and references hash by identity
+pub fn builtin_hash(ctx: &EvalContext, value: &Value) -> EvalResult<i64> {
+	let mut hasher = DefaultHasher::new();
+	// an Int equals the Float it rounds to, so numbers hash as the nearest f64.
+	// Parsing the digits rounds the same way as converting an i64 does
+	let number = match value {
+		Value::Int(n) => Some(*n as f64),
+		Value::Float(f) => Some(*f),
+		Value::BigInt(n) => n.to_string().parse::<f64>().ok(),
+		Value::Decimal(d) => d.to_string().parse::<f64>().ok(),
+		_ => None,
+	};
+	if let Some(number) = number {
+		let bits = if number == 0.0 {
+			0
+		} else if number.is_nan() {
+			f64::NAN.to_bits()
+		} else {
+			number.to_bits()
+		};
+		bits.hash(&mut hasher);
+		return Ok(hasher.finish() as i64);
+	}
+
+	std::mem::discriminant(value).hash(&mut hasher);
+	match value {
+		Value::Bool(b) => b.hash(&mut hasher),
+		Value::String(s) => s.hash(&mut hasher),
+		Value::Type(t) => t.hash(&mut hasher),
+		Value::Syntax(node) => node.to_string().hash(&mut hasher),
+		Value::List(items) => {
+			for item in items {
+				hash_value(ctx, item)?.hash(&mut hasher);
+			}
+		}
+		Value::Function(f) => Shared::as_ptr(f).hash(&mut hasher),
+		Value::Builtin(b) => Shared::as_ptr(b).hash(&mut hasher),
+		Value::Method(m) => Shared::as_ptr(m).hash(&mut hasher),
+		Value::Library(l) => Shared::as_ptr(l).hash(&mut hasher),
+		Value::Native(w) => w.address().hash(&mut hasher),
+		_ => (),
+	}
+	Ok(hasher.finish() as i64)
+}
+
+// the values that aren't equal to one before them, in order
+pub fn distinct(ctx: &EvalContext, values: &List) -> EvalResult<List> {
+	let mut seen: HashMap<i64, Vec<usize>> = HashMap::new();
+	let mut ret = List::new();
+	for value in values {
+		let bucket = seen.entry(hash_value(ctx, value)?).or_default();
+		let mut duplicate = false;
+		for &i in bucket.iter() {
+			let other = ret.get(i).unwrap();
+			if comparable(other, value) && values_equal(ctx, other, value)? {
+				duplicate = true;
+				break;
+			}
+		}
+		if !duplicate {
+			bucket.push(ret.len());
+			ret.push(value.clone());
+		}
+	}
+	Ok(ret)
+}
@@ -1,12 +1,16 @@
 use super::{
-	eval_callable, eval_function_decl, Callable, Environment, EvalContext, Exception, Type, Value,
+	call_with_args, eval_callable, eval_function_decl, Environment, EvalContext, EvalEvent,
+	Exception, Interface, Method, Shared, Type, Value,
 };
 use qry_lang::syntax::*;
-use std::rc::Rc;
 
 pub type EvalResult<T> = Result<T, Exception>;
 
 pub fn assign_value(ctx: &EvalContext, name: &str, value: Value) -> EvalResult<Value> {
+	ctx.emit(EvalEvent::Assign {
+		name,
+		value: &value,
+	});
 	ctx.env.update(name, value.clone());
 	Ok(value)
 }
@@ -21,13 +25,19 @@ fn eval_assign(ctx: &EvalContext, dest: &SyntaxNode, src: &SyntaxNode) -> EvalRe
 	}
 }
 
-fn eval_unop(ctx: &EvalContext, target: &SyntaxNode, op: UnaryOperator) -> EvalResult<Value> {
+fn eval_unop(
+	ctx: &EvalContext,
+	location: &SourceLocation,
+	target: &SyntaxNode,
+	op: UnaryOperator,
+) -> EvalResult<Value> {
 	let method = &ctx.methods.unops[&op];
-	eval_callable(ctx, &**method, &[target.clone()], &[])
+	eval_callable(ctx, &**method, location, &[target.clone()], &[])
 }
 
 fn eval_binop(
 	ctx: &EvalContext,
+	location: &SourceLocation,
 	lhs: &SyntaxNode,
 	rhs: &SyntaxNode,
 	op: BinaryOperator,
@@ -80,38 +90,74 @@ fn eval_binop(
 		},
 		_ => {
 			let method = &ctx.methods.binops[&op];
-			eval_callable(ctx, &**method, &[lhs.clone(), rhs.clone()], &[])
+			eval_callable(ctx, &**method, location, &[lhs.clone(), rhs.clone()], &[])
 		}
 	}
 }
 
+// a path whose first segment isn't a built-in or host library refers to a
+// module file on the search path
+pub fn is_module_path(ctx: &EvalContext, path: &[String]) -> bool {
+	match path.first() {
+		Some(root) => ctx.library_env.get(root).is_none(),
+		None => false,
+	}
+}
+
 fn resolve_lib(
 	ctx: &EvalContext,
-	parent_node: &SyntaxNode,
+	location: &SourceLocation,
 	from: &[String],
-) -> Result<Rc<Environment>, Exception> {
+) -> Result<Shared<Environment>, Exception> {
+	if is_module_path(ctx, from) {
+		return match ctx.modules.load(ctx, location, from)? {
+			Some(module) => Ok(module),
+			None => Err(ctx.exception(location, format!("module not found: {}", from.join("::")))),
+		};
+	}
+
 	let mut current_env = ctx.library_env.clone();
 	for name in from {
 		if let Some(lib_value) = current_env.get(name) {
 			if let Value::Library(lib_env) = lib_value {
 				current_env = lib_env;
 			} else {
-				return Err(ctx.exception(&parent_node.location, "expected a library"));
+				return Err(ctx.exception(location, "expected a library"));
 			}
 		} else {
-			return Err(ctx.exception(&parent_node.location, "expected a library"));
+			return Err(ctx.exception(location, "expected a library"));
 		}
 	}
 	Ok(current_env)
 }
 
-fn eval_import(
+pub fn eval_import(
 	ctx: &EvalContext,
-	parent_node: &SyntaxNode,
+	location: &SourceLocation,
 	from: &[String],
 	import: &Import,
 ) -> EvalResult<Value> {
-	let lib_env = resolve_lib(ctx, parent_node, from)?;
+	// `use mylib::helpers` binds the whole module when there's a
+	// mylib/helpers.qry, otherwise it imports helpers from mylib.qry
+	if let Import::Named(names) = import {
+		if let [name] = names.as_slice() {
+			let path = [from, std::slice::from_ref(name)].concat();
+			if is_module_path(ctx, &path) {
+				match ctx.modules.load(ctx, location, &path)? {
+					Some(module) => {
+						ctx.env.update(name, Value::Library(module));
+						return Ok(Value::Null(()));
+					}
+					None if ctx.modules.find(from).is_none() => {
+						return Err(ctx.exception(location, format!("module not found: {}", path.join("::"))));
+					}
+					None => (),
+				}
+			}
+		}
+	}
+
+	let lib_env = resolve_lib(ctx, location, from)?;
 
 	match import {
 		Import::Named(names) => {
@@ -119,7 +165,7 @@ fn eval_import(
 				if let Some(val) = lib_env.get(name) {
 					ctx.env.update(name, val);
 				} else {
-					return Err(ctx.exception(&parent_node.location, format!("not found: {}", name)));
+					return Err(ctx.exception(location, format!("not found: {}", name)));
 				}
 			}
 		}
@@ -129,7 +175,66 @@ fn eval_import(
 	Ok(Value::Null(()))
 }
 
+pub fn expect_type(ctx: &EvalContext, value: Value, location: &SourceLocation) -> EvalResult<Type> {
+	match value {
+		Value::Type(t) => Ok(t),
+		_ => Err(ctx.exception(location, "expected a type")),
+	}
+}
+
+pub fn expect_method(
+	ctx: &EvalContext,
+	value: Value,
+	location: &SourceLocation,
+) -> EvalResult<Shared<Method>> {
+	match value {
+		Value::Method(m) => Ok(m),
+		_ => Err(ctx.exception(location, "expected a method")),
+	}
+}
+
+pub fn make_interface(
+	ctx: &EvalContext,
+	name: &str,
+	methods: Vec<Value>,
+	location: &SourceLocation,
+) -> EvalResult<Value> {
+	let methods = methods
+		.into_iter()
+		.map(|m| match m {
+			Value::Method(method) => Ok(method),
+			_ => Err(ctx.exception(location, "interfaces can only require methods")),
+		})
+		.collect::<EvalResult<Vec<_>>>()?;
+
+	Ok(Value::Type(Type::Interface(Shared::new(Interface {
+		name: name.into(),
+		methods,
+	}))))
+}
+
+pub fn instantiate_generic(
+	ctx: &EvalContext,
+	target: Value,
+	location: &SourceLocation,
+	type_args: &[Type],
+) -> EvalResult<Value> {
+	let generic_lookup = match target {
+		Value::Type(t) => match t {
+			Type::Native(d) => match d.generic_lookup {
+				Some(l) => Ok(l),
+				_ => Err(ctx.exception(location, "native type is not generic")),
+			},
+			_ => Err(ctx.exception(location, "unsupported type for generics")),
+		},
+		_ => Err(ctx.exception(location, "unsupported value type for generics")),
+	}?;
+
+	Ok(Value::Type(generic_lookup(ctx, type_args)?))
+}
+
 pub fn eval_multi(ctx: &EvalContext, exprs: &[SyntaxNode]) -> EvalResult<Value> {
+	ctx.begin_run();
 	let mut ret = Value::Null(());
 	for expr in exprs {
 		ret = eval(ctx, expr)?;
@@ -138,14 +243,15 @@ pub fn eval_multi(ctx: &EvalContext, exprs: &[SyntaxNode]) -> EvalResult<Value>
 }
 
 pub fn eval(ctx: &EvalContext, node: &SyntaxNode) -> EvalResult<Value> {
+	ctx.step(&node.location)?;
 	match &node.syntax {
 		Syntax::Int(val) => Ok(Value::Int(*val)),
 		Syntax::Float(val) => Ok(Value::Float(*val)),
 		Syntax::String(val) => Ok(Value::String(val.clone().into_boxed_str())),
 		Syntax::Bool(val) => Ok(Value::Bool(*val)),
 		Syntax::Null => Ok(Value::Null(())),
-		Syntax::BinaryOp { lhs, rhs, op } => eval_binop(ctx, lhs, rhs, *op),
-		Syntax::UnaryOp { target, op } => eval_unop(ctx, target, *op),
+		Syntax::BinaryOp { lhs, rhs, op } => eval_binop(ctx, &node.location, lhs, rhs, *op),
+		Syntax::UnaryOp { target, op } => eval_unop(ctx, &node.location, target, *op),
 		Syntax::Interpolate(_) => Err(ctx.exception(
 			&node.location,
 			"interpolation not supported in regular code",
@@ -155,8 +261,17 @@ pub fn eval(ctx: &EvalContext, node: &SyntaxNode) -> EvalResult<Value> {
 			params,
 			return_type,
 			body,
-		} => eval_function_decl(ctx, &node.location, header, params, return_type, body),
-		Syntax::Use { from, import } => eval_import(ctx, node, from, import),
+			doc,
+		} => eval_function_decl(ctx, &node.location, header, params, return_type, body, doc),
+		Syntax::Export(target) => match target.defined_name() {
+			Some(name) => {
+				let val = eval(ctx, target)?;
+				ctx.env.export(name);
+				Ok(val)
+			}
+			None => Err(ctx.exception(&node.location, "pub requires a named definition")),
+		},
+		Syntax::Use { from, import } => eval_import(ctx, &node.location, from, import),
 		Syntax::Ident(name) => {
 			if let Some(val) = ctx.env.get(name) {
 				Ok(val)
@@ -174,10 +289,15 @@ pub fn eval(ctx: &EvalContext, node: &SyntaxNode) -> EvalResult<Value> {
 				.map(|(n, s)| (n.as_ref(), s.clone()))
 				.collect::<Vec<_>>();
 
+			let location = &node.location;
 			match eval(ctx, target)? {
-				Value::Builtin(builtin) => eval_callable(ctx, &*builtin, positional_args, &named_args),
-				Value::Function(func) => eval_callable(ctx, &*func, positional_args, &named_args),
-				Value::Method(method) => eval_callable(ctx, &*method, positional_args, &named_args),
+				Value::Builtin(builtin) => {
+					eval_callable(ctx, &*builtin, location, positional_args, &named_args)
+				}
+				Value::Function(func) => eval_callable(ctx, &*func, location, positional_args, &named_args),
+				Value::Method(method) => {
+					eval_callable(ctx, &*method, location, positional_args, &named_args)
+				}
 				_ => Err(ctx.exception(&node.location, "target is not callable")),
 			}
 		}
@@ -187,9 +307,18 @@ pub fn eval(ctx: &EvalContext, node: &SyntaxNode) -> EvalResult<Value> {
 			let eq_method = &ctx.methods.binops[&BinaryOperator::Equal];
 			for case in cases {
 				let case_val = eval(ctx, &case.expr)?;
-				let eq_val = eq_method.call(ctx, &[target_val.clone(), case_val], &[])?;
+				let eq_val = call_with_args(
+					ctx,
+					&**eq_method,
+					&case.expr.location,
+					&[target_val.clone(), case_val],
+					&[],
+				)?;
 
-				if eq_val.as_bool() {
+				if eq_val
+					.as_bool(ctx)
+					.map_err(|ex| ex.or_located_at(&case.expr.location))?
+				{
 					ret = eval(ctx, &case.returns)?;
 					break;
 				}
@@ -205,30 +334,28 @@ pub fn eval(ctx: &EvalContext, node: &SyntaxNode) -> EvalResult<Value> {
 					.collect::<Result<Vec<_>, _>>()?,
 			);
 
-			let ret = ctx.methods.index.call(ctx, &args, &[])?;
-			Ok(ret)
+			call_with_args(ctx, &*ctx.methods.index, &node.location, &args, &[])
 		}
 		Syntax::GenericInstantiation { target, type_args } => {
-			let generic_lookup = match eval(ctx, target)? {
-				Value::Type(t) => match t {
-					Type::Native(d) => match d.generic_lookup {
-						Some(l) => Ok(l),
-						_ => Err(ctx.exception(&target.location, "native type is not generic")),
-					},
-					_ => Err(ctx.exception(&target.location, "unsupported type for generics")),
-				},
-				_ => Err(ctx.exception(&target.location, "unsupported value type for generics")),
-			}?;
-
+			let target_val = eval(ctx, target)?;
 			let types = type_args
 				.iter()
-				.map(|a| match eval(ctx, a)? {
-					Value::Type(t) => Ok(t),
-					_ => Err(ctx.exception(&a.location, "expected a type")),
-				})
+				.map(|a| expect_type(ctx, eval(ctx, a)?, &a.location))
 				.collect::<EvalResult<Vec<_>>>()?;
 
-			Ok(Value::Type(generic_lookup(ctx, &types)?))
+			instantiate_generic(ctx, target_val, &target.location, &types)
+		}
+		Syntax::TypeAlias { name, target } => {
+			let alias = expect_type(ctx, eval(ctx, target)?, &target.location)?;
+			assign_value(ctx, name, Value::Type(alias))
+		}
+		Syntax::Interface { name, methods } => {
+			let methods = methods
+				.iter()
+				.map(|m| eval(ctx, m))
+				.collect::<EvalResult<Vec<_>>>()?;
+			let interface = make_interface(ctx, name, methods, &node.location)?;
+			assign_value(ctx, name, interface)
 		}
 	}
 }
@@ -1,22 +1,59 @@
-use super::{Environment, Exception, Method, Value};
-use crate::stdlib;
+use super::{
+	BudgetError, Callable, Capability, Debugger, Environment, EvalContextBuilder, EvalEvent,
+	EvalLimits, EvalResult, Exception, ExceptionKind, ExecutionBudget, InterruptHandle, Lock, Method,
+	ModuleLoader, Observer, Output, OutputSink, ProfileScope, Profiler, Sandbox, Shared, Type, Value,
+};
 use qry_lang::{BinaryOperator, SourceLocation, UnaryOperator};
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct StackFrame {
 	pub name: String,
+	// where the call was made from, definition is where the callee lives
 	pub location: SourceLocation,
+	pub definition: SourceLocation,
+	pub arg_types: Vec<Type>,
+}
+
+impl StackFrame {
+	pub fn new<C: Callable + ?Sized>(
+		callable: &C,
+		call_site: &SourceLocation,
+		args: &[Value],
+	) -> Self {
+		StackFrame {
+			name: callable.name().to_owned(),
+			location: call_site.clone(),
+			definition: callable.source_location().clone(),
+			arg_types: args.iter().map(|a| a.runtime_type()).collect(),
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
 pub struct RuntimeMethods {
-	pub to_string: Rc<Method>,
-	pub index: Rc<Method>,
-	pub binops: HashMap<BinaryOperator, Rc<Method>>,
-	pub unops: HashMap<UnaryOperator, Rc<Method>>,
+	pub to_string: Shared<Method>,
+	// writes a value to the output sink, as text unless the type has a richer
+	// form such as a table
+	pub display: Shared<Method>,
+	pub index: Shared<Method>,
+	// consistent with equal, so values that compare equal hash alike
+	pub hash: Shared<Method>,
+	pub binops: HashMap<BinaryOperator, Shared<Method>>,
+	pub unops: HashMap<UnaryOperator, Shared<Method>>,
+}
+
+impl RuntimeMethods {
+	pub fn named(&self, name: &str) -> Option<Shared<Method>> {
+		std::iter::once(&self.to_string)
+			.chain(std::iter::once(&self.display))
+			.chain(std::iter::once(&self.index))
+			.chain(std::iter::once(&self.hash))
+			.chain(self.binops.values())
+			.chain(self.unops.values())
+			.find(|m| m.name() == name)
+			.cloned()
+	}
 }
 
 pub struct EvalStackFrameScope<'a> {
@@ -24,12 +61,8 @@ pub struct EvalStackFrameScope<'a> {
 }
 
 impl<'a> EvalStackFrameScope<'a> {
-	pub fn new(ctx: &'a EvalContext, name: &str, location: &SourceLocation) -> Self {
-		ctx.callstack.borrow_mut().push(StackFrame {
-			name: name.to_owned(),
-			location: location.clone(),
-		});
-
+	pub fn new(ctx: &'a EvalContext, frame: StackFrame) -> Self {
+		ctx.callstack.borrow_mut().push(frame);
 		Self { ctx }
 	}
 }
@@ -42,58 +75,203 @@ impl Drop for EvalStackFrameScope<'_> {
 
 #[derive(Debug, Clone)]
 pub struct EvalContext {
-	pub env: Rc<Environment>,
-	pub library_env: Rc<Environment>,
-	pub methods: Rc<RuntimeMethods>,
-	pub callstack: Rc<RefCell<Vec<StackFrame>>>,
+	pub env: Shared<Environment>,
+	pub library_env: Shared<Environment>,
+	pub methods: Shared<RuntimeMethods>,
+	pub callstack: Shared<Lock<Vec<StackFrame>>>,
+	pub budget: Shared<ExecutionBudget>,
+	pub sandbox: Shared<Sandbox>,
+	pub modules: Shared<ModuleLoader>,
+	// named connection targets, usually from the project manifest
+	pub connections: Shared<HashMap<String, String>>,
+	pub debugger: Option<Shared<Debugger>>,
+	pub profiler: Option<Shared<Profiler>>,
+	pub observer: Option<Shared<dyn Observer>>,
+	pub output_sink: Shared<dyn OutputSink>,
 }
 
 impl EvalContext {
 	pub fn new_with_stdlib() -> Self {
-		let global_env_ptr = Environment::new("global");
-		let library_env_ptr = Environment::new("libraries");
+		EvalContextBuilder::new().build()
+	}
+
+	pub fn builder() -> EvalContextBuilder {
+		EvalContextBuilder::new()
+	}
+
+	pub fn child(&self, env: Shared<Environment>) -> EvalContext {
+		EvalContext {
+			env,
+			..self.clone()
+		}
+	}
 
-		let add_lib = |env: Rc<Environment>, add_to_global| {
-			let lib_val = Value::Library(env.clone());
-			library_env_ptr.update(env.name(), lib_val.clone());
+	// shares everything defined so far, but has its own callstack and budget so
+	// it can run on another thread alongside this one
+	pub fn fork(&self) -> EvalContext {
+		EvalContext {
+			callstack: Shared::default(),
+			budget: Shared::new(ExecutionBudget::new(self.limits())),
+			..self.clone()
+		}
+	}
+
+	#[must_use]
+	pub fn with_stack_frame(&self, frame: StackFrame) -> EvalStackFrameScope {
+		EvalStackFrameScope::new(self, frame)
+	}
+
+	// the location the innermost frame was called from, for calls made on
+	// behalf of that frame such as a method dispatching to an impl
+	pub fn call_site(&self) -> SourceLocation {
+		match self.callstack.borrow().last() {
+			Some(frame) => frame.location.clone(),
+			None => SourceLocation::Unknown,
+		}
+	}
+
+	pub fn limits(&self) -> EvalLimits {
+		self.budget.limits.borrow().clone()
+	}
+
+	pub fn set_limits(&self, limits: EvalLimits) {
+		*self.budget.limits.borrow_mut() = limits;
+	}
+
+	pub fn interrupt_handle(&self) -> InterruptHandle {
+		self.budget.interrupt_handle()
+	}
 
-			if add_to_global {
-				env.copy_to(&global_env_ptr);
+	// step and timeout budgets apply per top-level run rather than over the
+	// lifetime of the context, which matters for the repl
+	pub fn begin_run(&self) {
+		if self.callstack.borrow().is_empty() {
+			self.budget.reset();
+			if let Some(debugger) = &self.debugger {
+				debugger.begin_run();
 			}
+		}
+	}
+
+	pub fn step(&self, location: &SourceLocation) -> EvalResult<()> {
+		self
+			.budget
+			.step()
+			.map_err(|err| self.budget_exception(location, err))?;
+
+		if let Some(debugger) = &self.debugger {
+			debugger.step(self, location);
+		}
+		Ok(())
+	}
+
+	// timing for a call or query lasts until the scope is dropped
+	pub fn profile(&self, name: &str) -> Option<ProfileScope<'_>> {
+		self.profiler.as_ref().map(|p| p.enter(name))
+	}
+
+	pub fn profile_query(&self, sql: &str) -> Option<ProfileScope<'_>> {
+		self.profiler.as_ref().map(|p| p.enter_query(sql))
+	}
+
+	pub fn emit(&self, event: EvalEvent) {
+		if let Some(observer) = &self.observer {
+			observer.event(self, &event);
+		}
+	}
+
+	// for print and display builtins, which need the stdout capability whatever
+	// the sink is
+	pub fn write_output(&self, output: Output) -> EvalResult<()> {
+		self.require(Capability::Stdout, &SourceLocation::Unknown)?;
+		self.output_sink.write(&output);
+		Ok(())
+	}
+
+	pub fn check_interrupt(&self, location: &SourceLocation) -> EvalResult<()> {
+		self
+			.budget
+			.check_interrupt()
+			.map_err(|err| self.budget_exception(location, err))
+	}
 
-			global_env_ptr.update(env.name(), lib_val);
+	pub fn check_call_depth(&self, location: &SourceLocation) -> EvalResult<()> {
+		let max_call_depth = match self.budget.limits.borrow().max_call_depth {
+			Some(max_call_depth) if self.callstack.borrow().len() >= max_call_depth => max_call_depth,
+			_ => return Ok(()),
 		};
 
-		let (ops_methods, ops_env) = stdlib::ops::create();
-		add_lib(ops_env, false);
-		add_lib(stdlib::core::env(&ops_methods), true);
-		add_lib(stdlib::data::env(&ops_methods), false);
+		Err(self.exception_of_kind(
+			ExceptionKind::LimitExceeded,
+			location,
+			format!("call depth limit exceeded ({} frames)", max_call_depth),
+		))
+	}
 
-		EvalContext {
-			env: global_env_ptr,
-			library_env: library_env_ptr,
-			methods: Rc::new(ops_methods),
-			callstack: Rc::default(),
+	pub fn require(&self, capability: Capability, location: &SourceLocation) -> EvalResult<()> {
+		if self.sandbox.allows(capability) {
+			return Ok(());
 		}
+
+		Err(self.exception_of_kind(
+			ExceptionKind::Denied,
+			location,
+			format!("capability denied: {}", capability.name()),
+		))
 	}
 
-	pub fn child(&self, env: Rc<Environment>) -> EvalContext {
-		EvalContext {
-			env,
-			..self.clone()
+	pub fn require_connection(&self, target: &str, location: &SourceLocation) -> EvalResult<()> {
+		if self.sandbox.allows_connection(target) {
+			return Ok(());
 		}
+
+		Err(self.exception_of_kind(
+			ExceptionKind::Denied,
+			location,
+			format!("connection target not allowed: {}", target),
+		))
 	}
 
-	#[must_use]
-	pub fn with_stack_frame(&self, name: &str, location: &SourceLocation) -> EvalStackFrameScope {
-		EvalStackFrameScope::new(self, name, location)
+	fn budget_exception(&self, location: &SourceLocation, err: BudgetError) -> Exception {
+		let kind = match err {
+			BudgetError::Interrupted => ExceptionKind::Cancelled,
+			_ => ExceptionKind::LimitExceeded,
+		};
+		self.exception_of_kind(kind, location, err.to_string())
 	}
 
 	pub fn exception<S: Into<String>>(&self, location: &SourceLocation, message: S) -> Exception {
-		Exception {
+		self.exception_of_kind(ExceptionKind::Runtime, location, message)
+	}
+
+	pub fn exception_of_kind<S: Into<String>>(
+		&self,
+		kind: ExceptionKind,
+		location: &SourceLocation,
+		message: S,
+	) -> Exception {
+		let stack = self.callstack.borrow().clone();
+
+		// native code often can't tell where it was called from, so point at the
+		// innermost callable instead
+		let location = match location {
+			SourceLocation::Unknown => stack
+				.iter()
+				.rev()
+				.map(|frame| &frame.location)
+				.find(|l| **l != SourceLocation::Unknown)
+				.unwrap_or(location)
+				.clone(),
+			_ => location.clone(),
+		};
+
+		let exception = Exception {
+			kind,
 			message: message.into(),
-			location: location.clone(),
-			stack: self.callstack.borrow().clone(),
-		}
+			location,
+			stack,
+		};
+		self.emit(EvalEvent::Exception(&exception));
+		exception
 	}
 }
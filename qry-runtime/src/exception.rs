@@ -1,14 +1,36 @@
 use super::StackFrame;
 use qry_lang::SourceLocation;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionKind {
+	Runtime,
+	// raised when the host triggers an InterruptHandle
+	Cancelled,
+	LimitExceeded,
+	// the sandbox doesn't grant a capability or connection target
+	Denied,
+}
+
 #[derive(Debug, Clone)]
 pub struct Exception {
+	pub kind: ExceptionKind,
 	pub message: String,
 	pub location: SourceLocation,
 	pub stack: Vec<StackFrame>,
 }
 
-fn location_for_stacktrace(location: &SourceLocation) -> String {
+impl Exception {
+	// used by evaluators for runtime checks that aren't made from inside a
+	// stack frame
+	pub fn or_located_at(mut self, location: &SourceLocation) -> Self {
+		if self.location == SourceLocation::Unknown {
+			self.location = location.clone();
+		}
+		self
+	}
+}
+
+pub fn location_for_stacktrace(location: &SourceLocation) -> String {
 	match location {
 		SourceLocation::User { line, file } => format!("{}:{}", file, line),
 		SourceLocation::Native { line, file } => format!("native: {}:{}", file, line),
@@ -21,11 +43,20 @@ impl std::fmt::Display for Exception {
 		writeln!(f, "exception stacktrace:")?;
 
 		for frame in &self.stack {
+			let arg_types = frame
+				.arg_types
+				.iter()
+				.map(|t| t.to_string())
+				.collect::<Vec<_>>()
+				.join(", ");
+
 			writeln!(
 				f,
-				"  in {} ({})",
+				"  in {}({}) at {} (defined at {})",
 				frame.name,
-				location_for_stacktrace(&frame.location)
+				arg_types,
+				location_for_stacktrace(&frame.location),
+				location_for_stacktrace(&frame.definition)
 			)?;
 		}
 
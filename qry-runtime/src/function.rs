@@ -1,19 +1,51 @@
 use super::{
-	assign_value, eval, eval_multi, Callable, Environment, EvalContext, EvalResult, Parameter,
-	Signature, Value,
+	assign_value, call_compiled, eval, eval_multi, expect_method, expect_type, Callable,
+	CompiledBody, Environment, EvalContext, EvalResult, Parameter, Shared, Signature, Type, Value,
 };
 use qry_lang::{FunctionHeader, ParameterDef, SourceLocation, SyntaxNode};
-use std::rc::Rc;
 
 #[derive(Debug)]
 pub struct Function {
 	pub body: Vec<SyntaxNode>,
+	pub compiled: Option<Shared<CompiledBody>>,
 	pub signature: Signature,
-	pub env: Environment,
+	pub env: Shared<Environment>,
 	pub name: String,
 	pub location: SourceLocation,
 }
 
+pub fn function_name<T>(header: &FunctionHeader<T>) -> &str {
+	match header {
+		FunctionHeader::Function(Some(name)) => name,
+		FunctionHeader::Function(None) => "<anonymous function>",
+		FunctionHeader::MethodImpl { .. } => "<method impl>",
+	}
+}
+
+pub fn function_signature(
+	param_names: &[String],
+	param_types: Vec<Type>,
+	return_type: Type,
+	doc: Option<String>,
+) -> Signature {
+	let params = param_types
+		.into_iter()
+		.zip(param_names)
+		.map(|(param_type, name)| Parameter {
+			name: name.clone(),
+			param_type,
+		})
+		.collect();
+
+	Signature {
+		params,
+		trailing_type: None,
+		named_trailing_type: None,
+		return_type,
+		doc,
+	}
+}
+
 pub fn eval_function_decl(
 	ctx: &EvalContext,
 	location: &SourceLocation,
@@ -21,42 +53,22 @@ pub fn eval_function_decl(
 	params: &[ParameterDef<SyntaxNode>],
 	return_type: &SyntaxNode,
 	body: &[SyntaxNode],
+	doc: &Option<String>,
 ) -> EvalResult<Value> {
 	let param_types = params
 		.iter()
-		.map(|p| match eval(ctx, &p.param_type)? {
-			Value::Type(t) => Ok(t),
-			_ => Err(ctx.exception(&p.param_type.location, "expected a type")),
-		})
+		.map(|p| expect_type(ctx, eval(ctx, &p.param_type)?, &p.param_type.location))
 		.collect::<Result<Vec<_>, _>>()?;
 
-	let params = param_types
-		.iter()
-		.zip(params)
-		.map(|(t, def)| Parameter {
-			name: def.name.clone(),
-			param_type: t.clone(),
-		})
-		.collect();
+	let param_names = params.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+	let return_type = expect_type(ctx, eval(ctx, return_type)?, &return_type.location)?;
 
-	let function = Rc::new(Function {
+	let function = Shared::new(Function {
 		body: body.to_vec(),
-		signature: Signature {
-			params,
-			trailing_type: None,
-			named_trailing_type: None,
-			return_type: match eval(ctx, return_type)? {
-				Value::Type(t) => t,
-				_ => return Err(ctx.exception(&return_type.location, "expected a type")),
-			},
-		},
-		env: (*ctx.env).clone(),
-		name: match header {
-			FunctionHeader::Function(Some(name)) => name,
-			FunctionHeader::Function(None) => "<anonymous function>",
-			FunctionHeader::MethodImpl { .. } => "<method impl>",
-		}
-		.into(),
+		compiled: None,
+		signature: function_signature(&param_names, param_types, return_type, doc.clone()),
+		env: ctx.env.snapshot(),
+		name: function_name(header).into(),
 		location: location.clone(),
 	});
 
@@ -67,8 +79,8 @@ pub fn eval_function_decl(
 			assign_value(ctx, name, function_val.clone())?;
 		}
 		FunctionHeader::MethodImpl { impl_for } => {
-			let method = eval(ctx, impl_for)?.as_method();
-			method.register(function);
+			let method = expect_method(ctx, eval(ctx, impl_for)?, &impl_for.location)?;
+			method.register(ctx, function)?;
 		}
 		_ => (),
 	};
@@ -90,6 +102,10 @@ impl Callable for Function {
 	}
 
 	fn call(&self, ctx: &EvalContext, args: &[Value], _: &[(&str, Value)]) -> EvalResult<Value> {
+		if let Some(compiled) = &self.compiled {
+			return call_compiled(ctx, &self.env, compiled, args);
+		}
+
 		let func_body_env = self.env.child("funceval");
 
 		for (value, param) in args.iter().zip(&self.signature.params) {
@@ -98,4 +114,8 @@ impl Callable for Function {
 
 		eval_multi(&ctx.child(func_body_env), &self.body)
 	}
+
+	fn captured_env(&self) -> Option<&Shared<Environment>> {
+		Some(&self.env)
+	}
 }
@@ -0,0 +1,221 @@
+use super::{Callable, Environment, Method, Shared, Value, Weak};
+#[cfg(feature = "sync")]
+use lazy_static::lazy_static;
+#[cfg(not(feature = "sync"))]
+use std::cell::RefCell;
+use std::collections::HashMap;
+#[cfg(feature = "sync")]
+use std::sync::Mutex;
+
+// reference counting can't free a cycle, such as a method impl whose captured
+// environment holds the library holding the method. Every environment is
+// tracked here so that cycles of environments, closures and methods nothing
+// else refers to can be found and broken
+
+// collections start once this many environments are tracked, or twice what
+// was alive after the last one if that's more
+const MIN_THRESHOLD: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GcStats {
+	// environments still alive
+	pub environments: usize,
+	pub collections: usize,
+	// environments, closures and methods freed by collections so far
+	pub collected: usize,
+}
+
+struct Registry {
+	envs: Vec<Weak<Environment>>,
+	threshold: usize,
+	collecting: bool,
+	stats: GcStats,
+}
+
+// without the sync feature environments can't leave the thread that created
+// them, so each thread tracks its own
+#[cfg(not(feature = "sync"))]
+thread_local! {
+	static REGISTRY: RefCell<Registry> = RefCell::new(Registry::new());
+}
+
+// with it they can be in use on any thread, so there's one registry for all of
+// them. Collections only happen when the host asks for one, at a point where
+// no other thread is evaluating
+#[cfg(feature = "sync")]
+lazy_static! {
+	static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+}
+
+impl Registry {
+	fn new() -> Self {
+		Registry {
+			envs: Vec::new(),
+			threshold: MIN_THRESHOLD,
+			collecting: false,
+			stats: GcStats::default(),
+		}
+	}
+}
+
+#[cfg(not(feature = "sync"))]
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+	REGISTRY.with(|r| f(&mut r.borrow_mut()))
+}
+
+#[cfg(feature = "sync")]
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+	f(&mut REGISTRY.lock().unwrap_or_else(|err| err.into_inner()))
+}
+
+pub(crate) fn track(env: Environment) -> Shared<Environment> {
+	let env = Shared::new(env);
+	let collect = with_registry(|r| {
+		r.envs.push(Shared::downgrade(&env));
+		cfg!(not(feature = "sync")) && !r.collecting && r.envs.len() >= r.threshold
+	});
+	if collect {
+		collect_cycles();
+	}
+	env
+}
+
+fn live_environments(envs: &mut Vec<Weak<Environment>>) -> usize {
+	envs.retain(|env| env.strong_count() > 0);
+	envs.len()
+}
+
+pub fn gc_stats() -> GcStats {
+	with_registry(|r| {
+		let environments = live_environments(&mut r.envs);
+		GcStats {
+			environments,
+			..r.stats
+		}
+	})
+}
+
+enum Node {
+	Env(Shared<Environment>),
+	Closure(Shared<dyn Callable>),
+	Method(Shared<Method>),
+}
+
+impl Node {
+	fn from_value(value: &Value) -> Option<Node> {
+		match value {
+			Value::Function(function) => Some(Node::Closure(function.clone())),
+			Value::Method(method) => Some(Node::Method(method.clone())),
+			Value::Library(env) => Some(Node::Env(env.clone())),
+			_ => None,
+		}
+	}
+
+	fn key(&self) -> *const () {
+		match self {
+			Node::Env(env) => Shared::as_ptr(env) as *const (),
+			Node::Closure(closure) => Shared::as_ptr(closure) as *const (),
+			Node::Method(method) => Shared::as_ptr(method) as *const (),
+		}
+	}
+
+	fn strong_count(&self) -> usize {
+		match self {
+			Node::Env(env) => Shared::strong_count(env),
+			Node::Closure(closure) => Shared::strong_count(closure),
+			Node::Method(method) => Shared::strong_count(method),
+		}
+	}
+
+	// one node per reference held, so each is counted as many times as it's
+	// referred to. References the collector doesn't follow, such as from inside
+	// lists, only make it more likely that something is kept
+	fn children(&self) -> Vec<Node> {
+		match self {
+			Node::Env(env) => env.values().iter().filter_map(Node::from_value).collect(),
+			Node::Closure(closure) => closure
+				.captured_env()
+				.cloned()
+				.map(Node::Env)
+				.into_iter()
+				.collect(),
+			Node::Method(method) => method
+				.callables()
+				.into_iter()
+				.filter(|c| c.captured_env().is_some())
+				.map(Node::Closure)
+				.collect(),
+		}
+	}
+}
+
+// finds the environments, closures and methods that are only referred to by
+// each other and breaks the cycles between them, returning how many were freed.
+// With the sync feature this covers every thread, so nothing else may be
+// evaluating while it runs
+pub fn collect_cycles() -> usize {
+	let envs = with_registry(|r| {
+		r.collecting = true;
+		live_environments(&mut r.envs);
+		r.envs.iter().filter_map(Weak::upgrade).collect::<Vec<_>>()
+	});
+
+	let mut nodes = envs.into_iter().map(Node::Env).collect::<Vec<_>>();
+	let mut index = nodes
+		.iter()
+		.enumerate()
+		.map(|(i, node)| (node.key(), i))
+		.collect::<HashMap<_, _>>();
+	let mut edges = Vec::new();
+	let mut internal = vec![0; nodes.len()];
+	let mut i = 0;
+	while i < nodes.len() {
+		let mut targets = Vec::new();
+		for child in nodes[i].children() {
+			let target = *index.entry(child.key()).or_insert_with(|| {
+				nodes.push(child);
+				internal.push(0);
+				nodes.len() - 1
+			});
+			internal[target] += 1;
+			targets.push(target);
+		}
+		edges.push(targets);
+		i += 1;
+	}
+
+	// whatever has more references than the graph accounts for, besides the
+	// one in nodes, is referred to from outside it and keeps its children alive
+	let mut reachable = vec![false; nodes.len()];
+	let mut pending = (0..nodes.len())
+		.filter(|&i| nodes[i].strong_count() > internal[i] + 1)
+		.collect::<Vec<_>>();
+	while let Some(i) = pending.pop() {
+		if !reachable[i] {
+			reachable[i] = true;
+			pending.extend(&edges[i]);
+		}
+	}
+
+	// every cycle runs through an environment, so clearing the unreachable ones
+	// frees the rest once nodes is dropped
+	let mut collected = 0;
+	for (node, reachable) in nodes.iter().zip(&reachable) {
+		if !reachable {
+			if let Node::Env(env) = node {
+				env.clear();
+			}
+			collected += 1;
+		}
+	}
+	drop(nodes);
+
+	with_registry(|r| {
+		let live = live_environments(&mut r.envs);
+		r.threshold = MIN_THRESHOLD.max(live * 2);
+		r.collecting = false;
+		r.stats.collections += 1;
+		r.stats.collected += collected;
+	});
+	collected
+}
@@ -2,23 +2,69 @@
 
 mod builtin;
 mod callable;
+mod checker;
+mod compiler;
+mod context_builder;
+mod convert;
+mod debugger;
+mod doc;
 mod environment;
+mod equality;
 mod eval;
 mod eval_context;
 mod exception;
 mod function;
+mod gc;
+mod limits;
+mod list;
+mod manifest;
 mod method;
+mod modules;
+mod numeric;
+mod observer;
+mod output;
+mod profiler;
+mod sandbox;
 mod stdlib;
+mod sync;
+#[cfg(feature = "tracing")]
+mod tracing_observer;
 mod types;
 mod value;
+mod vm;
+mod workspace;
 
 pub use builtin::*;
 pub use callable::*;
+pub use checker::*;
+pub use compiler::*;
+pub use context_builder::*;
+pub use convert::*;
+pub use debugger::*;
+pub use doc::*;
 pub use environment::*;
+pub use equality::*;
 pub use eval::*;
 pub use eval_context::*;
 pub use exception::*;
 pub use function::*;
+pub use gc::*;
+pub use limits::*;
+pub use list::*;
+pub use manifest::*;
 pub use method::*;
+pub use modules::*;
+pub use numeric::*;
+pub use observer::*;
+pub use output::*;
+pub use profiler::*;
+pub use sandbox::*;
+pub use sync::*;
+#[cfg(feature = "tracing")]
+pub use tracing_observer::*;
 pub use types::*;
 pub use value::*;
+pub use vm::*;
+pub use workspace::*;
+
+pub use qry_derive::{qry_methods, NativeType};
@@ -0,0 +1,112 @@
+use super::Lock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// how many steps run between clock reads when a timeout is set
+const TIMEOUT_CHECK_INTERVAL: u64 = 256;
+
+// nothing is limited by default
+#[derive(Debug, Clone, Default)]
+pub struct EvalLimits {
+	// the tree-walker recurses on the native stack for every call, so without
+	// this runaway recursion overflows it
+	pub max_call_depth: Option<usize>,
+	// a step is one syntax node for the tree-walker and one op for the vm
+	pub max_steps: Option<u64>,
+	pub timeout: Option<Duration>,
+	pub max_rows: Option<usize>,
+}
+
+// can be sent to another thread (e.g. a signal handler) to stop evaluation at
+// the next step
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+	requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+	pub fn interrupt(&self) {
+		self.requested.store(true, Ordering::SeqCst);
+	}
+
+	fn take(&self) -> bool {
+		self.requested.swap(false, Ordering::SeqCst)
+	}
+}
+
+#[derive(Debug)]
+pub enum BudgetError {
+	Interrupted,
+	StepLimit(u64),
+	Timeout(Duration),
+}
+
+impl std::fmt::Display for BudgetError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		match self {
+			Self::Interrupted => write!(f, "interrupted"),
+			Self::StepLimit(limit) => write!(f, "step limit exceeded ({} steps)", limit),
+			Self::Timeout(timeout) => write!(f, "timed out after {:?}", timeout),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct ExecutionBudget {
+	pub limits: Lock<EvalLimits>,
+	interrupt: InterruptHandle,
+	steps: AtomicU64,
+	started: Lock<Instant>,
+}
+
+impl ExecutionBudget {
+	pub fn new(limits: EvalLimits) -> Self {
+		ExecutionBudget {
+			limits: Lock::new(limits),
+			interrupt: InterruptHandle::default(),
+			steps: AtomicU64::new(0),
+			started: Lock::new(Instant::now()),
+		}
+	}
+
+	pub fn interrupt_handle(&self) -> InterruptHandle {
+		self.interrupt.clone()
+	}
+
+	// an interrupt that arrived while nothing was running shouldn't cancel the
+	// next run
+	pub fn reset(&self) {
+		self.steps.store(0, Ordering::Relaxed);
+		*self.started.borrow_mut() = Instant::now();
+		self.interrupt.take();
+	}
+
+	pub fn check_interrupt(&self) -> Result<(), BudgetError> {
+		match self.interrupt.take() {
+			true => Err(BudgetError::Interrupted),
+			false => Ok(()),
+		}
+	}
+
+	pub fn step(&self) -> Result<(), BudgetError> {
+		self.check_interrupt()?;
+
+		let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+
+		let limits = self.limits.borrow();
+		if let Some(max_steps) = limits.max_steps {
+			if steps > max_steps {
+				return Err(BudgetError::StepLimit(max_steps));
+			}
+		}
+
+		if let Some(timeout) = limits.timeout {
+			if steps % TIMEOUT_CHECK_INTERVAL == 0 && self.started.borrow().elapsed() > timeout {
+				return Err(BudgetError::Timeout(timeout));
+			}
+		}
+
+		Ok(())
+	}
+}
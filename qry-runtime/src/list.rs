@@ -0,0 +1,212 @@
+use super::{Shared, Value};
+use std::iter::FromIterator;
+
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+#[derive(Debug, Clone)]
+enum Node {
+	Branch(Vec<Shared<Node>>),
+	Leaf(Vec<Value>),
+}
+
+impl Node {
+	fn empty(shift: u32) -> Shared<Node> {
+		Shared::new(match shift {
+			0 => Node::Leaf(Vec::with_capacity(WIDTH)),
+			_ => Node::Branch(Vec::with_capacity(WIDTH)),
+		})
+	}
+
+	// copies the path down to index first if any of it is shared
+	fn set(node: &mut Shared<Node>, shift: u32, index: usize, value: Value) {
+		match Shared::make_mut(node) {
+			Node::Branch(children) => {
+				let child = (index >> shift) & MASK;
+				if child == children.len() {
+					children.push(Node::empty(shift - BITS));
+				}
+				Node::set(&mut children[child], shift - BITS, index, value);
+			}
+			Node::Leaf(items) => match index & MASK {
+				i if i == items.len() => items.push(value),
+				i => items[i] = value,
+			},
+		}
+	}
+}
+
+// a persistent vector: items live in a trie of 32 wide nodes that copies of
+// a list share, so cloning one is cheap and a push only copies the nodes on
+// the path to the new item. Slices are views into the same trie, which keeps
+// all of it alive for as long as the slice is
+#[derive(Clone)]
+pub struct List {
+	root: Shared<Node>,
+	// how far to shift an index for the root's slot, 0 when it's a leaf
+	shift: u32,
+	// items in the trie, of which this list is start..end
+	size: usize,
+	start: usize,
+	end: usize,
+}
+
+impl List {
+	pub fn new() -> Self {
+		List {
+			root: Node::empty(0),
+			shift: 0,
+			size: 0,
+			start: 0,
+			end: 0,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+
+	pub fn get(&self, index: usize) -> Option<&Value> {
+		if index >= self.len() {
+			return None;
+		}
+		let index = self.start + index;
+		let (mut node, mut shift) = (&*self.root, self.shift);
+		loop {
+			match node {
+				Node::Branch(children) => {
+					node = &children[(index >> shift) & MASK];
+					shift -= BITS;
+				}
+				Node::Leaf(items) => return Some(&items[index & MASK]),
+			}
+		}
+	}
+
+	pub fn iter(&self) -> Iter<'_> {
+		Iter {
+			list: self,
+			index: 0,
+		}
+	}
+
+	pub fn push(&mut self, value: Value) {
+		// items past the end of a slice aren't part of it, so are overwritten
+		if self.end < self.size {
+			Node::set(&mut self.root, self.shift, self.end, value);
+			self.end += 1;
+			return;
+		}
+
+		if self.size == WIDTH << self.shift {
+			self.root = Shared::new(Node::Branch(vec![self.root.clone()]));
+			self.shift += BITS;
+		}
+		Node::set(&mut self.root, self.shift, self.size, value);
+		self.size += 1;
+		self.end += 1;
+	}
+
+	pub fn append(&self, value: Value) -> List {
+		let mut ret = self.clone();
+		ret.push(value);
+		ret
+	}
+
+	pub fn concat(&self, other: &List) -> List {
+		let mut ret = self.clone();
+		ret.extend(other.iter().cloned());
+		ret
+	}
+
+	// the items from..to, clamped to the list
+	pub fn slice(&self, from: usize, to: usize) -> List {
+		let to = to.min(self.len());
+		let from = from.min(to);
+		List {
+			start: self.start + from,
+			end: self.start + to,
+			..self.clone()
+		}
+	}
+
+	pub fn to_vec(&self) -> Vec<Value> {
+		self.iter().cloned().collect()
+	}
+}
+
+impl Default for List {
+	fn default() -> Self {
+		List::new()
+	}
+}
+
+impl std::fmt::Debug for List {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_list().entries(self.iter()).finish()
+	}
+}
+
+impl Extend<Value> for List {
+	fn extend<I: IntoIterator<Item = Value>>(&mut self, iter: I) {
+		for value in iter {
+			self.push(value);
+		}
+	}
+}
+
+impl FromIterator<Value> for List {
+	fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+		let mut list = List::new();
+		list.extend(iter);
+		list
+	}
+}
+
+impl From<Vec<Value>> for List {
+	fn from(items: Vec<Value>) -> Self {
+		items.into_iter().collect()
+	}
+}
+
+impl From<List> for Vec<Value> {
+	fn from(list: List) -> Self {
+		list.to_vec()
+	}
+}
+
+pub struct Iter<'a> {
+	list: &'a List,
+	index: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = &'a Value;
+
+	fn next(&mut self) -> Option<&'a Value> {
+		let value = self.list.get(self.index)?;
+		self.index += 1;
+		Some(value)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let remaining = self.list.len() - self.index;
+		(remaining, Some(remaining))
+	}
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a List {
+	type Item = &'a Value;
+	type IntoIter = Iter<'a>;
+
+	fn into_iter(self) -> Iter<'a> {
+		self.iter()
+	}
+}
@@ -0,0 +1,151 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILENAME: &str = "qry.toml";
+
+// connection targets that don't name a file, so aren't made relative to the
+// project root
+const IN_MEMORY_TARGET: &str = ":memory:";
+
+#[derive(Debug)]
+pub enum ManifestError {
+	Io(PathBuf, std::io::Error),
+	Parse(PathBuf, toml::de::Error),
+	DependencyCycle(Vec<String>),
+}
+
+impl std::fmt::Display for ManifestError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		match self {
+			Self::Io(path, err) => write!(f, "error reading {}: {}", path.display(), err),
+			Self::Parse(path, err) => write!(f, "error parsing {}: {}", path.display(), err),
+			Self::DependencyCycle(names) => write!(f, "dependency cycle: {}", names.join(" -> ")),
+		}
+	}
+}
+
+fn default_paths() -> Vec<PathBuf> {
+	vec![PathBuf::from(".")]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Project {
+	pub name: String,
+	// module search paths, relative to the project root
+	#[serde(default = "default_paths")]
+	pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+	pub path: PathBuf,
+}
+
+// a dependency's modules are imported under its name, e.g. `use shared::clean`
+// for a dependency called shared
+#[derive(Debug, Clone)]
+pub struct Package {
+	pub name: String,
+	pub search_path: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+	pub project: Project,
+	#[serde(default)]
+	pub connections: BTreeMap<String, String>,
+	#[serde(default)]
+	pub dependencies: BTreeMap<String, Dependency>,
+	#[serde(skip)]
+	pub root: PathBuf,
+	// every dependency, including transitive ones, resolved when loading
+	#[serde(skip)]
+	pub packages: Vec<Package>,
+}
+
+fn read_manifest(file: &Path) -> Result<Manifest, ManifestError> {
+	let src = fs::read_to_string(file).map_err(|err| ManifestError::Io(file.into(), err))?;
+	let mut manifest: Manifest =
+		toml::from_str(&src).map_err(|err| ManifestError::Parse(file.into(), err))?;
+	manifest.root = file.parent().unwrap_or_else(|| Path::new(".")).into();
+	Ok(manifest)
+}
+
+fn collect_packages(
+	manifest: &Manifest,
+	visiting: &mut Vec<String>,
+	packages: &mut Vec<Package>,
+) -> Result<(), ManifestError> {
+	for (name, dependency) in &manifest.dependencies {
+		if visiting.contains(name) {
+			let mut cycle = visiting.clone();
+			cycle.push(name.clone());
+			return Err(ManifestError::DependencyCycle(cycle));
+		}
+		if packages.iter().any(|p| p.name == *name) {
+			continue;
+		}
+
+		let file = manifest.root.join(&dependency.path).join(MANIFEST_FILENAME);
+		let dep_manifest = read_manifest(&file)?;
+
+		visiting.push(name.clone());
+		collect_packages(&dep_manifest, visiting, packages)?;
+		visiting.pop();
+
+		packages.push(Package {
+			name: name.clone(),
+			search_path: dep_manifest.search_path(),
+		});
+	}
+	Ok(())
+}
+
+impl Manifest {
+	pub fn load(file: &Path) -> Result<Self, ManifestError> {
+		let mut manifest = read_manifest(file)?;
+
+		let mut packages = Vec::new();
+		let mut visiting = vec![manifest.project.name.clone()];
+		collect_packages(&manifest, &mut visiting, &mut packages)?;
+		manifest.packages = packages;
+
+		Ok(manifest)
+	}
+
+	// looks for a manifest in dir and then each of its parents
+	pub fn discover(dir: &Path) -> Result<Option<Self>, ManifestError> {
+		for ancestor in dir.ancestors() {
+			let file = ancestor.join(MANIFEST_FILENAME);
+			if file.is_file() {
+				return Self::load(&file).map(Some);
+			}
+		}
+		Ok(None)
+	}
+
+	pub fn search_path(&self) -> Vec<PathBuf> {
+		self
+			.project
+			.paths
+			.iter()
+			.map(|p| self.root.join(p))
+			.collect()
+	}
+
+	pub fn connection_targets(&self) -> Vec<(String, String)> {
+		self
+			.connections
+			.iter()
+			.map(|(name, target)| match target.as_str() {
+				IN_MEMORY_TARGET => (name.clone(), target.clone()),
+				_ => (
+					name.clone(),
+					self.root.join(target).to_string_lossy().into_owned(),
+				),
+			})
+			.collect()
+	}
+}
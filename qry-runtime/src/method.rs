@@ -1,19 +1,111 @@
 use super::{
-	Builtin, BuiltinFunc, Callable, EvalContext, EvalResult, Parameter, Signature, Type, Value,
+	check_arity, BigInt, Builtin, BuiltinClosure, BuiltinFunc, Callable, Decimal, EvalContext,
+	EvalResult, IntoBuiltin, Lock, Parameter, Shared, Signature, StackFrame, Type, Value,
 };
 use qry_lang::SourceLocation;
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::panic::Location;
-use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum DispatchError {
+	NoMatch,
+	Ambiguous(Vec<Signature>),
+}
+
+// lower is more specific; None means the impl can't accept the argument
+pub fn type_distance(param_type: &Type, arg_type: &Type) -> Option<u32> {
+	match (param_type, arg_type) {
+		_ if param_type == arg_type => Some(0),
+		// a union member is less specific than the bare type but still beats a
+		// promotion to that type
+		(Type::Union(members), _) => members
+			.iter()
+			.filter_map(|m| type_distance(m, arg_type))
+			.min()
+			.map(|d| d + 1),
+		(Type::Float, Type::Int) | (Type::BigInt, Type::Int) | (Type::Decimal, Type::Int) => Some(2),
+		(Type::Interface(_), _) if param_type.accepts(arg_type) => Some(3),
+		(Type::Any, _) => Some(4),
+		_ => None,
+	}
+}
+
+pub fn select_by_specificity<'a, T>(
+	candidates: impl IntoIterator<Item = (&'a [Type], T)>,
+	arg_types: &[Type],
+) -> Result<T, Vec<T>> {
+	let mut scored = candidates
+		.into_iter()
+		.filter_map(|(param_types, candidate)| {
+			let distances = param_types
+				.iter()
+				.zip(arg_types)
+				.map(|(p, a)| type_distance(p, a))
+				.collect::<Option<Vec<_>>>()?;
+			Some((distances, candidate))
+		})
+		.collect::<Vec<_>>();
+
+	let dominates = |a: &[u32], b: &[u32]| a.iter().zip(b).all(|(x, y)| x <= y) && a != b;
+	let best = (0..scored.len())
+		.filter(|&i| {
+			!scored
+				.iter()
+				.any(|(other, _)| dominates(other, &scored[i].0))
+		})
+		.collect::<Vec<_>>();
+
+	let mut best_candidates = Vec::new();
+	for i in best.into_iter().rev() {
+		best_candidates.push(scored.remove(i).1);
+	}
+	best_candidates.reverse();
+
+	if best_candidates.len() == 1 {
+		Ok(best_candidates.pop().unwrap())
+	} else {
+		Err(best_candidates)
+	}
+}
+
+// exact targets come first so a BigInt | Float param doesn't lose precision
+fn promote_int(param_type: &Type, val: i64) -> Value {
+	if param_type.accepts(&Type::BigInt) {
+		Value::BigInt(BigInt::from(val))
+	} else if param_type.accepts(&Type::Decimal) {
+		Value::Decimal(Decimal::from(val))
+	} else if param_type.accepts(&Type::Float) {
+		Value::Float(val as f64)
+	} else {
+		Value::Int(val)
+	}
+}
+
+pub fn promote_args(signature: &Signature, args: &[Value]) -> Vec<Value> {
+	args
+		.iter()
+		.enumerate()
+		.map(
+			|(i, arg)| match (signature.params.get(i).map(|p| &p.param_type), arg) {
+				(Some(param_type), Value::Int(v)) if !param_type.accepts(&Type::Int) => {
+					promote_int(param_type, *v)
+				}
+				_ => arg.clone(),
+			},
+		)
+		.collect()
+}
 
 #[derive(Debug, Clone)]
 pub struct Method {
 	name: String,
 	signature: Signature,
-	impls: RefCell<HashMap<Vec<Type>, Rc<dyn Callable>>>,
+	impls: Lock<HashMap<Vec<Type>, Shared<dyn Callable>>>,
 	fixed_return_type: Option<Type>,
-	default_impl: Option<Rc<dyn Callable>>,
+	default_impl: Option<Shared<dyn Callable>>,
+	// set once the method exists, since runtime methods are shared by every
+	// library that adds impls to them
+	doc: Lock<Option<String>>,
 }
 
 impl Method {
@@ -21,8 +113,8 @@ impl Method {
 		name: &str,
 		dispatch_param_names: &[&str],
 		fixed_return_type: Option<Type>,
-		default_impl: Option<Rc<dyn Callable>>,
-	) -> Rc<Self> {
+		default_impl: Option<Shared<dyn Callable>>,
+	) -> Shared<Self> {
 		let params = dispatch_param_names
 			.iter()
 			.map(|n| Parameter {
@@ -31,7 +123,7 @@ impl Method {
 			})
 			.collect::<Vec<_>>();
 
-		Rc::new(Self {
+		Shared::new(Self {
 			name: name.into(),
 			fixed_return_type: fixed_return_type.clone(),
 			signature: Signature {
@@ -40,9 +132,11 @@ impl Method {
 				params,
 				trailing_type: None,
 				named_trailing_type: None,
+				doc: None,
 			},
 			impls: Default::default(),
 			default_impl,
+			doc: Default::default(),
 		})
 	}
 
@@ -50,6 +144,25 @@ impl Method {
 		&self.name
 	}
 
+	pub fn set_doc(&self, doc: &str) {
+		*self.doc.borrow_mut() = Some(doc.into());
+	}
+
+	pub fn has_default_impl(&self) -> bool {
+		self.default_impl.is_some()
+	}
+
+	// the registered impls and the default one, if any
+	pub(crate) fn callables(&self) -> Vec<Shared<dyn Callable>> {
+		self
+			.impls
+			.borrow()
+			.values()
+			.chain(self.default_impl.iter())
+			.cloned()
+			.collect()
+	}
+
 	pub fn supported_signatures(&self) -> Vec<Signature> {
 		self
 			.impls
@@ -59,39 +172,96 @@ impl Method {
 			.collect()
 	}
 
-	fn get_sig_key(&self, types: &[Type]) -> Vec<Type> {
-		types[..self.signature.params.len()].to_owned()
+	// only impls declared for the type itself count, an Any impl or one taking
+	// another interface would make every type conform
+	pub fn implements(&self, t: &Type) -> bool {
+		self.impls.borrow().keys().any(|key| match key.first() {
+			Some(Type::Union(members)) => members.contains(t),
+			Some(param_type) => param_type == t,
+			None => false,
+		})
+	}
+
+	fn get_sig_key(&self, types: &[Type]) -> Option<Vec<Type>> {
+		types.get(..self.signature.params.len()).map(|t| t.to_vec())
 	}
 
-	pub fn register(&self, callable: Rc<dyn Callable>) {
+	fn impl_key(&self, signature: &Signature) -> Result<Vec<Type>, String> {
 		if let Some(return_type) = &self.fixed_return_type {
-			assert_eq!(*return_type, callable.signature().return_type)
+			if *return_type != signature.return_type {
+				return Err(format!(
+					"impl of method '{}' must return {}, got {}",
+					self.name, return_type, signature.return_type
+				));
+			}
 		}
 
-		let key = self.get_sig_key(
-			&callable
-				.signature()
-				.params
-				.iter()
-				.map(|p| p.param_type.clone())
-				.collect::<Vec<_>>(),
-		);
-		self.impls.borrow_mut().insert(key, callable);
+		let param_types = signature
+			.params
+			.iter()
+			.map(|p| p.param_type.clone())
+			.collect::<Vec<_>>();
+
+		self.get_sig_key(&param_types).ok_or_else(|| {
+			format!(
+				"impl of method '{}' must take at least {} params, got {}",
+				self.name,
+				self.signature.params.len(),
+				param_types.len()
+			)
+		})
 	}
 
+	pub fn register(&self, ctx: &EvalContext, callable: Shared<dyn Callable>) -> EvalResult<()> {
+		match self.impl_key(callable.signature()) {
+			Ok(key) => {
+				self.impls.borrow_mut().insert(key, callable);
+				Ok(())
+			}
+			Err(message) => Err(ctx.exception(callable.source_location(), message)),
+		}
+	}
+
+	// builtin impls are registered by the host while setting up libraries, so a
+	// bad signature here is a bug in the host rather than in a script
 	#[track_caller]
 	pub fn register_builtin(&self, signature: Signature, func: BuiltinFunc) {
-		self.register(Builtin::new(
-			&self.name,
-			signature,
-			Location::caller().into(),
-			func,
-		));
+		self.register_builtin_closure(signature, Shared::new(func));
+	}
+
+	#[track_caller]
+	pub fn register_builtin_closure(&self, signature: Signature, func: BuiltinClosure) {
+		let key = self
+			.impl_key(&signature)
+			.unwrap_or_else(|message| panic!("{}", message));
+
+		let builtin = Builtin::new_closure(&self.name, signature, Location::caller().into(), func);
+		self.impls.borrow_mut().insert(key, builtin);
+	}
+
+	#[track_caller]
+	pub fn register_fn<Args, F: IntoBuiltin<Args>>(&self, func: F) {
+		self.register_builtin_closure(F::signature(), func.into_closure());
 	}
 
-	pub fn resolve(&self, types: &[Type]) -> Option<Rc<dyn Callable>> {
-		let key = self.get_sig_key(types);
-		self.impls.borrow().get(&key).cloned()
+	pub fn resolve(&self, types: &[Type]) -> Result<Shared<dyn Callable>, DispatchError> {
+		let key = match self.get_sig_key(types) {
+			Some(key) => key,
+			None => return Err(DispatchError::NoMatch),
+		};
+		let impls = self.impls.borrow();
+		if let Some(exact) = impls.get(&key) {
+			return Ok(exact.clone());
+		}
+
+		let candidates = impls.iter().map(|(k, v)| (k.as_slice(), v));
+		match select_by_specificity(candidates, &key) {
+			Ok(callable) => Ok(callable.clone()),
+			Err(ambiguous) if ambiguous.is_empty() => Err(DispatchError::NoMatch),
+			Err(ambiguous) => Err(DispatchError::Ambiguous(
+				ambiguous.iter().map(|c| c.signature().clone()).collect(),
+			)),
+		}
 	}
 }
 
@@ -108,20 +278,49 @@ impl Callable for Method {
 		&self.name
 	}
 
+	fn doc(&self) -> Option<String> {
+		self.doc.borrow().clone()
+	}
+
 	fn call(
 		&self,
 		ctx: &EvalContext,
 		args: &[Value],
 		named_trailing: &[(&str, Value)],
 	) -> EvalResult<Value> {
+		// runtime methods like index are called directly rather than through a
+		// call expression, so the arg count hasn't necessarily been checked yet
+		check_arity(ctx, &self.signature, args.len(), named_trailing.len())?;
 		let arg_types = args.iter().map(|a| a.runtime_type()).collect::<Vec<_>>();
 
-		if let Some(callable) = self.resolve(&arg_types) {
-			callable.call(ctx, args, named_trailing)
-		} else if let Some(callable) = &self.default_impl {
-			callable.call(ctx, args, named_trailing)
-		} else {
-			Err(ctx.exception(&SourceLocation::Unknown, "failed to resolve method"))
+		match self.resolve(&arg_types) {
+			Ok(callable) => {
+				check_arity(ctx, callable.signature(), args.len(), named_trailing.len())?;
+				let args = promote_args(callable.signature(), args);
+				let frame = StackFrame::new(&*callable, &ctx.call_site(), &args);
+				let _stackframe = ctx.with_stack_frame(frame);
+				callable.call(ctx, &args, named_trailing)
+			}
+			Err(DispatchError::NoMatch) => match &self.default_impl {
+				Some(callable) => {
+					let frame = StackFrame::new(&**callable, &ctx.call_site(), args);
+					let _stackframe = ctx.with_stack_frame(frame);
+					callable.call(ctx, args, named_trailing)
+				}
+				None => Err(ctx.exception(&SourceLocation::Unknown, "failed to resolve method")),
+			},
+			Err(DispatchError::Ambiguous(candidates)) => Err(ctx.exception(
+				&SourceLocation::Unknown,
+				format!(
+					"ambiguous call to method '{}', candidates:\n{}",
+					self.name,
+					candidates
+						.iter()
+						.map(|s| format!("  {}", s))
+						.collect::<Vec<_>>()
+						.join("\n")
+				),
+			)),
 		}
 	}
 }
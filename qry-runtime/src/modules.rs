@@ -0,0 +1,153 @@
+use super::{eval, Capability, Environment, EvalContext, EvalResult, Lock, Shared};
+use qry_lang::{parse, SourceLocation, Syntax, SyntaxNode};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const MODULE_EXTENSION: &str = "qry";
+
+#[derive(Debug)]
+pub struct ModuleLoader {
+	search_path: Lock<Vec<PathBuf>>,
+	// dependencies from a project manifest, searched when a path starts with
+	// the package name
+	packages: Lock<HashMap<String, Vec<PathBuf>>>,
+	// modules start from the same bindings as a fresh global environment,
+	// they don't see anything defined by the importing script
+	prelude: Shared<Environment>,
+	cache: Lock<HashMap<PathBuf, Shared<Environment>>>,
+	loading: Lock<Vec<(PathBuf, String)>>,
+}
+
+fn module_name(path: &[String]) -> String {
+	path.join("::")
+}
+
+fn parse_module(
+	ctx: &EvalContext,
+	location: &SourceLocation,
+	file: &PathBuf,
+) -> EvalResult<Vec<SyntaxNode>> {
+	let src = fs::read_to_string(file).map_err(|err| {
+		ctx.exception(
+			location,
+			format!("failed to read module {}: {}", file.display(), err),
+		)
+	})?;
+
+	parse(&src, &file.to_string_lossy()).map_err(|err| {
+		ctx.exception(
+			location,
+			format!("failed to parse module {}: {}", file.display(), err),
+		)
+	})
+}
+
+impl ModuleLoader {
+	pub fn new(search_path: Vec<PathBuf>, prelude: Shared<Environment>) -> Self {
+		ModuleLoader {
+			search_path: Lock::new(search_path),
+			packages: Lock::default(),
+			prelude,
+			cache: Lock::default(),
+			loading: Lock::default(),
+		}
+	}
+
+	pub fn add_search_path(&self, dir: impl Into<PathBuf>) {
+		self.search_path.borrow_mut().push(dir.into());
+	}
+
+	pub fn add_package(&self, name: &str, search_path: Vec<PathBuf>) {
+		self
+			.packages
+			.borrow_mut()
+			.insert(name.to_string(), search_path);
+	}
+
+	// `a::b::c` lives at a/b/c.qry under the first search path that has it,
+	// or at b/c.qry in package a's search path
+	pub fn find(&self, path: &[String]) -> Option<PathBuf> {
+		let package = match path.split_first() {
+			Some((root, rest)) if !rest.is_empty() => self
+				.packages
+				.borrow()
+				.get(root)
+				.map(|dirs| (dirs.clone(), rest)),
+			_ => None,
+		};
+		let (dirs, path) = package.unwrap_or_else(|| (self.search_path.borrow().clone(), path));
+
+		let mut relative = path.iter().collect::<PathBuf>();
+		relative.set_extension(MODULE_EXTENSION);
+
+		dirs
+			.iter()
+			.map(|dir| dir.join(&relative))
+			.find(|file| file.is_file())
+	}
+
+	// names a module marks with `pub`, found without evaluating it so the
+	// checker can resolve imports. Without filesystem access there are none
+	pub fn exported_names(&self, ctx: &EvalContext, path: &[String]) -> Option<Vec<String>> {
+		if !ctx.sandbox.allows(Capability::Filesystem) {
+			return None;
+		}
+		let file = self.find(path)?;
+		let syntax = parse_module(ctx, &SourceLocation::Unknown, &file).ok()?;
+		let names = syntax
+			.iter()
+			.filter_map(|node| match &node.syntax {
+				Syntax::Export(target) => target.defined_name().map(|n| n.to_string()),
+				_ => None,
+			})
+			.collect();
+		Some(names)
+	}
+
+	// evaluates a module the first time it's imported, later imports get the
+	// cached exports. Ok(None) means there's no such module on the search path
+	pub fn load(
+		&self,
+		ctx: &EvalContext,
+		location: &SourceLocation,
+		path: &[String],
+	) -> EvalResult<Option<Shared<Environment>>> {
+		// before searching, so a sandbox can't tell which files exist
+		ctx.require(Capability::Filesystem, location)?;
+		let file = match self.find(path) {
+			Some(file) => file,
+			None => return Ok(None),
+		};
+
+		let key = file.canonicalize().unwrap_or_else(|_| file.clone());
+		if let Some(exports) = self.cache.borrow().get(&key) {
+			return Ok(Some(exports.clone()));
+		}
+
+		let name = module_name(path);
+		if let Some(start) = self.loading.borrow().iter().position(|(f, _)| *f == key) {
+			let cycle = self.loading.borrow()[start..]
+				.iter()
+				.map(|(_, n)| n.clone())
+				.chain(std::iter::once(name))
+				.collect::<Vec<_>>();
+			return Err(ctx.exception(location, format!("import cycle: {}", cycle.join(" -> "))));
+		}
+
+		let syntax = parse_module(ctx, location, &file)?;
+		let env = self.prelude.child(&name);
+		let module_ctx = ctx.child(env.clone());
+
+		self.loading.borrow_mut().push((key.clone(), name.clone()));
+		let result = syntax
+			.iter()
+			.try_for_each(|node| eval(&module_ctx, node).map(|_| ()));
+		self.loading.borrow_mut().pop();
+		result?;
+
+		let exports = env.exported(&name);
+		self.cache.borrow_mut().insert(key, exports.clone());
+		Ok(Some(exports))
+	}
+}
@@ -0,0 +1,455 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+// magnitudes are little-endian base 2^32 limbs without trailing zero limbs,
+// so zero is the empty vec
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+	while mag.last() == Some(&0) {
+		mag.pop();
+	}
+	mag
+}
+
+fn mag_cmp(a: &[u32], b: &[u32]) -> Ordering {
+	a.len()
+		.cmp(&b.len())
+		.then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn mag_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut ret = Vec::with_capacity(a.len().max(b.len()) + 1);
+	let mut carry = 0u64;
+	for i in 0..a.len().max(b.len()) {
+		let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+		ret.push(sum as u32);
+		carry = sum >> 32;
+	}
+	ret.push(carry as u32);
+	trim(ret)
+}
+
+// requires a >= b
+fn mag_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut ret = Vec::with_capacity(a.len());
+	let mut borrow = 0i64;
+	for (i, &limb) in a.iter().enumerate() {
+		let mut diff = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+		borrow = if diff < 0 {
+			diff += 1 << 32;
+			1
+		} else {
+			0
+		};
+		ret.push(diff as u32);
+	}
+	trim(ret)
+}
+
+fn mag_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut ret = vec![0u32; a.len() + b.len()];
+	for (i, &x) in a.iter().enumerate() {
+		let mut carry = 0u64;
+		for (j, &y) in b.iter().enumerate() {
+			let cur = ret[i + j] as u64 + x as u64 * y as u64 + carry;
+			ret[i + j] = cur as u32;
+			carry = cur >> 32;
+		}
+		ret[i + b.len()] = carry as u32;
+	}
+	trim(ret)
+}
+
+fn mag_divmod_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+	let mut quotient = vec![0u32; a.len()];
+	let mut rem = 0u64;
+	for i in (0..a.len()).rev() {
+		let cur = (rem << 32) | a[i] as u64;
+		quotient[i] = (cur / divisor as u64) as u32;
+		rem = cur % divisor as u64;
+	}
+	(trim(quotient), rem as u32)
+}
+
+fn mag_shl1(mag: &mut Vec<u32>, low_bit: u32) {
+	let mut carry = low_bit;
+	for limb in mag.iter_mut() {
+		let next_carry = *limb >> 31;
+		*limb = (*limb << 1) | carry;
+		carry = next_carry;
+	}
+	if carry != 0 {
+		mag.push(carry);
+	}
+}
+
+// plain binary long division, which is plenty for the sizes scripts deal in
+fn mag_divmod(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+	let mut quotient = vec![0u32; a.len()];
+	let mut rem = Vec::new();
+	for i in (0..a.len() * 32).rev() {
+		mag_shl1(&mut rem, (a[i / 32] >> (i % 32)) & 1);
+		if mag_cmp(&rem, b) != Ordering::Less {
+			rem = mag_sub(&rem, b);
+			quotient[i / 32] |= 1 << (i % 32);
+		}
+	}
+	(trim(quotient), rem)
+}
+
+fn split_sign(s: &str) -> (bool, &str) {
+	match s.as_bytes().first() {
+		Some(b'-') => (true, &s[1..]),
+		_ => (false, s),
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+	negative: bool,
+	magnitude: Vec<u32>,
+}
+
+impl BigInt {
+	fn new(negative: bool, magnitude: Vec<u32>) -> Self {
+		let magnitude = trim(magnitude);
+		Self {
+			negative: negative && !magnitude.is_empty(),
+			magnitude,
+		}
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.magnitude.is_empty()
+	}
+
+	pub fn neg(&self) -> BigInt {
+		BigInt::new(!self.negative, self.magnitude.clone())
+	}
+
+	pub fn add(&self, other: &BigInt) -> BigInt {
+		if self.negative == other.negative {
+			return BigInt::new(self.negative, mag_add(&self.magnitude, &other.magnitude));
+		}
+
+		match mag_cmp(&self.magnitude, &other.magnitude) {
+			Ordering::Less => BigInt::new(other.negative, mag_sub(&other.magnitude, &self.magnitude)),
+			_ => BigInt::new(self.negative, mag_sub(&self.magnitude, &other.magnitude)),
+		}
+	}
+
+	pub fn sub(&self, other: &BigInt) -> BigInt {
+		self.add(&other.neg())
+	}
+
+	pub fn mul(&self, other: &BigInt) -> BigInt {
+		BigInt::new(
+			self.negative != other.negative,
+			mag_mul(&self.magnitude, &other.magnitude),
+		)
+	}
+
+	// truncates towards zero like i64 division
+	pub fn checked_div(&self, other: &BigInt) -> Option<BigInt> {
+		if other.is_zero() {
+			return None;
+		}
+
+		let (quotient, _) = mag_divmod(&self.magnitude, &other.magnitude);
+		Some(BigInt::new(self.negative != other.negative, quotient))
+	}
+}
+
+impl From<i64> for BigInt {
+	fn from(val: i64) -> Self {
+		let mag = val.wrapping_abs() as u64;
+		BigInt::new(val < 0, vec![mag as u32, (mag >> 32) as u32])
+	}
+}
+
+impl FromStr for BigInt {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (negative, digits) = split_sign(s);
+
+		if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+			return Err(format!("invalid integer: {}", s));
+		}
+
+		let mut magnitude = Vec::new();
+		for digit in digits.bytes() {
+			magnitude = mag_add(&mag_mul(&magnitude, &[10]), &[(digit - b'0') as u32]);
+		}
+
+		Ok(BigInt::new(negative, magnitude))
+	}
+}
+
+impl std::fmt::Display for BigInt {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		if self.is_zero() {
+			return write!(f, "0");
+		}
+
+		let mut chunks = Vec::new();
+		let mut rest = self.magnitude.clone();
+		while !rest.is_empty() {
+			let (quotient, chunk) = mag_divmod_small(&rest, 1_000_000_000);
+			chunks.push(chunk);
+			rest = quotient;
+		}
+
+		if self.negative {
+			write!(f, "-")?;
+		}
+
+		write!(f, "{}", chunks.pop().unwrap())?;
+		for chunk in chunks.iter().rev() {
+			write!(f, "{:09}", chunk)?;
+		}
+		Ok(())
+	}
+}
+
+impl Ord for BigInt {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (self.negative, other.negative) {
+			(false, true) => Ordering::Greater,
+			(true, false) => Ordering::Less,
+			(false, false) => mag_cmp(&self.magnitude, &other.magnitude),
+			(true, true) => mag_cmp(&other.magnitude, &self.magnitude),
+		}
+	}
+}
+
+impl PartialOrd for BigInt {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+const MAX_DECIMAL_SCALE: u32 = 28;
+
+// digits kept after the point when a division doesn't terminate
+const DIVISION_SCALE: u32 = 16;
+
+fn pow10(exp: u32) -> Option<i128> {
+	10i128.checked_pow(exp)
+}
+
+// rounds half to even, which keeps repeated rounding from drifting
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+	let quotient = numerator / denominator;
+	let rem = numerator % denominator;
+	if rem == 0 {
+		return quotient;
+	}
+
+	let away = if (numerator < 0) == (denominator < 0) {
+		1
+	} else {
+		-1
+	};
+	let doubled_rem = rem.wrapping_abs() as u128 * 2;
+	match doubled_rem.cmp(&(denominator.wrapping_abs() as u128)) {
+		Ordering::Less => quotient,
+		Ordering::Greater => quotient + away,
+		Ordering::Equal if quotient % 2 == 0 => quotient,
+		Ordering::Equal => quotient + away,
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+	mantissa: i128,
+	scale: u32,
+}
+
+impl Decimal {
+	pub fn new(mantissa: i128, scale: u32) -> Option<Decimal> {
+		if scale > MAX_DECIMAL_SCALE {
+			return None;
+		}
+		Some(Decimal { mantissa, scale })
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.mantissa == 0
+	}
+
+	fn rescale(&self, scale: u32) -> Option<Decimal> {
+		let mantissa = if scale >= self.scale {
+			self.mantissa.checked_mul(pow10(scale - self.scale)?)?
+		} else {
+			div_round(self.mantissa, pow10(self.scale - scale)?)
+		};
+		Decimal::new(mantissa, scale)
+	}
+
+	fn strip_zeros(mut self, min_scale: u32) -> Decimal {
+		while self.scale > min_scale && self.mantissa % 10 == 0 {
+			self.mantissa /= 10;
+			self.scale -= 1;
+		}
+		self
+	}
+
+	pub fn checked_neg(&self) -> Option<Decimal> {
+		Decimal::new(self.mantissa.checked_neg()?, self.scale)
+	}
+
+	pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+		let scale = self.scale.max(other.scale);
+		let (a, b) = (self.rescale(scale)?, other.rescale(scale)?);
+		Decimal::new(a.mantissa.checked_add(b.mantissa)?, scale)
+	}
+
+	pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+		self.checked_add(&other.checked_neg()?)
+	}
+
+	pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+		let mantissa = self.mantissa.checked_mul(other.mantissa)?;
+		let scale = self.scale + other.scale;
+		if scale > MAX_DECIMAL_SCALE {
+			Decimal { mantissa, scale }.rescale(MAX_DECIMAL_SCALE)
+		} else {
+			Decimal::new(mantissa, scale)
+		}
+	}
+
+	// None for a zero divisor as well as overflow, callers check is_zero first
+	// to tell them apart
+	pub fn checked_div(&self, other: &Decimal) -> Option<Decimal> {
+		if other.is_zero() {
+			return None;
+		}
+
+		// large dividends keep fewer digits after the point, down to the
+		// operands' own, rather than overflowing while being scaled up
+		let min_scale = self.scale.max(other.scale);
+		let (numerator, scale) =
+			(min_scale..=min_scale.max(DIVISION_SCALE))
+				.rev()
+				.find_map(|scale| {
+					let numerator = self
+						.mantissa
+						.checked_mul(pow10(scale + other.scale - self.scale)?)?;
+					Some((numerator, scale))
+				})?;
+		let mantissa = div_round(numerator, other.mantissa);
+		Some(Decimal::new(mantissa, scale)?.strip_zeros(min_scale))
+	}
+
+	pub fn from_f64(val: f64) -> Option<Decimal> {
+		if !val.is_finite() {
+			return None;
+		}
+
+		// Display for f64 gives the shortest digits that round trip, without an
+		// exponent
+		let decimal = format!("{}", val).parse::<Decimal>().ok()?;
+		Some(decimal.strip_zeros(0))
+	}
+}
+
+impl From<i64> for Decimal {
+	fn from(val: i64) -> Self {
+		Decimal {
+			mantissa: val as i128,
+			scale: 0,
+		}
+	}
+}
+
+impl FromStr for Decimal {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("invalid decimal: {}", s);
+		let (negative, unsigned) = split_sign(s);
+
+		let (int_digits, frac_digits) = match unsigned.find('.') {
+			Some(idx) => (&unsigned[..idx], &unsigned[idx + 1..]),
+			None => (unsigned, ""),
+		};
+
+		let all_digits = int_digits.bytes().chain(frac_digits.bytes());
+		if int_digits.is_empty() || !all_digits.clone().all(|b| b.is_ascii_digit()) {
+			return Err(invalid());
+		}
+
+		let mut mantissa = 0i128;
+		for digit in all_digits {
+			mantissa = mantissa
+				.checked_mul(10)
+				.and_then(|m| m.checked_add((digit - b'0') as i128))
+				.ok_or_else(|| format!("decimal out of range: {}", s))?;
+		}
+
+		if negative {
+			mantissa = -mantissa;
+		}
+
+		Decimal::new(mantissa, frac_digits.len() as u32)
+			.ok_or_else(|| format!("too many decimal places: {}", s))
+	}
+}
+
+impl std::fmt::Display for Decimal {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		let digits = format!(
+			"{:0width$}",
+			self.mantissa.wrapping_abs() as u128,
+			width = self.scale as usize + 1
+		);
+		let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+
+		if self.mantissa < 0 {
+			write!(f, "-")?;
+		}
+
+		if frac_part.is_empty() {
+			write!(f, "{}", int_part)
+		} else {
+			write!(f, "{}.{}", int_part, frac_part)
+		}
+	}
+}
+
+impl Ord for Decimal {
+	// compares whole and fractional parts separately so values with very
+	// different scales don't need to be aligned in an i128
+	fn cmp(&self, other: &Self) -> Ordering {
+		let scale = self.scale.max(other.scale);
+		let split = |d: &Decimal| {
+			let unit = pow10(d.scale).unwrap();
+			let frac = (d.mantissa % unit) * pow10(scale - d.scale).unwrap();
+			(d.mantissa / unit, frac)
+		};
+		split(self).cmp(&split(other))
+	}
+}
+
+impl PartialOrd for Decimal {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Decimal {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Decimal {}
+
+// equal decimals can differ in scale, so trailing zeros are dropped first
+impl std::hash::Hash for Decimal {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		let stripped = self.strip_zeros(0);
+		stripped.mantissa.hash(state);
+		stripped.scale.hash(state);
+	}
+}
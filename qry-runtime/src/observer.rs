@@ -0,0 +1,44 @@
+use super::{EvalContext, EvalResult, Exception, MaybeSync, Value};
+use qry_lang::SourceLocation;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum EvalEvent<'a> {
+	CallEnter {
+		name: &'a str,
+		location: &'a SourceLocation,
+		args: &'a [Value],
+	},
+	CallExit {
+		name: &'a str,
+		result: &'a EvalResult<Value>,
+	},
+	Assign {
+		name: &'a str,
+		value: &'a Value,
+	},
+	// sent when the exception is created, so one that's caught and handled by
+	// native code still shows up
+	Exception(&'a Exception),
+	SqlRendered {
+		sql: &'a str,
+	},
+	// rows is the number affected for a statement, or returned for a query
+	SqlExecuted {
+		sql: &'a str,
+		rows: usize,
+		elapsed: Duration,
+	},
+}
+
+// sees everything the runtime does, for hosts that want to log, audit or
+// meter scripts. Called on the evaluating thread, so should be quick
+pub trait Observer: MaybeSync {
+	fn event(&self, ctx: &EvalContext, event: &EvalEvent);
+}
+
+impl std::fmt::Debug for dyn Observer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Observer")
+	}
+}
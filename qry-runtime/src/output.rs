@@ -0,0 +1,86 @@
+use super::{Lock, MaybeSync, Shared};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+	Text(String),
+	// a DataFrame, with its cells already formatted and nulls as None. text is
+	// how it prints for sinks that only handle text
+	Table {
+		columns: Vec<String>,
+		rows: Vec<Vec<Option<String>>>,
+		text: String,
+	},
+}
+
+impl Output {
+	pub fn text(&self) -> &str {
+		match self {
+			Output::Text(text) => text,
+			Output::Table { text, .. } => text,
+		}
+	}
+}
+
+// where print and display send their output. Closures taking an &Output are
+// sinks too, for hosts that forward it somewhere of their own
+pub trait OutputSink: MaybeSync {
+	fn write(&self, output: &Output);
+}
+
+impl<F: Fn(&Output) + MaybeSync> OutputSink for F {
+	fn write(&self, output: &Output) {
+		self(output)
+	}
+}
+
+impl std::fmt::Debug for dyn OutputSink {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "OutputSink")
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+	fn write(&self, output: &Output) {
+		println!("{}", output.text());
+	}
+}
+
+// keeps everything written to it, clones share the same buffer so one can be
+// handed to the context and another kept to read it back
+#[derive(Debug, Default, Clone)]
+pub struct BufferSink {
+	outputs: Shared<Lock<Vec<Output>>>,
+}
+
+impl BufferSink {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn outputs(&self) -> Vec<Output> {
+		self.outputs.borrow().clone()
+	}
+
+	// the text of each output, one per line as stdout would show it
+	pub fn text(&self) -> String {
+		self
+			.outputs
+			.borrow()
+			.iter()
+			.map(|o| format!("{}\n", o.text()))
+			.collect()
+	}
+
+	pub fn clear(&self) {
+		self.outputs.borrow_mut().clear();
+	}
+}
+
+impl OutputSink for BufferSink {
+	fn write(&self, output: &Output) {
+		self.outputs.borrow_mut().push(output.clone());
+	}
+}
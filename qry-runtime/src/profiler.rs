@@ -0,0 +1,179 @@
+use super::{Lock, Shared};
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// frame name queries show up under in folded stacks, the SQL itself is kept
+// in the per-query timings
+const QUERY_FRAME: &str = "[sql]";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileEntry {
+	pub calls: u64,
+	// recursive calls only count towards inclusive time once, for the
+	// outermost call
+	pub inclusive: Duration,
+	pub exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct ActiveCall {
+	frame: String,
+	query: Option<String>,
+	started: Instant,
+	children: Duration,
+}
+
+// records time spent in each callable and query while attached to a context.
+// It follows one thread of evaluation, so forks on other threads need a
+// profiler of their own
+#[derive(Debug, Default)]
+pub struct Profiler {
+	active: Lock<Vec<ActiveCall>>,
+	functions: Lock<HashMap<String, ProfileEntry>>,
+	queries: Lock<HashMap<String, ProfileEntry>>,
+	folded: Lock<HashMap<String, Duration>>,
+}
+
+pub struct ProfileScope<'a> {
+	profiler: &'a Profiler,
+}
+
+impl Drop for ProfileScope<'_> {
+	fn drop(&mut self) {
+		self.profiler.exit();
+	}
+}
+
+fn millis(duration: Duration) -> f64 {
+	duration.as_secs_f64() * 1000.0
+}
+
+fn sorted(entries: &HashMap<String, ProfileEntry>) -> Vec<(String, ProfileEntry)> {
+	let mut entries = entries
+		.iter()
+		.map(|(k, v)| (k.clone(), v.clone()))
+		.collect::<Vec<_>>();
+	entries.sort_by(|(a_name, a), (b_name, b)| {
+		b.inclusive
+			.cmp(&a.inclusive)
+			.then_with(|| a_name.cmp(b_name))
+	});
+	entries
+}
+
+impl Profiler {
+	pub fn new() -> Shared<Self> {
+		Shared::new(Self::default())
+	}
+
+	#[must_use]
+	pub fn enter(&self, name: &str) -> ProfileScope<'_> {
+		self.push(name.to_string(), None)
+	}
+
+	#[must_use]
+	pub fn enter_query(&self, sql: &str) -> ProfileScope<'_> {
+		self.push(QUERY_FRAME.to_string(), Some(sql.to_string()))
+	}
+
+	fn push(&self, frame: String, query: Option<String>) -> ProfileScope<'_> {
+		self.active.borrow_mut().push(ActiveCall {
+			frame,
+			query,
+			started: Instant::now(),
+			children: Duration::default(),
+		});
+		ProfileScope { profiler: self }
+	}
+
+	fn exit(&self) {
+		let mut active = self.active.borrow_mut();
+		let call = match active.pop() {
+			Some(call) => call,
+			None => return,
+		};
+
+		let inclusive = call.started.elapsed();
+		let exclusive = inclusive.checked_sub(call.children).unwrap_or_default();
+		if let Some(parent) = active.last_mut() {
+			parent.children += inclusive;
+		}
+
+		let recursive = active.iter().any(|c| c.frame == call.frame);
+		let path = active
+			.iter()
+			.map(|c| c.frame.as_str())
+			.chain(std::iter::once(call.frame.as_str()))
+			.collect::<Vec<_>>()
+			.join(";");
+		drop(active);
+
+		let (entries, key) = match call.query {
+			Some(sql) => (&self.queries, sql),
+			None => (&self.functions, call.frame),
+		};
+		let mut entries = entries.borrow_mut();
+		let entry = entries.entry(key).or_default();
+		entry.calls += 1;
+		entry.exclusive += exclusive;
+		if !recursive {
+			entry.inclusive += inclusive;
+		}
+
+		*self.folded.borrow_mut().entry(path).or_default() += exclusive;
+	}
+
+	// slowest first
+	pub fn functions(&self) -> Vec<(String, ProfileEntry)> {
+		sorted(&self.functions.borrow())
+	}
+
+	pub fn queries(&self) -> Vec<(String, ProfileEntry)> {
+		sorted(&self.queries.borrow())
+	}
+
+	pub fn reset(&self) {
+		self.functions.borrow_mut().clear();
+		self.queries.borrow_mut().clear();
+		self.folded.borrow_mut().clear();
+	}
+
+	// one `a;b;c <microseconds>` line per distinct stack, the input format of
+	// flamegraph.pl and inferno
+	pub fn write_folded(&self, out: &mut impl Write) -> std::io::Result<()> {
+		let folded = self.folded.borrow();
+		let mut stacks = folded.iter().collect::<Vec<_>>();
+		stacks.sort();
+		for (stack, duration) in stacks {
+			writeln!(out, "{} {}", stack, duration.as_micros())?;
+		}
+		Ok(())
+	}
+
+	pub fn report(&self) -> String {
+		let mut report = format!(
+			"{:<40} {:>8} {:>14} {:>14}\n",
+			"function", "calls", "inclusive ms", "exclusive ms"
+		);
+		let rows = self
+			.functions()
+			.into_iter()
+			.map(|row| ("", row))
+			.chain(self.queries().into_iter().map(|row| ("sql: ", row)));
+		for (prefix, (name, entry)) in rows {
+			report += &format!(
+				"{:<40} {:>8} {:>14.3} {:>14.3}\n",
+				format!(
+					"{}{}",
+					prefix,
+					name.split_whitespace().collect::<Vec<_>>().join(" ")
+				),
+				entry.calls,
+				millis(entry.inclusive),
+				millis(entry.exclusive)
+			);
+		}
+		report
+	}
+}
@@ -0,0 +1,38 @@
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+	Filesystem,
+	DatabaseConnect,
+	Stdout,
+}
+
+impl Capability {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Filesystem => "filesystem",
+			Self::DatabaseConnect => "database connect",
+			Self::Stdout => "stdout",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+	pub denied: HashSet<Capability>,
+	// None allows connecting to any target
+	pub allowed_connections: Option<Vec<String>>,
+}
+
+impl Sandbox {
+	pub fn allows(&self, capability: Capability) -> bool {
+		!self.denied.contains(&capability)
+	}
+
+	pub fn allows_connection(&self, target: &str) -> bool {
+		match &self.allowed_connections {
+			Some(targets) => targets.iter().any(|t| t == target),
+			None => true,
+		}
+	}
+}
@@ -1,3 +1,4 @@
+mod reflection;
 mod types;
 
 pub use types::*;
@@ -0,0 +1,185 @@
+use crate::{
+	help_text, location_for_stacktrace, Callable, Environment, EvalContext, EvalResult, Output,
+	Shared, Signature, Type, Value,
+};
+use qry_lang::SourceLocation;
+
+fn callable_type() -> Type {
+	Type::union(vec![Type::Function, Type::Builtin, Type::Method])
+}
+
+fn as_callable<'a>(ctx: &EvalContext, value: &'a Value) -> EvalResult<&'a dyn Callable> {
+	match value {
+		Value::Function(function) => Ok(&**function),
+		Value::Builtin(builtin) => Ok(&**builtin),
+		Value::Method(method) => Ok(&**method),
+		other => Err(ctx.exception(
+			&SourceLocation::Unknown,
+			format!("expected a callable, got {}", other.runtime_type()),
+		)),
+	}
+}
+
+// a signature as a list of its params, each a list of name and type, followed
+// by its return type
+fn signature_value(signature: &Signature) -> Value {
+	let params = signature
+		.params
+		.iter()
+		.map(|p| {
+			Value::List(
+				vec![
+					Value::String(p.name.as_str().into()),
+					Value::Type(p.param_type.clone()),
+				]
+				.into(),
+			)
+		})
+		.collect();
+	Value::List(
+		vec![
+			Value::List(params),
+			Value::Type(signature.return_type.clone()),
+		]
+		.into(),
+	)
+}
+
+// callables go by the name they were defined with, which for builtins
+// includes their library
+fn help_name(value: &Value) -> String {
+	match value {
+		Value::Function(function) => function.name().into(),
+		Value::Builtin(builtin) => builtin.name().into(),
+		Value::Method(method) => method.name().into(),
+		Value::Library(env) => env.name().into(),
+		Value::Type(t) => t.to_string(),
+		other => other.runtime_type().to_string(),
+	}
+}
+
+fn same_binding(a: &Value, b: &Value) -> bool {
+	match (a, b) {
+		(Value::Builtin(a), Value::Builtin(b)) => Shared::ptr_eq(a, b),
+		(Value::Method(a), Value::Method(b)) => Shared::ptr_eq(a, b),
+		(Value::Library(a), Value::Library(b)) => Shared::ptr_eq(a, b),
+		(Value::Type(a), Value::Type(b)) => a == b,
+		_ => false,
+	}
+}
+
+// names bound in env, leaving out those bound to the same thing in hidden
+fn names(env: &Environment, hidden: &[Shared<Environment>]) -> Value {
+	let mut names = env
+		.bindings()
+		.into_iter()
+		.filter(|(name, value)| {
+			!hidden
+				.iter()
+				.filter_map(|h| h.get(name))
+				.any(|h| same_binding(value, &h))
+		})
+		.map(|(name, _)| name)
+		.collect::<Vec<_>>();
+	names.sort();
+	Value::List(
+		names
+			.into_iter()
+			.map(|n| Value::String(n.into_boxed_str()))
+			.collect(),
+	)
+}
+
+pub fn init(env: &Environment) {
+	// without a library, what's bound in the current scope other than the
+	// libraries and what core puts in every scope
+	env.define_builtin(
+		"ls",
+		Signature::returning(&Type::List)
+			.with_trailing(&Type::Library)
+			.with_doc("The names bound in the current scope other than core's, or those in a library."),
+		|ctx, args, _| match args {
+			[] => {
+				let mut hidden = vec![ctx.library_env.clone()];
+				if let Some(Value::Library(core)) = ctx.library_env.get("core") {
+					hidden.push(core);
+				}
+				Ok(names(&ctx.env, &hidden))
+			}
+			[Value::Library(library)] => Ok(names(library, &[])),
+			_ => Err(ctx.exception(
+				&SourceLocation::Unknown,
+				format!("ls takes at most one library, got {}", args.len()),
+			)),
+		},
+	);
+
+	env.define_builtin(
+		"signature",
+		Signature::returning(&Type::List)
+			.param("callable", &callable_type())
+			.with_doc("A callable's params, each a list of name and type, then its return type."),
+		|ctx, args, _| Ok(signature_value(as_callable(ctx, &args[0])?.signature())),
+	);
+
+	env.define_builtin(
+		"impls",
+		Signature::returning(&Type::List)
+			.param("method", &Type::Method)
+			.with_doc("The signatures of a method's impls."),
+		|ctx, args, _| {
+			let method = args[0].as_method(ctx)?;
+			let mut signatures = method.supported_signatures();
+			signatures.sort_by_key(|s| s.to_string());
+			Ok(Value::List(
+				signatures.iter().map(signature_value).collect(),
+			))
+		},
+	);
+
+	// null for callables the runtime made itself, such as methods
+	env.define_builtin(
+		"location",
+		Signature::returning(&Type::union(vec![Type::String, Type::Null]))
+			.param("callable", &callable_type())
+			.with_doc("Where a callable was defined, or null for ones the runtime made itself."),
+		|ctx, args, _| match as_callable(ctx, &args[0])?.source_location() {
+			SourceLocation::Unknown => Ok(Value::Null(())),
+			location => Ok(Value::String(location_for_stacktrace(location).into())),
+		},
+	);
+
+	env.define_builtin(
+		"help",
+		Signature::returning(&Type::Null)
+			.with_trailing(&Type::Any)
+			.with_doc("How to call a value and what it does, what's in a library, or the libraries."),
+		|ctx, args, _| {
+			let text = match args {
+				[] => {
+					let mut libraries = ctx
+						.library_env
+						.bindings()
+						.into_iter()
+						.filter(|(_, value)| matches!(value, Value::Library(_)))
+						.map(|(name, _)| name)
+						.collect::<Vec<_>>();
+					libraries.sort();
+					format!(
+						"libraries: {}\nhelp(x) describes x, ls(library) lists what's in a library",
+						libraries.join(", ")
+					)
+				}
+				[value] => help_text(&help_name(value), value),
+				_ => {
+					return Err(ctx.exception(
+						&SourceLocation::Unknown,
+						format!("help takes at most one value, got {}", args.len()),
+					))
+				}
+			};
+			ctx.write_output(Output::Text(text))?;
+			Ok(Value::Null(()))
+		},
+	);
+}
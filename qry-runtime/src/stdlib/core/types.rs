@@ -1,12 +1,22 @@
-use crate::{Callable, Environment, RuntimeMethods, Signature, Type, Value};
-use std::rc::Rc;
+#[cfg(not(feature = "sync"))]
+use crate::collect_cycles;
+use crate::{
+	distinct, gc_stats, identical, load_workspace, save_workspace, BigInt, Callable, Decimal,
+	Environment, EvalContext, EvalResult, Method, RuntimeMethods, Shared, Signature, Type, Value,
+};
+use qry_lang::SourceLocation;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::str::FromStr;
 
-pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
+pub fn env(methods: &RuntimeMethods) -> Shared<Environment> {
 	let env = Environment::new("core");
 	for t in &[
 		Type::Null,
 		Type::Int,
 		Type::Float,
+		Type::BigInt,
+		Type::Decimal,
 		Type::String,
 		Type::Bool,
 		Type::List,
@@ -16,33 +26,204 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 	}
 
 	env.update("to_string", Value::Method(methods.to_string.clone()));
+	env.update("display", Value::Method(methods.display.clone()));
+	env.update("hash", Value::Method(methods.hash.clone()));
+	init_bigint(&env.define_method("bigint", &["val"], Some(Type::BigInt), None));
+	init_decimal(&env.define_method("decimal", &["val"], Some(Type::Decimal), None));
+	super::reflection::init(&env);
 
 	env.define_builtin(
 		"typeof",
-		Signature::returning(&Type::Type).param("obj", &Type::Any),
+		Signature::returning(&Type::Type)
+			.param("obj", &Type::Any)
+			.with_doc("The type of a value."),
 		|_, args, _| Ok(Value::Type(args[0].runtime_type())),
 	);
 
+	env.define_builtin(
+		"identical",
+		Signature::returning(&Type::Bool)
+			.param("a", &Type::Any)
+			.param("b", &Type::Any)
+			.with_doc("Whether a and b are the same value, comparing references rather than contents."),
+		|_, args, _| Ok(Value::Bool(identical(&args[0], &args[1]))),
+	);
+
+	env.define_builtin(
+		"distinct",
+		Signature::returning(&Type::List)
+			.param("list", &Type::List)
+			.with_doc("The items of a list that aren't equal to an earlier one, in order."),
+		|ctx, args, _| Ok(Value::List(distinct(ctx, args[0].as_list(ctx)?)?)),
+	);
+
 	env.define_builtin(
 		"parse",
-		Signature::returning(&Type::Syntax).param("code", &Type::SyntaxPlaceholder),
+		Signature::returning(&Type::Syntax)
+			.param("code", &Type::SyntaxPlaceholder)
+			.with_doc("The syntax of the code it's given, unevaluated."),
 		|_, args, _| Ok(args[0].clone()),
 	);
 
 	env.define_builtin(
 		"list",
-		Signature::returning(&Type::List).with_trailing(&Type::Any),
-		|_, args, _| Ok(Value::List(args.to_vec())),
+		Signature::returning(&Type::List)
+			.with_trailing(&Type::Any)
+			.with_doc("A list of the values given."),
+		|_, args, _| Ok(Value::List(args.iter().cloned().collect())),
+	);
+
+	// these share the list they're given rather than copying it
+	env.define_builtin(
+		"append",
+		Signature::returning(&Type::List)
+			.param("list", &Type::List)
+			.param("value", &Type::Any)
+			.with_doc("A copy of the list with value added to the end, sharing its items."),
+		|ctx, args, _| Ok(Value::List(args[0].as_list(ctx)?.append(args[1].clone()))),
+	);
+
+	env.define_builtin(
+		"concat",
+		Signature::returning(&Type::List)
+			.param("a", &Type::List)
+			.param("b", &Type::List)
+			.with_doc("A list of the items in a followed by those in b."),
+		|ctx, args, _| {
+			Ok(Value::List(
+				args[0].as_list(ctx)?.concat(args[1].as_list(ctx)?),
+			))
+		},
+	);
+
+	env.define_builtin(
+		"slice",
+		Signature::returning(&Type::List)
+			.param("list", &Type::List)
+			.param("from", &Type::Int)
+			.param("to", &Type::Int)
+			.with_doc("The items of a list from index from up to to, clamped to its length."),
+		|ctx, args, _| {
+			let list = args[0].as_list(ctx)?;
+			let (from, to) = (args[1].as_int(ctx)?, args[2].as_int(ctx)?);
+			match (usize::try_from(from), usize::try_from(to)) {
+				(Ok(from), Ok(to)) if from <= to => Ok(Value::List(list.slice(from, to))),
+				_ => Err(ctx.exception(
+					&SourceLocation::Unknown,
+					format!("invalid slice: {}..{}", from, to),
+				)),
+			}
+		},
 	);
 
 	env.define_builtin(
 		"print",
-		Signature::returning(&Type::Null).param("obj", &Type::Any),
+		Signature::returning(&Type::Null)
+			.param("obj", &Type::Any)
+			.with_doc("Writes a value to the output."),
+		|ctx, args, _| ctx.methods.display.call(ctx, &[args[0].clone()], &[]),
+	);
+
+	env.define_builtin(
+		"save_workspace",
+		Signature::returning(&Type::Int)
+			.param("path", &Type::String)
+			.with_doc("Saves what can be saved of the current scope, returning how much was."),
 		|ctx, args, _| {
-			let str_val = ctx.methods.to_string.call(ctx, &[args[0].clone()], &[])?;
-			println!("{}", str_val.as_string());
-			Ok(Value::Null(()))
+			let path = Path::new(args[0].as_string(ctx)?);
+			Ok(Value::Int(save_workspace(ctx, &ctx.env, path)? as i64))
+		},
+	);
+
+	env.define_builtin(
+		"load_workspace",
+		Signature::returning(&Type::List)
+			.param("path", &Type::String)
+			.with_doc("Restores the bindings saved in a workspace file, returning their names."),
+		|ctx, args, _| {
+			let names = load_workspace(ctx, Path::new(args[0].as_string(ctx)?))?;
+			Ok(Value::List(
+				names
+					.into_iter()
+					.map(|n| Value::String(n.into_boxed_str()))
+					.collect(),
+			))
+		},
+	);
+
+	// returns how many environments, closures and methods were freed. With the
+	// sync feature a collection covers every thread, so it's left to the host
+	// to run when nothing else is evaluating
+	#[cfg(not(feature = "sync"))]
+	env.define_builtin(
+		"gc",
+		Signature::returning(&Type::Int).with_doc(
+			"Frees closures and environments only referring to each other, returning how many.",
+		),
+		|_, _, _| Ok(Value::Int(collect_cycles() as i64)),
+	);
+
+	env.define_builtin(
+		"gc_stats",
+		Signature::returning(&Type::List)
+			.with_doc("Counts of live environments, collections run and what they freed."),
+		|_, _, _| {
+			let stats = gc_stats();
+			let stat = |name: &str, n: usize| {
+				Value::List(vec![Value::String(name.into()), Value::Int(n as i64)].into())
+			};
+			Ok(Value::List(
+				vec![
+					stat("environments", stats.environments),
+					stat("collections", stats.collections),
+					stat("collected", stats.collected),
+				]
+				.into(),
+			))
 		},
 	);
 	env
 }
+
+fn parse_numeric<T: FromStr<Err = String>>(ctx: &EvalContext, val: &Value) -> EvalResult<T> {
+	val
+		.as_string(ctx)?
+		.parse()
+		.map_err(|message: String| ctx.exception(&SourceLocation::Unknown, message))
+}
+
+fn init_bigint(bigint: &Method) {
+	bigint.set_doc("Converts a number or its digits in a string to an arbitrary size integer.");
+	bigint.register_builtin(
+		Signature::returning(&Type::BigInt).param("val", &Type::String),
+		|ctx, args, _| Ok(Value::BigInt(parse_numeric(ctx, &args[0])?)),
+	);
+	bigint.register_builtin(
+		Signature::returning(&Type::BigInt).param("val", &Type::Int),
+		|ctx, args, _| Ok(Value::BigInt(BigInt::from(args[0].as_int(ctx)?))),
+	);
+}
+
+fn init_decimal(decimal: &Method) {
+	decimal.set_doc("Converts a number or its digits in a string to an exact decimal.");
+	decimal.register_builtin(
+		Signature::returning(&Type::Decimal).param("val", &Type::String),
+		|ctx, args, _| Ok(Value::Decimal(parse_numeric(ctx, &args[0])?)),
+	);
+	decimal.register_builtin(
+		Signature::returning(&Type::Decimal).param("val", &Type::Int),
+		|ctx, args, _| Ok(Value::Decimal(Decimal::from(args[0].as_int(ctx)?))),
+	);
+	decimal.register_builtin(
+		Signature::returning(&Type::Decimal).param("val", &Type::Float),
+		|ctx, args, _| {
+			let f = args[0].as_float(ctx)?;
+			Decimal::from_f64(f).map(Value::Decimal).ok_or_else(|| {
+				ctx.exception(
+					&SourceLocation::Unknown,
+					format!("{:?} can't be represented as a decimal", f),
+				)
+			})
+		},
+	);
+}
@@ -1,8 +1,12 @@
+#[cfg(not(target_arch = "wasm32"))]
+use super::sqlite::open_sqlite;
 use super::ColumnMap;
-use crate::{EvalContext, EvalResult, NativeType};
+use crate::{EvalContext, EvalEvent, EvalResult, MaybeSync, NativeType, Value};
 use arrow::record_batch::RecordBatch;
+use qry_lang::SourceLocation;
+use std::time::Instant;
 
-pub trait ConnectionImpl {
+pub trait ConnectionImpl: MaybeSync {
 	fn get_relation_metadata(&self, ctx: &EvalContext, table: &str) -> EvalResult<ColumnMap>;
 	fn execute(&self, ctx: &EvalContext, sql: &str) -> EvalResult<i64>;
 	fn collect(
@@ -15,9 +19,57 @@ pub trait ConnectionImpl {
 
 pub struct Connection {
 	pub driver: String,
+	// what it was opened with, e.g. the sqlite file
+	pub target: String,
 	pub conn_impl: Box<dyn ConnectionImpl>,
 }
 
+impl Connection {
+	pub fn open(ctx: &EvalContext, driver: &str, target: &str) -> EvalResult<Value> {
+		match driver {
+			#[cfg(not(target_arch = "wasm32"))]
+			"sqlite" => open_sqlite(ctx, target),
+			_ => Err(ctx.exception(
+				&SourceLocation::Unknown,
+				format!("unsupported driver: {}", driver),
+			)),
+		}
+	}
+}
+
+// queries go through here rather than conn_impl so they show up in the
+// profiler and to observers
+impl Connection {
+	pub fn execute(&self, ctx: &EvalContext, sql: &str) -> EvalResult<i64> {
+		let _profile = ctx.profile_query(sql);
+		let started = Instant::now();
+		let rows = self.conn_impl.execute(ctx, sql)?;
+		ctx.emit(EvalEvent::SqlExecuted {
+			sql,
+			rows: rows as usize,
+			elapsed: started.elapsed(),
+		});
+		Ok(rows)
+	}
+
+	pub fn collect(
+		&self,
+		ctx: &EvalContext,
+		sql: &str,
+		result_metadata: &ColumnMap,
+	) -> EvalResult<RecordBatch> {
+		let _profile = ctx.profile_query(sql);
+		let started = Instant::now();
+		let batch = self.conn_impl.collect(ctx, sql, result_metadata)?;
+		ctx.emit(EvalEvent::SqlExecuted {
+			sql,
+			rows: batch.num_rows(),
+			elapsed: started.elapsed(),
+		});
+		Ok(batch)
+	}
+}
+
 impl NativeType for Connection {
 	fn name() -> &'static str {
 		"Connection"
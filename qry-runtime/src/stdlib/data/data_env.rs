@@ -4,32 +4,42 @@ use super::{
 	df_to_string, AggregateStep, Connection, DataFrame, FilterStep, GroupStep, IntVector, MutateStep,
 	QueryPipeline, SelectStep, Vector,
 };
-use crate::{Environment, RuntimeMethods, Signature, Type, Value};
-use qry_lang::SourceLocation;
-use std::rc::Rc;
+use crate::{
+	Environment, EvalContext, EvalResult, Output, RuntimeMethods, Shared, Signature, Type, Value,
+};
+use qry_lang::{BinaryOperator, SourceLocation};
+
+// by contents rather than identity, like lists
+fn dataframes_equal(ctx: &EvalContext, args: &[Value]) -> EvalResult<bool> {
+	let a = args[0].as_native::<DataFrame>(ctx)?;
+	let b = args[1].as_native::<DataFrame>(ctx)?;
+	Ok(a.same_contents(&b))
+}
 
-pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
+pub fn env(methods: &RuntimeMethods) -> Shared<Environment> {
 	let env = Environment::new("data");
-	let connection_type = &env.define_native_type::<Connection>();
-	let pipeline_type = &env.define_native_type::<QueryPipeline>();
-	let dataframe_type = &env.define_native_type::<DataFrame>();
-	env.define_native_generic_type::<Vector>();
-	let intvector_type = &env.define_native_type::<IntVector>();
+	let connection_type = &env.define_native_type::<Connection>(methods);
+	let pipeline_type = &env.define_native_type::<QueryPipeline>(methods);
+	let dataframe_type = &env.define_native_type::<DataFrame>(methods);
+	env.define_native_generic_type::<Vector>(methods);
+	let intvector_type = &env.define_native_type::<IntVector>(methods);
 
 	let sum_method = env.define_method("sum", &["vec"], None, None);
+	sum_method.set_doc("The sum of a vector's values.");
 	sum_method.register_builtin(
 		Signature::returning(&Type::Int).param("vec", intvector_type),
-		|_, args, _| {
-			let vec = args[0].as_native::<IntVector>();
+		|ctx, args, _| {
+			let vec = args[0].as_native::<IntVector>(ctx)?;
 			Ok(Value::Int(vec.sum()))
 		},
 	);
 
 	let min_method = env.define_method("min", &["vec"], None, None);
+	min_method.set_doc("The smallest of a vector's values.");
 	min_method.register_builtin(
 		Signature::returning(&Type::Int).param("vec", intvector_type),
 		|ctx, args, _| {
-			let vec = args[0].as_native::<IntVector>();
+			let vec = args[0].as_native::<IntVector>(ctx)?;
 
 			match vec.min() {
 				Some(val) => Ok(Value::Int(val)),
@@ -39,10 +49,11 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 	);
 
 	let max_method = env.define_method("max", &["vec"], None, None);
+	max_method.set_doc("The largest of a vector's values.");
 	max_method.register_builtin(
 		Signature::returning(&Type::Int).param("vec", intvector_type),
 		|ctx, args, _| {
-			let vec = args[0].as_native::<IntVector>();
+			let vec = args[0].as_native::<IntVector>(ctx)?;
 			match vec.max() {
 				Some(val) => Ok(Value::Int(val)),
 				None => Err(ctx.exception(&SourceLocation::Unknown, "empty vector")),
@@ -52,25 +63,28 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 
 	env.define_builtin(
 		"intvec",
-		Signature::returning(intvector_type).with_trailing(&Type::Int),
-		|_, args, _| {
-			let vec = IntVector::from_values(args);
+		Signature::returning(intvector_type)
+			.with_trailing(&Type::Int)
+			.with_doc("An integer vector of the values given."),
+		|ctx, args, _| {
+			let vec = IntVector::from_values(ctx, args)?;
 			Ok(Value::new_native(vec))
 		},
 	);
 
 	#[cfg(not(target_arch = "wasm32"))]
-	sqlite_init_env(&env);
+	sqlite_init_env(&env, connection_type);
 
 	env.define_builtin(
 		"execute",
 		Signature::returning(&Type::Int)
 			.param("connection", connection_type)
-			.param("query", &Type::String),
+			.param("query", &Type::String)
+			.with_doc("Runs a statement on a connection, returning how many rows it changed."),
 		|ctx, args, _| {
-			let conn = args[0].as_native::<Connection>();
-			let query = args[1].as_string();
-			Ok(Value::Int(conn.conn_impl.execute(ctx, query)?))
+			let conn = args[0].as_native::<Connection>(ctx)?;
+			let query = args[1].as_string(ctx)?;
+			Ok(Value::Int(conn.execute(ctx, query)?))
 		},
 	);
 
@@ -78,30 +92,33 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"table",
 		Signature::returning(pipeline_type)
 			.param("connection", connection_type)
-			.param("table", &Type::String),
-		|_, args, _| {
-			let conn = args[0].as_native::<Connection>();
-			let table = args[1].as_string();
+			.param("table", &Type::String)
+			.with_doc("A pipeline reading a table, which queries it once it's collected."),
+		|ctx, args, _| {
+			let conn = args[0].as_native::<Connection>(ctx)?;
+			let table = args[1].as_string(ctx)?;
 			Ok(Value::new_native(QueryPipeline::new(conn, table)))
 		},
 	);
 
 	env.define_builtin(
 		"collect",
-		Signature::returning(dataframe_type).param("pipeline", pipeline_type),
+		Signature::returning(dataframe_type)
+			.param("pipeline", pipeline_type)
+			.with_doc("Runs a pipeline's query and returns the result as a DataFrame."),
 		|ctx, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
-			let batch = pipeline.collect(ctx)?;
-			let df = DataFrame::new(vec![batch]);
-			Ok(Value::new_native(df))
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
+			Ok(Value::new_native(pipeline.collect(ctx)?))
 		},
 	);
 
 	env.define_builtin(
 		"render",
-		Signature::returning(&Type::String).param("pipeline", pipeline_type),
+		Signature::returning(&Type::String)
+			.param("pipeline", pipeline_type)
+			.with_doc("The SQL a pipeline runs when it's collected."),
 		|ctx, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			let state = pipeline.generate(ctx)?;
 			Ok(Value::String(state.query.into()))
 		},
@@ -111,15 +128,16 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"filter",
 		Signature::returning(pipeline_type)
 			.param("pipeline", pipeline_type)
-			.param("expr", &Type::SyntaxPlaceholder),
+			.param("expr", &Type::SyntaxPlaceholder)
+			.with_doc("Keeps the rows expr is true for, as in `filter(age > 30)`."),
 		|ctx, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
-			let predicate = args[1].as_syntax();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
+			let predicate = args[1].as_syntax(ctx)?;
 			let step = FilterStep {
 				ctx: ctx.clone(),
 				predicate: predicate.clone(),
 			};
-			Ok(Value::new_native(pipeline.add(Rc::new(step))))
+			Ok(Value::new_native(pipeline.add(Shared::new(step))))
 		},
 	);
 
@@ -127,19 +145,20 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"select",
 		Signature::returning(pipeline_type)
 			.param("pipeline", pipeline_type)
-			.with_trailing(&Type::SyntaxPlaceholder),
+			.with_trailing(&Type::SyntaxPlaceholder)
+			.with_doc("Keeps only the columns given, as in `select(name, age)`."),
 		|ctx, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			let cols = args[1..]
 				.iter()
-				.map(|a| a.as_syntax().clone())
-				.collect::<Vec<_>>();
+				.map(|a| Ok(a.as_syntax(ctx)?.clone()))
+				.collect::<EvalResult<Vec<_>>>()?;
 
 			let step = SelectStep {
 				ctx: ctx.clone(),
 				cols,
 			};
-			Ok(Value::new_native(pipeline.add(Rc::new(step))))
+			Ok(Value::new_native(pipeline.add(Shared::new(step))))
 		},
 	);
 
@@ -147,19 +166,20 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"mutate",
 		Signature::returning(pipeline_type)
 			.param("pipeline", pipeline_type)
-			.with_named_trailing(&Type::SyntaxPlaceholder),
+			.with_named_trailing(&Type::SyntaxPlaceholder)
+			.with_doc("Adds a column for each named argument, as in `mutate(months = age * 12)`."),
 		|ctx, args, named_args| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			let new_cols = named_args
 				.iter()
-				.map(|(n, a)| (n.to_string(), a.as_syntax().clone()))
-				.collect::<Vec<_>>();
+				.map(|(n, a)| Ok((n.to_string(), a.as_syntax(ctx)?.clone())))
+				.collect::<EvalResult<Vec<_>>>()?;
 
 			let step = MutateStep {
 				ctx: ctx.clone(),
 				new_cols,
 			};
-			Ok(Value::new_native(pipeline.add(Rc::new(step))))
+			Ok(Value::new_native(pipeline.add(Shared::new(step))))
 		},
 	);
 
@@ -167,19 +187,20 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"group_by",
 		Signature::returning(pipeline_type)
 			.param("pipeline", pipeline_type)
-			.with_trailing(&Type::SyntaxPlaceholder),
+			.with_trailing(&Type::SyntaxPlaceholder)
+			.with_doc("Groups rows by the columns given, for the aggregate that follows."),
 		|ctx, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			let grouping = args[1..]
 				.iter()
-				.map(|a| a.as_syntax().clone())
-				.collect::<Vec<_>>();
+				.map(|a| Ok(a.as_syntax(ctx)?.clone()))
+				.collect::<EvalResult<Vec<_>>>()?;
 
 			let step = GroupStep {
 				ctx: ctx.clone(),
 				grouping,
 			};
-			Ok(Value::new_native(pipeline.add(Rc::new(step))))
+			Ok(Value::new_native(pipeline.add(Shared::new(step))))
 		},
 	);
 
@@ -187,36 +208,41 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		"aggregate",
 		Signature::returning(pipeline_type)
 			.param("pipeline", pipeline_type)
-			.with_named_trailing(&Type::SyntaxPlaceholder),
+			.with_named_trailing(&Type::SyntaxPlaceholder)
+			.with_doc("A row per group, with a column per named argument as in `total = sum(age)`."),
 		|ctx, args, named_args| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			let aggregations = named_args
 				.iter()
-				.map(|(n, a)| (n.to_string(), a.as_syntax().clone()))
-				.collect::<Vec<_>>();
+				.map(|(n, a)| Ok((n.to_string(), a.as_syntax(ctx)?.clone())))
+				.collect::<EvalResult<Vec<_>>>()?;
 
 			let step = AggregateStep {
 				ctx: ctx.clone(),
 				aggregations,
 			};
-			Ok(Value::new_native(pipeline.add(Rc::new(step))))
+			Ok(Value::new_native(pipeline.add(Shared::new(step))))
 		},
 	);
 
 	env.define_builtin(
 		"num_rows",
-		Signature::returning(&Type::Int).param("df", dataframe_type),
-		|_, args, _| {
-			let df = args[0].as_native::<DataFrame>();
+		Signature::returning(&Type::Int)
+			.param("df", dataframe_type)
+			.with_doc("The number of rows in a DataFrame."),
+		|ctx, args, _| {
+			let df = args[0].as_native::<DataFrame>(ctx)?;
 			Ok(Value::Int(df.num_rows()))
 		},
 	);
 
 	env.define_builtin(
 		"num_cols",
-		Signature::returning(&Type::Int).param("df", dataframe_type),
-		|_, args, _| {
-			let df = args[0].as_native::<DataFrame>();
+		Signature::returning(&Type::Int)
+			.param("df", dataframe_type)
+			.with_doc("The number of columns in a DataFrame."),
+		|ctx, args, _| {
+			let df = args[0].as_native::<DataFrame>(ctx)?;
 			Ok(Value::Int(df.num_cols()))
 		},
 	);
@@ -227,53 +253,87 @@ pub fn env(methods: &RuntimeMethods) -> Rc<Environment> {
 		// when the signature only knows it's a Vector<?>
 		Signature::returning(&Type::Any)
 			.param("df", dataframe_type)
-			.param("name", &Type::String),
+			.param("name", &Type::String)
+			.with_doc("A DataFrame's column with that name, as a vector."),
 		|ctx, args, _| {
-			let df = args[0].as_native::<DataFrame>();
-			let col_name = args[1].as_string();
-			match df.col(col_name) {
-				Some(col) => Ok(col),
-				None => Err(ctx.exception(
-					&SourceLocation::Unknown,
-					format!("column not found: {}", col_name),
-				)),
-			}
+			let df = args[0].as_native::<DataFrame>(ctx)?;
+			let col_name = args[1].as_string(ctx)?;
+			df.col(ctx, col_name)
 		},
 	);
 
 	env.define_builtin(
 		"dimensions",
-		Signature::returning(&Type::List).param("df", dataframe_type),
-		|_, args, _| {
-			let df = args[0].as_native::<DataFrame>();
-			Ok(Value::List(vec![
-				Value::Int(df.num_rows()),
-				Value::Int(df.num_cols()),
-			]))
+		Signature::returning(&Type::List)
+			.param("df", dataframe_type)
+			.with_doc("The number of rows and columns in a DataFrame."),
+		|ctx, args, _| {
+			let df = args[0].as_native::<DataFrame>(ctx)?;
+			Ok(Value::List(
+				vec![Value::Int(df.num_rows()), Value::Int(df.num_cols())].into(),
+			))
 		},
 	);
 
 	methods.to_string.register_builtin(
 		Signature::returning(&Type::String).param("obj", connection_type),
-		|_, args, _| {
+		|ctx, args, _| {
 			Ok(Value::String(
-				format!("Connection: {}", args[0].as_native::<Connection>().driver).into_boxed_str(),
+				format!(
+					"Connection: {}",
+					args[0].as_native::<Connection>(ctx)?.driver
+				)
+				.into_boxed_str(),
 			))
 		},
 	);
 
 	methods.to_string.register_builtin(
 		Signature::returning(&Type::String).param("obj", dataframe_type),
-		|_, args, _| {
-			let df = args[0].as_native::<DataFrame>();
+		|ctx, args, _| {
+			let df = args[0].as_native::<DataFrame>(ctx)?;
 			Ok(Value::String(df_to_string(&df).into_boxed_str()))
 		},
 	);
 
+	methods.display.register_builtin(
+		Signature::returning(&Type::Null).param("obj", dataframe_type),
+		|ctx, args, _| {
+			let df = args[0].as_native::<DataFrame>(ctx)?;
+			ctx.write_output(Output::Table {
+				columns: df.column_names(),
+				rows: df.formatted_rows(),
+				text: df_to_string(&df),
+			})?;
+			Ok(Value::Null(()))
+		},
+	);
+
+	methods.binops[&BinaryOperator::Equal].register_builtin(
+		Signature::returning(&Type::Bool)
+			.param("a", dataframe_type)
+			.param("b", dataframe_type),
+		|ctx, args, _| Ok(Value::Bool(dataframes_equal(ctx, args)?)),
+	);
+	methods.binops[&BinaryOperator::NotEqual].register_builtin(
+		Signature::returning(&Type::Bool)
+			.param("a", dataframe_type)
+			.param("b", dataframe_type),
+		|ctx, args, _| Ok(Value::Bool(!dataframes_equal(ctx, args)?)),
+	);
+	methods.hash.register_builtin(
+		Signature::returning(&Type::Int).param("val", dataframe_type),
+		|ctx, args, _| {
+			Ok(Value::Int(
+				args[0].as_native::<DataFrame>(ctx)?.contents_hash(),
+			))
+		},
+	);
+
 	methods.to_string.register_builtin(
 		Signature::returning(&Type::String).param("obj", pipeline_type),
-		|_, args, _| {
-			let pipeline = args[0].as_native::<QueryPipeline>();
+		|ctx, args, _| {
+			let pipeline = args[0].as_native::<QueryPipeline>(ctx)?;
 			Ok(Value::String(
 				format!("QueryPipeline ({} steps)", pipeline.steps.len()).into_boxed_str(),
 			))
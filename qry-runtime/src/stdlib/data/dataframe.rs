@@ -1,10 +1,16 @@
 use super::IntVector;
-use crate::{NativeType, Value};
-use arrow::array::{BooleanArray, Float64Array, Int64Array, StringArray};
+use crate::{EvalContext, EvalResult, Exception, List, NativeType, Type, Value};
+use arrow::array::{Array, BooleanArray, Float64Array, Int64Array, StringArray};
 use arrow::datatypes::DataType;
-use arrow::record_batch::RecordBatch;
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
 #[cfg(not(target_arch = "wasm32"))]
 use prettytable::{Cell, Row, Table};
+use qry_lang::SourceLocation;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 macro_rules! array_cast {
 	($arrtype: ident, $arr: expr) => {
@@ -21,50 +27,49 @@ macro_rules! array_val {
 	};
 }
 
+// no prettytable on wasm, so just tab separated
 #[cfg(target_arch = "wasm32")]
-pub fn df_to_string(_: &DataFrame) -> String {
-	unimplemented!()
+pub fn df_to_string(df: &DataFrame) -> String {
+	let mut lines = vec![df.column_names().join("\t")];
+	for row in df.formatted_rows() {
+		let cells = row
+			.into_iter()
+			.map(|cell| cell.unwrap_or_else(|| "<NULL>".to_string()))
+			.collect::<Vec<_>>();
+		lines.push(cells.join("\t"));
+	}
+	lines.join("\n")
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn df_to_string(df: &DataFrame) -> String {
-	let batches = &df.batches;
 	let mut table = Table::new();
 
-	let mut header = Vec::new();
-	for field in batches[0].schema().fields() {
-		header.push(Cell::new(field.name()));
-	}
-
+	let header = df
+		.column_names()
+		.iter()
+		.map(|name| Cell::new(name))
+		.collect();
 	table.set_titles(Row::new(header));
 
-	for batch in batches {
-		for row_idx in 0..batch.num_rows() {
-			let mut row = Vec::new();
-			for col_idx in 0..batch.num_columns() {
-				let col = batch.column(col_idx);
-				let val_str = if col.is_null(row_idx) {
-					"<NULL>".to_string()
-				} else {
-					match col.data_type() {
-						DataType::Int64 => array_val!(Int64Array, col, row_idx).to_string(),
-						DataType::Float64 => array_val!(Float64Array, col, row_idx).to_string(),
-						DataType::Boolean => array_val!(BooleanArray, col, row_idx).to_string(),
-						DataType::Utf8 => array_val!(StringArray, col, row_idx).to_string(),
-						_ => unreachable!(),
-					}
-				};
-				row.push(Cell::new(&val_str));
-			}
-			table.add_row(Row::new(row));
-		}
+	for row in df.formatted_rows() {
+		let cells = row
+			.iter()
+			.map(|cell| Cell::new(cell.as_deref().unwrap_or("<NULL>")))
+			.collect();
+		table.add_row(Row::new(cells));
 	}
 
 	table.to_string()
 }
 
+fn ipc_error(ctx: &EvalContext, err: ArrowError) -> Exception {
+	ctx.exception(&SourceLocation::Unknown, format!("arrow error: {}", err))
+}
+
 pub struct DataFrame {
 	batches: Vec<RecordBatch>,
+	column_types: Vec<Type>,
 	num_rows: i64,
 	num_cols: i64,
 }
@@ -76,11 +81,12 @@ impl NativeType for DataFrame {
 }
 
 impl DataFrame {
-	pub fn new(batches: Vec<RecordBatch>) -> Self {
+	pub fn new(batches: Vec<RecordBatch>, column_types: Vec<Type>) -> Self {
 		let num_rows = batches.iter().map(|b| b.num_rows() as i64).sum();
 		let num_cols = batches[0].num_columns() as i64;
 		DataFrame {
 			batches,
+			column_types,
 			num_rows,
 			num_cols,
 		}
@@ -94,12 +100,120 @@ impl DataFrame {
 		self.num_cols
 	}
 
-	pub fn col(&self, name: &str) -> Option<Value> {
-		let (col_idx, field) = self.batches[0].schema().column_with_name(name)?;
+	pub fn column_types(&self) -> &[Type] {
+		&self.column_types
+	}
+
+	// as an Arrow IPC stream, which is how workspaces save them
+	pub fn to_ipc(&self, ctx: &EvalContext) -> EvalResult<Vec<u8>> {
+		let mut ipc = Vec::new();
+		let mut writer = StreamWriter::try_new(&mut ipc, &self.batches[0].schema())
+			.map_err(|err| ipc_error(ctx, err))?;
+		for batch in &self.batches {
+			writer.write(batch).map_err(|err| ipc_error(ctx, err))?;
+		}
+		writer.finish().map_err(|err| ipc_error(ctx, err))?;
+		drop(writer);
+		Ok(ipc)
+	}
+
+	pub fn from_ipc(ctx: &EvalContext, ipc: &[u8], column_types: Vec<Type>) -> EvalResult<Self> {
+		let mut reader = StreamReader::try_new(ipc).map_err(|err| ipc_error(ctx, err))?;
+		let mut batches = Vec::new();
+		while let Some(batch) = reader.next_batch().map_err(|err| ipc_error(ctx, err))? {
+			batches.push(batch);
+		}
+		match batches.is_empty() {
+			true => Err(ctx.exception(&SourceLocation::Unknown, "no record batches")),
+			false => Ok(DataFrame::new(batches, column_types)),
+		}
+	}
+
+	pub fn column_names(&self) -> Vec<String> {
+		self.batches[0]
+			.schema()
+			.fields()
+			.iter()
+			.map(|f| f.name().clone())
+			.collect()
+	}
+
+	// every cell as display text, with None for nulls
+	pub fn formatted_rows(&self) -> Vec<Vec<Option<String>>> {
+		let mut rows = Vec::new();
+		for batch in &self.batches {
+			for row_idx in 0..batch.num_rows() {
+				let row = (0..batch.num_columns())
+					.map(|col_idx| batch.column(col_idx))
+					.map(|col| match col.is_null(row_idx) {
+						true => None,
+						false => Some(match col.data_type() {
+							DataType::Int64 => array_val!(Int64Array, col, row_idx).to_string(),
+							DataType::Float64 => array_val!(Float64Array, col, row_idx).to_string(),
+							DataType::Boolean => array_val!(BooleanArray, col, row_idx).to_string(),
+							DataType::Utf8 => array_val!(StringArray, col, row_idx).to_string(),
+							_ => unreachable!(),
+						}),
+					})
+					.collect();
+				rows.push(row);
+			}
+		}
+		rows
+	}
+
+	// the same columns holding the same cells, however they're batched
+	pub fn same_contents(&self, other: &DataFrame) -> bool {
+		self.column_names() == other.column_names()
+			&& self.column_types == other.column_types
+			&& self.formatted_rows() == other.formatted_rows()
+	}
+
+	pub fn contents_hash(&self) -> i64 {
+		let mut hasher = DefaultHasher::new();
+		self.column_names().hash(&mut hasher);
+		self.column_types.hash(&mut hasher);
+		self.formatted_rows().hash(&mut hasher);
+		hasher.finish() as i64
+	}
+
+	pub fn col(&self, ctx: &EvalContext, name: &str) -> EvalResult<Value> {
+		let (col_idx, field) = self.batches[0]
+			.schema()
+			.column_with_name(name)
+			.ok_or_else(|| {
+				ctx.exception(
+					&SourceLocation::Unknown,
+					format!("column not found: {}", name),
+				)
+			})?;
+
 		let arrays = self.batches.iter().map(|b| b.column(col_idx));
-		match field.data_type() {
-			DataType::Int64 => Some(Value::new_native(IntVector::from_arrays(arrays))),
-			_ => panic!("unhandled datatype"),
+		match (field.data_type(), &self.column_types[col_idx]) {
+			(DataType::Int64, _) => Ok(Value::new_native(IntVector::from_arrays(arrays))),
+			(DataType::Utf8, Type::Decimal) => {
+				let mut values = List::new();
+				for arr in arrays {
+					let arr = array_cast!(StringArray, arr);
+					for idx in 0..arr.len() {
+						if arr.is_null(idx) {
+							values.push(Value::Null(()));
+							continue;
+						}
+
+						let decimal = arr
+							.value(idx)
+							.parse()
+							.map_err(|message: String| ctx.exception(&SourceLocation::Unknown, message))?;
+						values.push(Value::Decimal(decimal));
+					}
+				}
+				Ok(Value::List(values))
+			}
+			(other, _) => Err(ctx.exception(
+				&SourceLocation::Unknown,
+				format!("unsupported column type: {:?}", other),
+			)),
 		}
 	}
 }
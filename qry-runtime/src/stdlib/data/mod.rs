@@ -5,6 +5,7 @@ mod sql_codegen;
 mod sql_pipeline;
 mod sqlite;
 mod vectors;
+mod workspace;
 
 pub use connection::*;
 pub use data_env::*;
@@ -12,3 +13,4 @@ pub use dataframe::*;
 pub use sql_codegen::*;
 pub use sql_pipeline::*;
 pub use vectors::*;
+pub use workspace::*;
@@ -1,7 +1,9 @@
 use super::{ColumnMap, Vector};
-use crate::{eval, EvalContext, EvalResult, NativeGenericType, Type, Value};
+use crate::{
+	eval, expect_method, Decimal, EvalContext, EvalResult, NativeGenericType, Type, Value,
+};
 use lazy_static::lazy_static;
-use qry_lang::{BinaryOperator, Syntax, SyntaxNode};
+use qry_lang::{BinaryOperator, SourceLocation, Syntax, SyntaxNode};
 use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
@@ -59,6 +61,13 @@ fn int_literal(i: i64) -> SqlExpression {
 	}
 }
 
+fn decimal_literal(d: Decimal) -> SqlExpression {
+	SqlExpression {
+		sql_type: Type::Decimal,
+		text: d.to_string(),
+	}
+}
+
 fn bool_literal(b: bool) -> SqlExpression {
 	SqlExpression {
 		sql_type: Type::Bool,
@@ -66,14 +75,22 @@ fn bool_literal(b: bool) -> SqlExpression {
 	}
 }
 
-fn interpret_value(val: Value) -> SqlExpression {
+fn interpret_value(
+	ctx: &EvalContext,
+	val: Value,
+	location: &SourceLocation,
+) -> EvalResult<SqlExpression> {
 	match val {
-		Value::Null(_) => null_literal(),
-		Value::String(s) => string_literal(&s),
-		Value::Int(i) => int_literal(i),
-		Value::Float(f) => float_literal(f),
-		Value::Bool(b) => bool_literal(b),
-		_ => unreachable!(),
+		Value::Null(_) => Ok(null_literal()),
+		Value::String(s) => Ok(string_literal(&s)),
+		Value::Int(i) => Ok(int_literal(i)),
+		Value::Float(f) => Ok(float_literal(f)),
+		Value::Decimal(d) => Ok(decimal_literal(d)),
+		Value::Bool(b) => Ok(bool_literal(b)),
+		_ => Err(ctx.exception(
+			location,
+			format!("can't use a {} value in a query", val.runtime_type()),
+		)),
 	}
 }
 
@@ -91,22 +108,33 @@ lazy_static! {
 	};
 }
 
+// table and column names come from scripts, so they're quoted rather than
+// trusted to be plain identifiers
+pub fn quote_identifier(name: &str) -> String {
+	format!("\"{}\"", name.replace('"', "\"\""))
+}
+
 pub fn expr_to_sql(
 	ctx: &EvalContext,
 	expr: &SyntaxNode,
 	metadata: &ColumnMap,
 ) -> EvalResult<SqlExpression> {
 	match &expr.syntax {
-		Syntax::Interpolate(contained_expr) => Ok(interpret_value(eval(ctx, contained_expr)?)),
+		Syntax::Interpolate(contained_expr) => {
+			interpret_value(ctx, eval(ctx, contained_expr)?, &contained_expr.location)
+		}
 		Syntax::Null => Ok(null_literal()),
 		Syntax::String(s) => Ok(string_literal(&s)),
 		Syntax::Int(i) => Ok(int_literal(*i)),
 		Syntax::Float(f) => Ok(float_literal(*f)),
 		Syntax::Bool(b) => Ok(bool_literal(*b)),
-		Syntax::Ident(col_name) => Ok(SqlExpression {
-			text: col_name.to_string(),
-			sql_type: metadata[col_name].data_type.clone(),
-		}),
+		Syntax::Ident(col_name) => match metadata.get(col_name) {
+			Some(col) => Ok(SqlExpression {
+				text: col_name.to_string(),
+				sql_type: col.data_type.clone(),
+			}),
+			None => Err(ctx.exception(&expr.location, format!("column not found: {}", col_name))),
+		},
 		Syntax::BinaryOp { lhs, op, rhs } => {
 			let lhs_val = expr_to_sql(ctx, lhs, metadata)?;
 			let rhs_val = expr_to_sql(ctx, rhs, metadata)?;
@@ -115,11 +143,11 @@ pub fn expr_to_sql(
 					let resolved = method.resolve(&[lhs_val.sql_type, rhs_val.sql_type]);
 
 					match resolved {
-						Some(callable) => Ok(SqlExpression {
+						Ok(callable) => Ok(SqlExpression {
 							text: format!("{} {} {}", lhs_val.text, binop_symbol(op), rhs_val.text),
 							sql_type: callable.signature().return_type.clone(),
 						}),
-						None => Err(ctx.exception(&expr.location, "failed to resolve method")),
+						Err(_) => Err(ctx.exception(&expr.location, "failed to resolve method")),
 					}
 				}
 				None => Err(ctx.exception(&expr.location, "unhandled binary operator")),
@@ -144,7 +172,10 @@ pub fn expr_to_sql(
 
 			// TODO: validate consistency of return types
 			// for now, just use the first
-			let sql_type = expr_to_sql(ctx, &cases[0].returns, metadata)?.sql_type;
+			let first_case = cases
+				.first()
+				.ok_or_else(|| ctx.exception(&expr.location, "switch requires at least one case"))?;
+			let sql_type = expr_to_sql(ctx, &first_case.returns, metadata)?.sql_type;
 
 			Ok(SqlExpression { text, sql_type })
 		}
@@ -158,11 +189,11 @@ pub fn expr_to_sql(
 				_ => Err(ctx.exception(&target.location, "expected identifier for call")),
 			}?;
 
-			let method = ctx
+			let method_val = ctx
 				.env
 				.get(&target_ident)
-				.ok_or_else(|| ctx.exception(&target.location, "no method found"))?
-				.as_method();
+				.ok_or_else(|| ctx.exception(&target.location, "no method found"))?;
+			let method = expect_method(ctx, method_val, &target.location)?;
 
 			let args = positional_args
 				.iter()
@@ -192,7 +223,7 @@ pub fn expr_to_sql(
 
 			let method_impl = method
 				.resolve(&arg_types)
-				.ok_or_else(|| ctx.exception(&expr.location, "failed to resolve method impl"))?;
+				.map_err(|_| ctx.exception(&expr.location, "failed to resolve method impl"))?;
 
 			Ok(SqlExpression {
 				text,
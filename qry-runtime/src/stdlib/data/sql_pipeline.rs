@@ -1,15 +1,13 @@
-use super::{expr_to_sql, Connection};
-use crate::{EvalContext, EvalResult, NativeType, Type};
-use arrow::record_batch::RecordBatch;
+use super::{expr_to_sql, quote_identifier, Connection, DataFrame};
+use crate::{EvalContext, EvalEvent, EvalResult, MaybeSync, NativeType, Shared, Type};
 use indexmap::IndexMap;
-use qry_lang::SyntaxNode;
-use std::rc::Rc;
+use qry_lang::{SourceLocation, SyntaxNode};
 use std::sync::atomic::{AtomicI64, Ordering};
 
 #[derive(Clone)]
 pub struct QueryPipeline {
-	conn: Rc<Connection>,
-	pub steps: Vec<Rc<dyn PipelineStep>>,
+	conn: Shared<Connection>,
+	pub steps: Vec<Shared<dyn PipelineStep>>,
 }
 
 impl NativeType for QueryPipeline {
@@ -19,16 +17,25 @@ impl NativeType for QueryPipeline {
 }
 
 impl QueryPipeline {
-	pub fn new(conn: Rc<Connection>, table: &str) -> Self {
+	pub fn new(conn: Shared<Connection>, table: &str) -> Self {
 		QueryPipeline {
 			conn,
-			steps: vec![Rc::new(FromStep {
+			steps: vec![Shared::new(FromStep {
 				table: table.to_string(),
 			})],
 		}
 	}
 
-	pub fn add(&self, step: Rc<dyn PipelineStep>) -> QueryPipeline {
+	pub fn connection(&self) -> &Shared<Connection> {
+		&self.conn
+	}
+
+	// the calls that built this pipeline, see PipelineStep::describe
+	pub fn describe(&self) -> Vec<String> {
+		self.steps.iter().map(|s| s.describe()).collect()
+	}
+
+	pub fn add(&self, step: Shared<dyn PipelineStep>) -> QueryPipeline {
 		let mut ret = self.clone();
 		ret.steps.push(step);
 		ret
@@ -41,7 +48,7 @@ impl QueryPipeline {
 				grouping: vec![],
 				columns: IndexMap::new(),
 			},
-			counter: Rc::new(AtomicI64::new(0)),
+			counter: Shared::new(AtomicI64::new(0)),
 			query: "".into(),
 		};
 
@@ -49,15 +56,28 @@ impl QueryPipeline {
 			state = step.render(ctx, state)?;
 		}
 
+		ctx.emit(EvalEvent::SqlRendered { sql: &state.query });
 		Ok(state)
 	}
 
-	pub fn collect(&self, ctx: &EvalContext) -> EvalResult<RecordBatch> {
+	pub fn collect(&self, ctx: &EvalContext) -> EvalResult<DataFrame> {
 		let state = self.generate(ctx)?;
-		self
+		let batch = self
 			.conn
-			.conn_impl
-			.collect(ctx, &state.query, &state.metadata.columns)
+			.collect(ctx, &state.query, &state.metadata.columns)?;
+
+		// the arrow schema alone can't tell a decimal column from a string one
+		let column_types = batch
+			.schema()
+			.fields()
+			.iter()
+			.map(|f| match state.metadata.columns.get(f.name()) {
+				Some(col) => col.data_type.clone(),
+				None => Type::Any,
+			})
+			.collect();
+
+		Ok(DataFrame::new(vec![batch], column_types))
 	}
 }
 
@@ -99,8 +119,8 @@ impl QueryMetadata {
 
 #[derive(Clone)]
 pub struct RenderState {
-	pub conn: Rc<Connection>,
-	counter: Rc<AtomicI64>,
+	pub conn: Shared<Connection>,
+	counter: Shared<AtomicI64>,
 	pub query: String,
 	pub metadata: QueryMetadata,
 }
@@ -147,8 +167,21 @@ impl RenderState {
 	}
 }
 
-pub trait PipelineStep {
+pub trait PipelineStep: MaybeSync {
 	fn render(&self, ctx: &EvalContext, state: RenderState) -> EvalResult<RenderState>;
+
+	// the call that added this step, as source with the pipeline argument left
+	// out, so that chaining the descriptions with |> rebuilds the pipeline
+	fn describe(&self) -> String;
+}
+
+fn describe_call(name: &str, args: &[SyntaxNode], named_args: &[(String, SyntaxNode)]) -> String {
+	let args = args
+		.iter()
+		.map(|a| a.to_string())
+		.chain(named_args.iter().map(|(n, a)| format!("{} = {}", n, a)))
+		.collect::<Vec<_>>();
+	format!("{}({})", name, args.join(", "))
 }
 
 pub struct FromStep {
@@ -169,10 +202,16 @@ impl PipelineStep for FromStep {
 				columns,
 				grouping: vec![],
 			},
-			query: format!("select {} from {}", select, self.table),
+			query: format!("select {} from {}", select, quote_identifier(&self.table)),
 			..state
 		})
 	}
+
+	// the first step, so there's no pipeline to leave out. The connection is
+	// left for whoever evaluates it to bind
+	fn describe(&self) -> String {
+		format!("table(connection, \"{}\")", self.table)
+	}
 }
 
 pub struct FilterStep {
@@ -185,6 +224,10 @@ impl PipelineStep for FilterStep {
 		let predicate = expr_to_sql(&self.ctx, &self.predicate, &state.metadata.columns)?;
 		Ok(state.wrap(&state.metadata, Some(&format!("where {}", predicate.text))))
 	}
+
+	fn describe(&self) -> String {
+		describe_call("filter", std::slice::from_ref(&self.predicate), &[])
+	}
 }
 
 pub struct SelectStep {
@@ -212,6 +255,10 @@ impl PipelineStep for SelectStep {
 
 		Ok(state.wrap(&state.metadata.with_cols(new_col_types), None))
 	}
+
+	fn describe(&self) -> String {
+		describe_call("select", &self.cols, &[])
+	}
 }
 
 pub struct MutateStep {
@@ -240,6 +287,10 @@ impl PipelineStep for MutateStep {
 
 		Ok(state.wrap(&new_metadata, None))
 	}
+
+	fn describe(&self) -> String {
+		describe_call("mutate", &[], &self.new_cols)
+	}
 }
 
 pub struct GroupStep {
@@ -259,6 +310,10 @@ impl PipelineStep for GroupStep {
 
 		Ok(state.wrap(&new_metadata, None))
 	}
+
+	fn describe(&self) -> String {
+		describe_call("group_by", &self.grouping, &[])
+	}
 }
 
 pub struct AggregateStep {
@@ -267,7 +322,7 @@ pub struct AggregateStep {
 }
 
 impl PipelineStep for AggregateStep {
-	fn render(&self, _: &EvalContext, state: RenderState) -> EvalResult<RenderState> {
+	fn render(&self, ctx: &EvalContext, state: RenderState) -> EvalResult<RenderState> {
 		let aggregation_exprs = self
 			.aggregations
 			.iter()
@@ -277,10 +332,17 @@ impl PipelineStep for AggregateStep {
 		let mut new_columns = ColumnMap::new();
 
 		for name in &state.metadata.grouping {
+			let grouped_col = state.metadata.columns.get(name).ok_or_else(|| {
+				ctx.exception(
+					&SourceLocation::Unknown,
+					format!("can only group by columns, got: {}", name),
+				)
+			})?;
+
 			new_columns.insert(
 				name.to_string(),
 				QueryColumn {
-					data_type: state.metadata.columns[name].data_type.clone(),
+					data_type: grouped_col.data_type.clone(),
 					kind: ColumnKind::Named,
 				},
 			);
@@ -310,4 +372,8 @@ impl PipelineStep for AggregateStep {
 			))
 		}
 	}
+
+	fn describe(&self) -> String {
+		describe_call("aggregate", &[], &self.aggregations)
+	}
 }
@@ -1,23 +1,37 @@
 #![cfg(not(target_arch = "wasm32"))]
 
-use super::{ColumnKind, ColumnMap, Connection, ConnectionImpl, QueryColumn};
-use crate::{Environment, EvalContext, EvalResult, Signature, Type, Value};
+use super::{quote_identifier, ColumnKind, ColumnMap, Connection, ConnectionImpl, QueryColumn};
+use crate::{
+	Capability, Decimal, Environment, EvalContext, EvalResult, Exception, ExceptionKind, Signature,
+	Type, Value,
+};
 use arrow::array::{ArrayBuilder, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::error::Result as ArrowResult;
 use arrow::record_batch::RecordBatch;
 use lazy_static::lazy_static;
 use qry_lang::SourceLocation;
+use rusqlite::limits::Limit;
 use rusqlite::types::ValueRef;
 use rusqlite::{Connection as SqliteConnection, Result as SqliteResult, NO_PARAMS};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::panic::Location;
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
 
+// rusqlite connections can be sent between threads but not shared, so
+// statements run one at a time
 struct SqliteConnectionImpl {
-	conn: SqliteConnection,
+	conn: Mutex<SqliteConnection>,
+	// opened without the filesystem capability, so attaching is disabled
+	files_denied: bool,
+}
+
+impl SqliteConnectionImpl {
+	fn conn(&self) -> MutexGuard<'_, SqliteConnection> {
+		self.conn.lock().unwrap_or_else(|err| err.into_inner())
+	}
 }
 
 fn box_builder<T>(builder: T) -> Rc<RefCell<dyn ArrayBuilder>>
@@ -52,6 +66,35 @@ pub fn sqlite_op<T>(ctx: &EvalContext, res: SqliteResult<T>) -> EvalResult<T> {
 	}
 }
 
+fn unsupported_column_type(ctx: &EvalContext, data_type: &Type) -> Exception {
+	ctx.exception(
+		&SourceLocation::Unknown,
+		format!("unsupported column type: {}", data_type),
+	)
+}
+
+// sqlite has no decimal storage class, so a decimal column can hold integers,
+// reals or text depending on how the value was written
+fn read_decimal(ctx: &EvalContext, val: ValueRef) -> EvalResult<Decimal> {
+	let decimal = match val {
+		ValueRef::Integer(i) => Some(Decimal::from(i)),
+		ValueRef::Real(f) => Decimal::from_f64(f),
+		other => other.as_str().ok().and_then(|s| s.parse().ok()),
+	};
+
+	decimal.ok_or_else(|| {
+		ctx.exception(
+			&SourceLocation::Unknown,
+			format!("invalid decimal value: {:?}", val),
+		)
+	})
+}
+
+fn is_decimal_decl(decl_type: &str) -> bool {
+	let decl_type = decl_type.to_lowercase();
+	decl_type.starts_with("decimal") || decl_type.starts_with("numeric")
+}
+
 macro_rules! write_cell {
 	($ctx: expr, $builder: expr, $builder_type: ty, $row: expr, $col_idx: expr, $write_func: expr, $getter: expr) => {{
 		let mut builder = $builder.borrow_mut();
@@ -82,18 +125,27 @@ macro_rules! write_cell {
 
 impl ConnectionImpl for SqliteConnectionImpl {
 	fn get_relation_metadata(&self, ctx: &EvalContext, table: &str) -> EvalResult<ColumnMap> {
+		let conn = self.conn();
+		let table = quote_identifier(table);
 		let names_query = format!("select * from {} limit 0", table);
-		let stmt = sqlite_op(ctx, self.conn.prepare(&names_query))?;
+		let stmt = sqlite_op(ctx, conn.prepare(&names_query))?;
 
 		let col_names = stmt.column_names();
+		// typeof() only reports the storage class, the declared type is the only
+		// place a decimal column shows up
+		let decimal_cols = stmt
+			.columns()
+			.iter()
+			.map(|c| c.decl_type().map_or(false, is_decimal_decl))
+			.collect::<Vec<_>>();
 
 		let typeof_calls = col_names
 			.iter()
-			.map(|n| format!("typeof({})", n))
+			.map(|n| format!("typeof({})", quote_identifier(n)))
 			.collect::<Vec<_>>();
 
 		let type_query = format!("select {} from {} limit 1", typeof_calls.join(", "), table);
-		let mut stmt = sqlite_op(ctx, self.conn.prepare(&type_query))?;
+		let mut stmt = sqlite_op(ctx, conn.prepare(&type_query))?;
 		let mut rows = sqlite_op(ctx, stmt.query(NO_PARAMS))?;
 
 		let mut metadata = ColumnMap::new();
@@ -101,7 +153,16 @@ impl ConnectionImpl for SqliteConnectionImpl {
 		if let Some(row) = sqlite_op(ctx, rows.next())? {
 			for (col_idx, name) in col_names.iter().enumerate() {
 				let col_affinity: String = sqlite_op(ctx, row.get(col_idx))?;
-				let data_type = AFFINITY_MAP[&col_affinity].clone();
+				let data_type = match AFFINITY_MAP.get(&col_affinity) {
+					_ if decimal_cols[col_idx] => Type::Decimal,
+					Some(data_type) => data_type.clone(),
+					None => {
+						return Err(ctx.exception(
+							&SourceLocation::Unknown,
+							format!("unsupported sqlite column type: {}", col_affinity),
+						))
+					}
+				};
 				metadata.insert(
 					name.to_string(),
 					QueryColumn {
@@ -116,7 +177,17 @@ impl ConnectionImpl for SqliteConnectionImpl {
 	}
 
 	fn execute(&self, ctx: &EvalContext, sql: &str) -> EvalResult<i64> {
-		let rows = sqlite_op(ctx, self.conn.execute(sql, NO_PARAMS))?;
+		let rows = match self.conn().execute(sql, NO_PARAMS) {
+			// how sqlite reports the attach limit set in open_sqlite
+			Err(err) if self.files_denied && err.to_string().starts_with("too many attached") => {
+				return Err(ctx.exception_of_kind(
+					ExceptionKind::Denied,
+					&SourceLocation::Unknown,
+					format!("capability denied: {}", Capability::Filesystem.name()),
+				))
+			}
+			res => sqlite_op(ctx, res)?,
+		};
 		Ok(rows as i64)
 	}
 
@@ -126,27 +197,50 @@ impl ConnectionImpl for SqliteConnectionImpl {
 		query: &str,
 		result_metadata: &ColumnMap,
 	) -> EvalResult<RecordBatch> {
-		let mut stmt = sqlite_op(ctx, self.conn.prepare(query))?;
+		let conn = self.conn();
+		let mut stmt = sqlite_op(ctx, conn.prepare(query))?;
 
 		let col_metadata = stmt
 			.columns()
 			.iter()
-			.map(|c| (c.name().to_string(), result_metadata[c.name()].clone()))
-			.collect::<Vec<_>>();
+			.map(|c| match result_metadata.get(c.name()) {
+				Some(col) => Ok((c.name().to_string(), col.clone())),
+				None => Err(ctx.exception(
+					&SourceLocation::Unknown,
+					format!("column not found: {}", c.name()),
+				)),
+			})
+			.collect::<EvalResult<Vec<_>>>()?;
 
 		let builders = col_metadata
 			.iter()
 			.map(|(_, c)| match c.data_type {
-				Type::Int => box_builder(Int64Builder::new(0)),
-				Type::Float => box_builder(Float64Builder::new(0)),
-				Type::Bool => box_builder(BooleanBuilder::new(0)),
-				Type::String => box_builder(StringBuilder::new(0)),
-				_ => unimplemented!(),
+				Type::Int => Ok(box_builder(Int64Builder::new(0))),
+				Type::Float => Ok(box_builder(Float64Builder::new(0))),
+				Type::Bool => Ok(box_builder(BooleanBuilder::new(0))),
+				Type::String | Type::Decimal => Ok(box_builder(StringBuilder::new(0))),
+				ref other => Err(unsupported_column_type(ctx, other)),
 			})
-			.collect::<Vec<_>>();
+			.collect::<EvalResult<Vec<_>>>()?;
 
+		let max_rows = ctx.limits().max_rows;
+		let mut num_rows = 0;
 		let mut rows = sqlite_op(ctx, stmt.query(NO_PARAMS))?;
 		while let Some(row) = sqlite_op(ctx, rows.next())? {
+			// long queries are only interruptible between rows
+			ctx.check_interrupt(&SourceLocation::Unknown)?;
+			num_rows += 1;
+			match max_rows {
+				Some(max_rows) if num_rows > max_rows => {
+					return Err(ctx.exception_of_kind(
+						ExceptionKind::LimitExceeded,
+						&SourceLocation::Unknown,
+						format!("row limit exceeded ({} rows)", max_rows),
+					))
+				}
+				_ => (),
+			}
+
 			for (col_idx, (builder, (_, col))) in builders.iter().zip(&col_metadata).enumerate() {
 				match col.data_type {
 					Type::Int => write_cell!(ctx, builder, Int64Builder, row, col_idx),
@@ -161,7 +255,22 @@ impl ConnectionImpl for SqliteConnectionImpl {
 						|b: &mut StringBuilder, s| b.append_value(s),
 						|| row.get::<usize, String>(col_idx)
 					),
-					_ => unimplemented!(),
+					Type::Decimal => {
+						let mut builder = builder.borrow_mut();
+						let concrete_builder = builder
+							.as_any_mut()
+							.downcast_mut::<StringBuilder>()
+							.unwrap();
+
+						match row.get_raw(col_idx) {
+							ValueRef::Null => arrow_op(ctx, concrete_builder.append_null())?,
+							val => {
+								let decimal = read_decimal(ctx, val)?;
+								arrow_op(ctx, concrete_builder.append_value(&decimal.to_string()))?
+							}
+						}
+					}
+					ref other => return Err(unsupported_column_type(ctx, other)),
 				};
 			}
 		}
@@ -169,19 +278,19 @@ impl ConnectionImpl for SqliteConnectionImpl {
 		let fields = col_metadata
 			.iter()
 			.map(|(n, c)| {
-				Field::new(
-					n,
-					match c.data_type {
-						Type::Int => DataType::Int64,
-						Type::Float => DataType::Float64,
-						Type::Bool => DataType::Boolean,
-						Type::String => DataType::Utf8,
-						_ => unimplemented!(),
-					},
-					true,
-				)
+				let data_type = match c.data_type {
+					Type::Int => DataType::Int64,
+					Type::Float => DataType::Float64,
+					Type::Bool => DataType::Boolean,
+					Type::String => DataType::Utf8,
+					// arrow 0.16 has no decimal type, so decimals are carried as their
+					// exact text and parsed back by DataFrame::col
+					Type::Decimal => DataType::Utf8,
+					ref other => return Err(unsupported_column_type(ctx, other)),
+				};
+				Ok(Field::new(n, data_type, true))
 			})
-			.collect();
+			.collect::<EvalResult<Vec<_>>>()?;
 
 		let cols = builders.iter().map(|b| b.borrow_mut().finish()).collect();
 		Ok(arrow_op(
@@ -191,27 +300,64 @@ impl ConnectionImpl for SqliteConnectionImpl {
 	}
 }
 
-fn connect_sqlite(ctx: &EvalContext, args: &[Value], _: &[(&str, Value)]) -> EvalResult<Value> {
-	let connstring = args[0].as_string();
+pub fn open_sqlite(ctx: &EvalContext, connstring: &str) -> EvalResult<Value> {
+	ctx.require(Capability::DatabaseConnect, &SourceLocation::Unknown)?;
+	if connstring != ":memory:" {
+		ctx.require(Capability::Filesystem, &SourceLocation::Unknown)?;
+	}
+	ctx.require_connection(connstring, &SourceLocation::Unknown)?;
+
 	let sqlite_conn = match SqliteConnection::open(connstring) {
 		Ok(conn) => conn,
 		Err(err) => {
 			return Err(ctx.exception(&SourceLocation::Unknown, format!("sqlite error: {}", err)))
 		}
 	};
+	// ATTACH and VACUUM INTO can open any file, even from an in-memory database
+	let files_denied = !ctx.sandbox.allows(Capability::Filesystem);
+	if files_denied {
+		sqlite_conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0);
+	}
 
 	Ok(Value::new_native(Connection {
 		driver: "sqlite".into(),
-		conn_impl: Box::new(SqliteConnectionImpl { conn: sqlite_conn }),
+		target: connstring.to_string(),
+		conn_impl: Box::new(SqliteConnectionImpl {
+			conn: Mutex::new(sqlite_conn),
+			files_denied,
+		}),
 	}))
 }
 
-pub fn sqlite_init_env(env: &Environment) {
-	let connection_type = &env.get("Connection").unwrap().as_type();
+fn connect_sqlite(ctx: &EvalContext, args: &[Value], _: &[(&str, Value)]) -> EvalResult<Value> {
+	open_sqlite(ctx, args[0].as_string(ctx)?)
+}
 
+// named connections are configured by the host, e.g. from a project manifest
+fn connect_named(ctx: &EvalContext, args: &[Value], _: &[(&str, Value)]) -> EvalResult<Value> {
+	let name = args[0].as_string(ctx)?;
+	match ctx.connections.get(name) {
+		Some(target) => open_sqlite(ctx, target),
+		None => Err(ctx.exception(
+			&SourceLocation::Unknown,
+			format!("unknown connection: {}", name),
+		)),
+	}
+}
+
+pub fn sqlite_init_env(env: &Environment, connection_type: &Type) {
 	env.define_builtin(
 		"connect_sqlite",
-		Signature::returning(connection_type).param("connstring", &Type::String),
+		Signature::returning(connection_type)
+			.param("connstring", &Type::String)
+			.with_doc("Opens a SQLite database from a path or connection string."),
 		connect_sqlite,
 	);
+	env.define_builtin(
+		"connect",
+		Signature::returning(connection_type)
+			.param("name", &Type::String)
+			.with_doc("Opens the connection configured with that name in qry.toml."),
+		connect_named,
+	);
 }
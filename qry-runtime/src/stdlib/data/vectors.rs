@@ -39,15 +39,17 @@ impl IntVector {
 		}
 	}
 
-	pub fn from_values(values: &[Value]) -> Self {
+	pub fn from_values(ctx: &EvalContext, values: &[Value]) -> EvalResult<Self> {
 		let mut builder = Int64Builder::new(values.len());
 		for value in values {
-			builder.append_value(value.as_int()).unwrap();
+			if let Err(err) = builder.append_value(value.as_int(ctx)?) {
+				return Err(ctx.exception(&SourceLocation::Unknown, format!("arrow error: {}", err)));
+			}
 		}
 
-		Self {
+		Ok(Self {
 			data: vec![Arc::new(builder.finish())],
-		}
+		})
 	}
 
 	pub fn sum(&self) -> i64 {
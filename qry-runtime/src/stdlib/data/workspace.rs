@@ -0,0 +1,84 @@
+use super::{Connection, DataFrame, QueryPipeline};
+use crate::{
+	eval_multi, EvalResult, SavedConnection, SavedValue, Shared, Type, Value, WorkspaceReader,
+	WorkspaceWriter,
+};
+use qry_lang::{parse, SourceLocation};
+
+fn column_type(name: &str) -> Type {
+	match name {
+		"Int" => Type::Int,
+		"Float" => Type::Float,
+		"Bool" => Type::Bool,
+		"String" => Type::String,
+		"Decimal" => Type::Decimal,
+		_ => Type::Any,
+	}
+}
+
+fn save_connection(writer: &mut WorkspaceWriter, conn: &Shared<Connection>) -> usize {
+	writer.add_connection(
+		&**conn as *const Connection as usize,
+		SavedConnection {
+			driver: conn.driver.clone(),
+			target: conn.target.clone(),
+		},
+	)
+}
+
+pub fn save_native(writer: &mut WorkspaceWriter, value: &Value) -> EvalResult<Option<SavedValue>> {
+	if let Some(df) = value.native::<DataFrame>() {
+		let (offset, length) = writer.add_blob(&df.to_ipc(writer.ctx)?);
+		return Ok(Some(SavedValue::DataFrame {
+			offset,
+			length,
+			column_types: df
+				.column_types()
+				.iter()
+				.map(|t| t.name().to_string())
+				.collect(),
+		}));
+	}
+	if let Some(pipeline) = value.native::<QueryPipeline>() {
+		return Ok(Some(SavedValue::Pipeline {
+			connection: save_connection(writer, pipeline.connection()),
+			steps: pipeline.describe(),
+		}));
+	}
+	if let Some(conn) = value.native::<Connection>() {
+		return Ok(Some(SavedValue::Connection {
+			connection: save_connection(writer, &conn),
+		}));
+	}
+	Ok(None)
+}
+
+pub fn restore_native(reader: &WorkspaceReader, saved: &SavedValue) -> EvalResult<Value> {
+	let ctx = reader.ctx;
+	match saved {
+		SavedValue::DataFrame {
+			offset,
+			length,
+			column_types,
+		} => {
+			let column_types = column_types.iter().map(|t| column_type(t)).collect();
+			let df = DataFrame::from_ipc(ctx, reader.blob(*offset, *length)?, column_types)?;
+			Ok(Value::new_native(df))
+		}
+		// rebuilt by running the calls that made it, with the data library in
+		// scope and the connection bound to the name the first step expects
+		SavedValue::Pipeline { connection, steps } => {
+			let env = ctx.env.child("workspace");
+			if let Some(Value::Library(data)) = ctx.library_env.get("data") {
+				data.copy_to(&env);
+			}
+			env.update("connection", reader.connection(*connection)?);
+
+			let source = steps.join(" |> ");
+			let syntax = parse(&source, "<workspace>")
+				.map_err(|err| ctx.exception(&SourceLocation::Unknown, format!("parser {}", err)))?;
+			eval_multi(&ctx.child(env), &syntax)
+		}
+		_ => Err(ctx.exception(&SourceLocation::Unknown, "unsupported workspace value")),
+	}
+}
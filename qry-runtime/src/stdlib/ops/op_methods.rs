@@ -1,16 +1,28 @@
-use crate::{Environment, Method, RuntimeMethods, Signature, Type, Value};
-use qry_lang::{BinaryOperator, UnaryOperator};
+use crate::{
+	builtin_hash, comparable, register_identity_equality, values_equal, BigInt, Callable, Decimal,
+	Environment, EvalContext, EvalResult, List, Method, Output, RuntimeMethods, Shared, Signature,
+	Type, Value,
+};
+use qry_lang::{BinaryOperator, SourceLocation, UnaryOperator};
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::convert::TryFrom;
 
-pub fn create() -> (RuntimeMethods, Rc<Environment>) {
+pub fn create() -> (RuntimeMethods, Shared<Environment>) {
 	let env = Environment::new("ops");
 	let binops = init_binops();
 	let unops = init_unops();
 	let to_string = Method::new("to_string", &["val"], Some(Type::String), None);
 	let index = Method::new("index", &["container", "key"], None, None);
+	let display = Method::new("display", &["val"], Some(Type::Null), None);
+	let hash = Method::new("hash", &["val"], Some(Type::Int), None);
+	to_string.set_doc("Converts a value to a string, which is how it's printed.");
+	index.set_doc("Implements `container[key]`.");
+	display.set_doc("Writes a value to the output, as a table for types that have one.");
+	hash.set_doc("A hash of a value, the same for any values that compare equal.");
 	init_to_string(&to_string);
+	init_display(&display);
 	init_index(&index);
+	init_hash(&hash);
 
 	for v in binops.values() {
 		env.update(v.name(), Value::Method(v.clone()));
@@ -23,7 +35,9 @@ pub fn create() -> (RuntimeMethods, Rc<Environment>) {
 	(
 		RuntimeMethods {
 			to_string,
+			display,
 			index,
+			hash,
 			binops,
 			unops,
 		},
@@ -67,36 +81,27 @@ macro_rules! equality_ops {
 	};
 }
 
-macro_rules! numeric_binops {
-	($map: expr, $lhs_type: ident, $rhs_type: ident, $return_type: ident, $native_type: ty) => {
-		binop!(
-			$map[&BinaryOperator::Add],
-			$lhs_type,
-			$rhs_type,
-			$return_type,
-			|a, b| (a as $native_type) + (b as $native_type)
-		);
-		binop!(
-			$map[&BinaryOperator::Sub],
-			$lhs_type,
-			$rhs_type,
-			$return_type,
-			|a, b| (a as $native_type) - (b as $native_type)
-		);
-		binop!(
-			$map[&BinaryOperator::Mul],
-			$lhs_type,
-			$rhs_type,
-			$return_type,
-			|a, b| (a as $native_type) * (b as $native_type)
-		);
-		binop!(
-			$map[&BinaryOperator::Div],
-			$lhs_type,
-			$rhs_type,
-			$return_type,
-			|a, b| (a as $native_type) / (b as $native_type)
-		);
+// like binop! but the builder returns a Result, so overflow and division by
+// zero raise exceptions instead of wrapping or panicking
+macro_rules! checked_binop {
+	($method: expr, $lhs_type: ident, $rhs_type: ident, $return_type: ident, $builder: expr) => {
+		$method.register_builtin(
+			Signature::returning(&Type::$return_type)
+				.param("a", &Type::$lhs_type)
+				.param("b", &Type::$rhs_type),
+			|ctx, args, _| match (&args[0], &args[1]) {
+				(Value::$lhs_type(a), Value::$rhs_type(b)) => match $builder(a.clone(), b.clone()) {
+					Ok(val) => Ok(Value::$return_type(val)),
+					Err(message) => Err(ctx.exception(&SourceLocation::Unknown, message)),
+				},
+				_ => unreachable!(),
+			},
+			)
+	};
+}
+
+macro_rules! comparison_ops {
+	($map: expr, $lhs_type: ident, $rhs_type: ident, $native_type: ty) => {
 		binop!(
 			$map[&BinaryOperator::Lt],
 			$lhs_type,
@@ -142,6 +147,21 @@ macro_rules! unop {
 	};
 }
 
+macro_rules! checked_unop {
+	($method: expr, $target_type: ident, $return_type: ident, $builder: expr) => {
+		$method.register_builtin(
+			Signature::returning(&Type::$return_type).param("a", &Type::$target_type),
+			|ctx, args, _| match &args[0] {
+				Value::$target_type(a) => match $builder(a.clone()) {
+					Ok(val) => Ok(Value::$return_type(val)),
+					Err(message) => Err(ctx.exception(&SourceLocation::Unknown, message)),
+				},
+				_ => unreachable!(),
+			},
+			)
+	};
+}
+
 fn init_to_string(to_string: &Method) {
 	unop!(to_string, Null, String, |_| "null".into());
 	unop!(to_string, String, String, |a| a);
@@ -150,26 +170,23 @@ fn init_to_string(to_string: &Method) {
 		.into_boxed_str());
 	unop!(to_string, Float, String, |a: f64| format!("{:?}", a)
 		.into_boxed_str());
+	unop!(to_string, BigInt, String, |a: BigInt| a
+		.to_string()
+		.into_boxed_str());
+	unop!(to_string, Decimal, String, |a: Decimal| a
+		.to_string()
+		.into_boxed_str());
 	unop!(to_string, Bool, String, |a: bool| a
 		.to_string()
 		.into_boxed_str());
 	to_string.register_builtin(
 		Signature::returning(&Type::String).param("obj", &Type::Method),
-		|_, args, _| {
-			let method = args[0].as_method();
+		|ctx, args, _| {
+			let method = args[0].as_method(ctx)?;
 			let signatures = method
 				.supported_signatures()
 				.iter()
-				.map(|s| {
-					let param_string = s
-						.params
-						.iter()
-						.map(|p| format!("{}: {}", p.name, p.param_type.name()))
-						.collect::<Vec<_>>()
-						.join(", ");
-
-					format!("({}) -> {}", param_string, s.return_type.name())
-				})
+				.map(|s| s.to_string())
 				.collect::<Vec<_>>()
 				.join("\n");
 
@@ -180,9 +197,20 @@ fn init_to_string(to_string: &Method) {
 	);
 	to_string.register_builtin(
 		Signature::returning(&Type::String).param("obj", &Type::Type),
-		|_, args, _| {
-			let type_val = args[0].as_type();
-			Ok(Value::String(type_val.name().into()))
+		|ctx, args, _| {
+			let type_val = args[0].as_type(ctx)?;
+			Ok(Value::String(type_val.to_string().into_boxed_str()))
+		},
+	);
+}
+
+fn init_display(display: &Method) {
+	display.register_builtin(
+		Signature::returning(&Type::Null).param("obj", &Type::Any),
+		|ctx, args, _| {
+			let text = ctx.methods.to_string.call(ctx, &[args[0].clone()], &[])?;
+			ctx.write_output(Output::Text(text.as_string(ctx)?.to_string()))?;
+			Ok(Value::Null(()))
 		},
 	);
 }
@@ -192,69 +220,176 @@ fn init_index(index: &Method) {
 		Signature::returning(&Type::Any)
 			.param("list", &Type::List)
 			.param("index", &Type::Int),
-		|_, args, _| {
-			let list = args[0].as_list();
-			let index = args[1].as_int() as usize;
-			Ok(list[index].clone())
+		|ctx, args, _| {
+			let list = args[0].as_list(ctx)?;
+			let index = args[1].as_int(ctx)?;
+			match usize::try_from(index).ok().and_then(|i| list.get(i)) {
+				Some(val) => Ok(val.clone()),
+				None => Err(ctx.exception(
+					&SourceLocation::Unknown,
+					format!(
+						"index out of bounds: {} (list has {} items)",
+						index,
+						list.len()
+					),
+				)),
+			}
 		},
 	);
 }
 
+fn init_hash(hash: &Method) {
+	hash.register_builtin(
+		Signature::returning(&Type::Int).param("val", &Type::Any),
+		|ctx, args, _| Ok(Value::Int(builtin_hash(ctx, &args[0])?)),
+	);
+}
+
+fn lists_equal(ctx: &EvalContext, a: &List, b: &List) -> EvalResult<bool> {
+	if a.len() != b.len() {
+		return Ok(false);
+	}
+	// items == can't compare make the lists unequal, rather than an error
+	for (a, b) in a.iter().zip(b) {
+		if !comparable(a, b) || !values_equal(ctx, a, b)? {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
 #[allow(clippy::float_cmp)] // this is invoked by the Float == Float method
-fn init_binops() -> HashMap<BinaryOperator, Rc<Method>> {
+fn init_binops() -> HashMap<BinaryOperator, Shared<Method>> {
 	let mut m = HashMap::new();
-	let mut new_binop = |name, op| {
+	let mut new_binop = |name, op, symbol| {
 		let method = Method::new(name, &["a", "b"], None, None);
+		method.set_doc(&format!("Implements `a {} b`.", symbol));
 		m.insert(op, method.clone());
 		method
 	};
 
-	let add = new_binop("add", BinaryOperator::Add);
-	new_binop("sub", BinaryOperator::Sub);
-	new_binop("mul", BinaryOperator::Mul);
-	new_binop("div", BinaryOperator::Div);
-	let equal = new_binop("equal", BinaryOperator::Equal);
-	let not_equal = new_binop("not_equal", BinaryOperator::NotEqual);
-	new_binop("lt", BinaryOperator::Lt);
-	new_binop("lte", BinaryOperator::Lte);
-	new_binop("gt", BinaryOperator::Gt);
-	new_binop("gte", BinaryOperator::Gte);
-	let and = new_binop("and", BinaryOperator::And);
-	let or = new_binop("or", BinaryOperator::Or);
-
-	numeric_binops!(m, Int, Int, Int, i64);
-	numeric_binops!(m, Float, Float, Float, f64);
-	numeric_binops!(m, Int, Float, Float, f64);
-	numeric_binops!(m, Float, Int, Float, f64);
+	let add = new_binop("add", BinaryOperator::Add, "+");
+	let sub = new_binop("sub", BinaryOperator::Sub, "-");
+	let mul = new_binop("mul", BinaryOperator::Mul, "*");
+	let div = new_binop("div", BinaryOperator::Div, "/");
+	let equal = new_binop("equal", BinaryOperator::Equal, "==");
+	let not_equal = new_binop("not_equal", BinaryOperator::NotEqual, "!=");
+	new_binop("lt", BinaryOperator::Lt, "<");
+	new_binop("lte", BinaryOperator::Lte, "<=");
+	new_binop("gt", BinaryOperator::Gt, ">");
+	new_binop("gte", BinaryOperator::Gte, ">=");
+	let and = new_binop("and", BinaryOperator::And, "&");
+	let or = new_binop("or", BinaryOperator::Or, "|");
+
+	// mixed arithmetic with an Int resolves to the Float, BigInt or Decimal
+	// impls via promotion
+	checked_binop!(add, Int, Int, Int, |a: i64, b: i64| a
+		.checked_add(b)
+		.ok_or("integer overflow"));
+	checked_binop!(sub, Int, Int, Int, |a: i64, b: i64| a
+		.checked_sub(b)
+		.ok_or("integer overflow"));
+	checked_binop!(mul, Int, Int, Int, |a: i64, b: i64| a
+		.checked_mul(b)
+		.ok_or("integer overflow"));
+	checked_binop!(div, Int, Int, Int, |a: i64, b: i64| match b {
+		0 => Err("division by zero"),
+		_ => a.checked_div(b).ok_or("integer overflow"),
+	});
+	comparison_ops!(m, Int, Int, i64);
+
+	binop!(add, Float, Float, Float, |a, b| a + b);
+	binop!(sub, Float, Float, Float, |a, b| a - b);
+	binop!(mul, Float, Float, Float, |a, b| a * b);
+	binop!(div, Float, Float, Float, |a, b| a / b);
+	comparison_ops!(m, Float, Float, f64);
+
+	binop!(add, BigInt, BigInt, BigInt, |a: BigInt, b| a.add(&b));
+	binop!(sub, BigInt, BigInt, BigInt, |a: BigInt, b| a.sub(&b));
+	binop!(mul, BigInt, BigInt, BigInt, |a: BigInt, b| a.mul(&b));
+	checked_binop!(div, BigInt, BigInt, BigInt, |a: BigInt, b| a
+		.checked_div(&b)
+		.ok_or("division by zero"));
+	comparison_ops!(m, BigInt, BigInt, BigInt);
+
+	checked_binop!(add, Decimal, Decimal, Decimal, |a: Decimal, b| a
+		.checked_add(&b)
+		.ok_or("decimal overflow"));
+	checked_binop!(sub, Decimal, Decimal, Decimal, |a: Decimal, b| a
+		.checked_sub(&b)
+		.ok_or("decimal overflow"));
+	checked_binop!(mul, Decimal, Decimal, Decimal, |a: Decimal, b| a
+		.checked_mul(&b)
+		.ok_or("decimal overflow"));
+	checked_binop!(div, Decimal, Decimal, Decimal, |a: Decimal, b: Decimal| {
+		if b.is_zero() {
+			return Err("division by zero");
+		}
+		a.checked_div(&b).ok_or("decimal overflow")
+	});
+	comparison_ops!(m, Decimal, Decimal, Decimal);
 
 	equality_ops!(m, Bool, Bool, bool);
 	equality_ops!(m, Type, Type, Type);
 	binop!(and, Bool, Bool, Bool, |a, b| a && b);
 	binop!(or, Bool, Bool, Bool, |a, b| a || b);
+	binop!(or, Type, Type, Type, |a, b| Type::union(vec![a, b]));
 
 	binop!(add, String, String, String, |a, b| format!("{}{}", a, b)
 		.into_boxed_str());
 	binop!(equal, String, String, Bool, |a, b| a == b);
 	binop!(not_equal, String, String, Bool, |a, b| a != b);
 
+	equal.register_builtin(
+		Signature::returning(&Type::Bool)
+			.param("a", &Type::List)
+			.param("b", &Type::List),
+		|ctx, args, _| {
+			let (a, b) = (args[0].as_list(ctx)?, args[1].as_list(ctx)?);
+			Ok(Value::Bool(lists_equal(ctx, a, b)?))
+		},
+	);
+	not_equal.register_builtin(
+		Signature::returning(&Type::Bool)
+			.param("a", &Type::List)
+			.param("b", &Type::List),
+		|ctx, args, _| {
+			let (a, b) = (args[0].as_list(ctx)?, args[1].as_list(ctx)?);
+			Ok(Value::Bool(!lists_equal(ctx, a, b)?))
+		},
+	);
+
+	// values of unrelated types don't compare, but any two functions can
+	let callable = Type::union(vec![Type::Function, Type::Builtin, Type::Method]);
+	for value_type in &[Type::Null, Type::Syntax, Type::Library, callable] {
+		register_identity_equality(&equal, &not_equal, value_type);
+	}
+
 	m
 }
 
-fn init_unops() -> HashMap<UnaryOperator, Rc<Method>> {
+fn init_unops() -> HashMap<UnaryOperator, Shared<Method>> {
 	let mut m = HashMap::new();
-	let mut new_unop = |name, op| {
+	let mut new_unop = |name, op, symbol| {
 		let method = Method::new(name, &["a"], None, None);
+		method.set_doc(&format!("Implements `{}a`.", symbol));
 		m.insert(op, method.clone());
 		method
 	};
 
-	let negate = new_unop("negate", UnaryOperator::Negate);
-	let minus = new_unop("minus", UnaryOperator::Minus);
+	let negate = new_unop("negate", UnaryOperator::Negate, "!");
+	let minus = new_unop("minus", UnaryOperator::Minus, "-");
 
 	unop!(negate, Bool, Bool, |a: bool| !a);
 
-	unop!(minus, Int, Int, |a: i64| -a);
+	checked_unop!(minus, Int, Int, |a: i64| a
+		.checked_neg()
+		.ok_or("integer overflow"));
 	unop!(minus, Float, Float, |a: f64| -a);
+	unop!(minus, BigInt, BigInt, |a: BigInt| a.neg());
+	checked_unop!(minus, Decimal, Decimal, |a: Decimal| a
+		.checked_neg()
+		.ok_or("decimal overflow"));
 
 	m
 }
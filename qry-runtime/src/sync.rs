@@ -0,0 +1,67 @@
+// the runtime is single threaded by default. The sync feature swaps the
+// shared pointers and interior mutability used throughout for thread-safe
+// ones, so contexts can be moved between threads and values shared across
+// them
+
+#[cfg(not(feature = "sync"))]
+mod shared {
+	use std::any::Any;
+
+	pub type Shared<T> = std::rc::Rc<T>;
+	pub type Weak<T> = std::rc::Weak<T>;
+	pub type Lock<T> = std::cell::RefCell<T>;
+	pub type AnyObject = dyn Any;
+
+	// bound on anything stored in a value, which only has to be thread-safe
+	// when the sync feature is on
+	pub trait MaybeSync {}
+	impl<T: ?Sized> MaybeSync for T {}
+}
+
+#[cfg(feature = "sync")]
+mod shared {
+	use std::any::Any;
+	use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+	pub type Shared<T> = std::sync::Arc<T>;
+	pub type Weak<T> = std::sync::Weak<T>;
+	pub type AnyObject = dyn Any + Send + Sync;
+
+	pub trait MaybeSync: Send + Sync {}
+	impl<T: ?Sized + Send + Sync> MaybeSync for T {}
+
+	// has the same borrow API as RefCell so the rest of the runtime doesn't
+	// care which one it gets
+	#[derive(Default)]
+	pub struct Lock<T>(RwLock<T>);
+
+	impl<T> Lock<T> {
+		pub fn new(value: T) -> Self {
+			Lock(RwLock::new(value))
+		}
+
+		// a panic while the lock is held is reported as an exception and leaves
+		// the value usable, same as RefCell, so poisoning is ignored
+		pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+			self.0.read().unwrap_or_else(|err| err.into_inner())
+		}
+
+		pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+			self.0.write().unwrap_or_else(|err| err.into_inner())
+		}
+	}
+
+	impl<T: Clone> Clone for Lock<T> {
+		fn clone(&self) -> Self {
+			Lock::new(self.borrow().clone())
+		}
+	}
+
+	impl<T: std::fmt::Debug> std::fmt::Debug for Lock<T> {
+		fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+			self.borrow().fmt(f)
+		}
+	}
+}
+
+pub use shared::*;
@@ -0,0 +1,57 @@
+use super::{location_for_stacktrace, EvalContext, EvalEvent, Observer};
+use tracing::{debug, info, trace, warn};
+
+// forwards evaluation events to the host's tracing subscriber, with calls and
+// assignments at trace level, queries at debug/info and exceptions at warn.
+// Targets are all under `qry::` so they can be filtered together
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingObserver;
+
+impl Observer for TracingObserver {
+	fn event(&self, ctx: &EvalContext, event: &EvalEvent) {
+		let depth = ctx.callstack.borrow().len() as u64;
+		match event {
+			EvalEvent::CallEnter {
+				name,
+				location,
+				args,
+			} => trace!(
+				target: "qry::call",
+				name,
+				depth,
+				location = %location_for_stacktrace(location),
+				args = args.len() as u64,
+				"enter"
+			),
+			EvalEvent::CallExit { name, result } => trace!(
+				target: "qry::call",
+				name,
+				depth,
+				ok = result.is_ok(),
+				"exit"
+			),
+			EvalEvent::Assign { name, value } => trace!(
+				target: "qry::assign",
+				name,
+				depth,
+				ty = value.runtime_type().name(),
+				"assign"
+			),
+			EvalEvent::Exception(ex) => warn!(
+				target: "qry::exception",
+				kind = ?ex.kind,
+				location = %location_for_stacktrace(&ex.location),
+				"{}",
+				ex.message
+			),
+			EvalEvent::SqlRendered { sql } => debug!(target: "qry::sql", sql, "rendered"),
+			EvalEvent::SqlExecuted { sql, rows, elapsed } => info!(
+				target: "qry::sql",
+				sql,
+				rows = *rows as u64,
+				elapsed_us = elapsed.as_micros() as u64,
+				"executed"
+			),
+		}
+	}
+}
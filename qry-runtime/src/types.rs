@@ -1,4 +1,4 @@
-use super::{EvalContext, EvalResult};
+use super::{EvalContext, EvalResult, MaybeSync, Method, Shared};
 use std::any::TypeId;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -7,6 +7,8 @@ pub enum Type {
 	Null,
 	Int,
 	Float,
+	BigInt,
+	Decimal,
 	Bool,
 	String,
 	Type,
@@ -18,6 +20,8 @@ pub enum Type {
 	SyntaxPlaceholder,
 	Native(Box<NativeDescriptor>),
 	List,
+	Union(Vec<Type>),
+	Interface(Shared<Interface>),
 }
 
 impl Type {
@@ -29,12 +33,50 @@ impl Type {
 		Type::Native(Box::new(NativeDescriptor::of_generic::<T>()))
 	}
 
+	// flattens nested unions and drops duplicates so equal unions compare equal
+	// regardless of how they were written
+	pub fn union(types: impl IntoIterator<Item = Type>) -> Type {
+		let mut members: Vec<Type> = Vec::new();
+		for t in types {
+			let flattened = match t {
+				Type::Union(inner) => inner,
+				Type::Any => return Type::Any,
+				t => vec![t],
+			};
+
+			for member in flattened {
+				if !members.contains(&member) {
+					members.push(member);
+				}
+			}
+		}
+
+		members.sort_by_cached_key(|t| t.to_string());
+		match members.len() {
+			1 => members.pop().unwrap(),
+			_ => Type::Union(members),
+		}
+	}
+
+	pub fn accepts(&self, other: &Type) -> bool {
+		match (self, other) {
+			(Type::Any, _) => true,
+			_ if self == other => true,
+			(_, Type::Union(members)) => members.iter().all(|m| self.accepts(m)),
+			(Type::Union(members), _) => members.iter().any(|m| m.accepts(other)),
+			(Type::Interface(interface), _) => interface.missing_impls(other).is_empty(),
+			_ => false,
+		}
+	}
+
 	pub fn name(&self) -> &str {
 		match self {
 			Self::Any => "Any",
 			Self::Null => "Null",
 			Self::Int => "Int",
 			Self::Float => "Float",
+			Self::BigInt => "BigInt",
+			Self::Decimal => "Decimal",
 			Self::Bool => "Bool",
 			Self::String => "String",
 			Self::Type => "Type",
@@ -46,10 +88,63 @@ impl Type {
 			Self::SyntaxPlaceholder => "SyntaxPlaceholder",
 			Self::Native(d) => d.name,
 			Self::List => "List",
+			Self::Union(_) => "Union",
+			Self::Interface(i) => &i.name,
+		}
+	}
+}
+
+impl std::fmt::Display for Type {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		match self {
+			Self::Union(members) => {
+				let member_names = members
+					.iter()
+					.map(|m| m.to_string())
+					.collect::<Vec<_>>()
+					.join(" | ");
+				write!(f, "{}", member_names)
+			}
+			_ => write!(f, "{}", self.name()),
 		}
 	}
 }
 
+#[derive(Debug)]
+pub struct Interface {
+	pub name: String,
+	pub methods: Vec<Shared<Method>>,
+}
+
+impl Interface {
+	pub fn missing_impls(&self, t: &Type) -> Vec<&str> {
+		self
+			.methods
+			.iter()
+			.filter(|m| !m.implements(t))
+			.map(|m| m.name())
+			.collect()
+	}
+}
+
+// interfaces are nominal, two declarations with the same methods are distinct
+impl std::hash::Hash for Interface {
+	fn hash<H>(&self, h: &mut H)
+	where
+		H: std::hash::Hasher,
+	{
+		(self as *const Self).hash(h)
+	}
+}
+
+impl std::cmp::PartialEq for Interface {
+	fn eq(&self, other: &Self) -> bool {
+		std::ptr::eq(self, other)
+	}
+}
+
+impl std::cmp::Eq for Interface {}
+
 type GenericResolver = fn(&EvalContext, &[Type]) -> EvalResult<Type>;
 
 #[derive(Clone)]
@@ -103,7 +198,7 @@ impl NativeDescriptor {
 	}
 }
 
-pub trait NativeType {
+pub trait NativeType: MaybeSync {
 	fn name() -> &'static str;
 }
 
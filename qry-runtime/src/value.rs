@@ -1,12 +1,23 @@
-use super::{Builtin, Environment, Function, Method, NativeDescriptor, NativeType, Type};
-use qry_lang::SyntaxNode;
-use std::any::Any;
-use std::rc::Rc;
+use super::{
+	identical, AnyObject, BigInt, Builtin, Decimal, Environment, EvalContext, EvalResult, Function,
+	List, Method, NativeDescriptor, NativeType, Shared, Type,
+};
+use qry_lang::{SourceLocation, SyntaxNode};
 
 #[derive(Debug, Clone)]
 pub struct NativeWrapper {
 	descriptor: Box<NativeDescriptor>,
-	obj: Rc<dyn Any>,
+	obj: Shared<AnyObject>,
+}
+
+impl NativeWrapper {
+	pub fn same_object(&self, other: &NativeWrapper) -> bool {
+		self.address() == other.address()
+	}
+
+	pub fn address(&self) -> usize {
+		Shared::as_ptr(&self.obj) as *const () as usize
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -14,16 +25,18 @@ pub enum Value {
 	Null(()),
 	Int(i64),
 	Float(f64),
+	BigInt(BigInt),
+	Decimal(Decimal),
 	Bool(bool),
 	String(Box<str>),
 	Type(Type),
-	Function(Rc<Function>),
-	Builtin(Rc<Builtin>),
-	Method(Rc<Method>),
-	Library(Rc<Environment>),
+	Function(Shared<Function>),
+	Builtin(Shared<Builtin>),
+	Method(Shared<Method>),
+	Library(Shared<Environment>),
 	Syntax(Box<SyntaxNode>),
 	Native(NativeWrapper),
-	List(Vec<Value>),
+	List(List),
 }
 
 impl Value {
@@ -32,6 +45,8 @@ impl Value {
 			Self::Null(_) => Type::Null,
 			Self::Int(_) => Type::Int,
 			Self::Float(_) => Type::Float,
+			Self::BigInt(_) => Type::BigInt,
+			Self::Decimal(_) => Type::Decimal,
 			Self::Bool(_) => Type::Bool,
 			Self::String(_) => Type::String,
 			Self::Type(_) => Type::Type,
@@ -45,88 +60,100 @@ impl Value {
 		}
 	}
 
-	pub fn as_native<T>(&self) -> Rc<T>
+	fn unexpected<T>(&self, ctx: &EvalContext, expected: &str) -> EvalResult<T> {
+		Err(ctx.exception(
+			&SourceLocation::Unknown,
+			format!("expected {}, got {}", expected, self.runtime_type()),
+		))
+	}
+
+	pub fn native<T>(&self) -> Option<Shared<T>>
 	where
-		T: Any,
+		T: 'static + NativeType,
 	{
-		if let Self::Native(w) = self {
-			return w.obj.clone().downcast::<T>().unwrap();
+		match self {
+			Self::Native(w) => w.obj.clone().downcast::<T>().ok(),
+			_ => None,
 		}
+	}
 
-		panic!("value is not a native type");
+	pub fn as_native<T>(&self, ctx: &EvalContext) -> EvalResult<Shared<T>>
+	where
+		T: 'static + NativeType,
+	{
+		match self.native::<T>() {
+			Some(obj) => Ok(obj),
+			None => self.unexpected(ctx, T::name()),
+		}
+	}
+
+	pub fn as_string(&self, ctx: &EvalContext) -> EvalResult<&str> {
+		match self {
+			Self::String(s) => Ok(s),
+			_ => self.unexpected(ctx, "a string"),
+		}
 	}
 
-	pub fn as_string(&self) -> &str {
+	pub fn as_syntax(&self, ctx: &EvalContext) -> EvalResult<&SyntaxNode> {
 		match self {
-			Self::String(s) => s,
-			_ => panic!("value is not a string"),
+			Self::Syntax(expr) => Ok(expr),
+			_ => self.unexpected(ctx, "an expression"),
 		}
 	}
 
-	pub fn as_syntax(&self) -> &SyntaxNode {
+	pub fn as_bool(&self, ctx: &EvalContext) -> EvalResult<bool> {
 		match self {
-			Self::Syntax(expr) => expr,
-			_ => panic!("value is not an expression"),
+			Self::Bool(b) => Ok(*b),
+			_ => self.unexpected(ctx, "a bool"),
 		}
 	}
 
-	pub fn as_bool(&self) -> bool {
+	pub fn as_int(&self, ctx: &EvalContext) -> EvalResult<i64> {
 		match self {
-			Self::Bool(b) => *b,
-			_ => panic!("value is not a bool"),
+			Self::Int(i) => Ok(*i),
+			_ => self.unexpected(ctx, "an int"),
 		}
 	}
 
-	pub fn as_int(&self) -> i64 {
+	pub fn as_float(&self, ctx: &EvalContext) -> EvalResult<f64> {
 		match self {
-			Self::Int(i) => *i,
-			_ => panic!("value is not an int"),
+			Self::Float(f) => Ok(*f),
+			_ => self.unexpected(ctx, "a float"),
 		}
 	}
 
-	pub fn as_method(&self) -> Rc<Method> {
+	pub fn as_method(&self, ctx: &EvalContext) -> EvalResult<Shared<Method>> {
 		match self {
-			Self::Method(m) => m.clone(),
-			_ => panic!("value is not a method"),
+			Self::Method(m) => Ok(m.clone()),
+			_ => self.unexpected(ctx, "a method"),
 		}
 	}
 
-	pub fn as_type(&self) -> Type {
+	pub fn as_type(&self, ctx: &EvalContext) -> EvalResult<Type> {
 		match self {
-			Self::Type(t) => t.clone(),
-			_ => panic!("value is not a type"),
+			Self::Type(t) => Ok(t.clone()),
+			_ => self.unexpected(ctx, "a type"),
 		}
 	}
 
-	pub fn as_list(&self) -> &[Value] {
+	pub fn as_list(&self, ctx: &EvalContext) -> EvalResult<&List> {
 		match self {
-			Self::List(l) => &l,
-			_ => panic!("value is not a list"),
+			Self::List(l) => Ok(l),
+			_ => self.unexpected(ctx, "a list"),
 		}
 	}
 
 	pub fn new_native<T: 'static + NativeType>(obj: T) -> Value {
 		Value::Native(NativeWrapper {
-			obj: Rc::new(obj),
+			obj: Shared::new(obj),
 			descriptor: Box::new(NativeDescriptor::of::<T>()),
 		})
 	}
 }
 
-// FIXME: only used for unit tests
-// switch to matching to remove this outright
+// identity rather than what `==` says, which needs a context to dispatch
 impl PartialEq<Value> for Value {
 	fn eq(&self, other: &Value) -> bool {
-		match (self, &other) {
-			(Value::Null(_), Value::Null(_)) => true,
-			(Value::Int(a), Value::Int(b)) => a == b,
-			(Value::Float(a), Value::Float(b)) => a == b,
-			(Value::Bool(a), Value::Bool(b)) => a == b,
-			(Value::String(a), Value::String(b)) => a == b,
-			(Value::Type(a), Value::Type(b)) => a == b,
-			(Value::Syntax(a), Value::Syntax(b)) => a == b,
-			(Value::List(a), Value::List(b)) => a == b,
-			_ => false,
-		}
+		identical(self, other)
 	}
 }
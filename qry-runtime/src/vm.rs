@@ -0,0 +1,327 @@
+use super::{
+	assign_value, call_with_args, check_arity, eval_import, expect_method, function_signature,
+	instantiate_generic, make_interface, positional_param_type, typecheck_val, CallArg, CallSite,
+	Callable, Chunk, CompiledBody, Environment, EvalContext, EvalEvent, EvalResult, Function, Op,
+	Shared, Type, Value,
+};
+use qry_lang::SourceLocation;
+
+struct Frame<'a> {
+	ctx: EvalContext,
+	slots: Vec<Option<Value>>,
+	locals: &'a [String],
+	owns_env: bool,
+}
+
+impl Frame<'_> {
+	// function frames read from their captured environment directly and only
+	// copy it once something needs to observe or modify locals by name
+	fn materialize(&mut self) {
+		if self.locals.is_empty() {
+			return;
+		}
+
+		if !self.owns_env {
+			self.ctx = self.ctx.child(self.ctx.env.child("funceval"));
+			self.owns_env = true;
+		}
+
+		for (name, val) in self.locals.iter().zip(&self.slots) {
+			if let Some(val) = val {
+				self.ctx.env.update(name, val.clone());
+			}
+		}
+	}
+
+	fn load_name(&self, name: &str, location: &SourceLocation) -> EvalResult<Value> {
+		match self.ctx.env.get(name) {
+			Some(val) => Ok(val),
+			None => Err(self.ctx.exception(location, format!("not found: {}", name))),
+		}
+	}
+}
+
+fn eval_call_arg(frame: &mut Frame, param_type: &Type, arg: &CallArg) -> EvalResult<Value> {
+	match param_type {
+		Type::SyntaxPlaceholder => Ok(Value::Syntax(Box::new(arg.syntax.clone()))),
+		_ => {
+			let val = run_chunk(frame, &arg.chunk)?;
+			typecheck_val(&frame.ctx, val, param_type)
+		}
+	}
+}
+
+fn invoke<C: Callable + ?Sized>(
+	frame: &mut Frame,
+	callable: &C,
+	site: &CallSite,
+	location: &SourceLocation,
+	is_builtin: bool,
+) -> EvalResult<Value> {
+	let sig = callable.signature();
+	check_arity(&frame.ctx, sig, site.positional.len(), site.named.len())?;
+
+	let quotes_args = sig
+		.params
+		.iter()
+		.map(|p| &p.param_type)
+		.chain(&sig.trailing_type)
+		.chain(&sig.named_trailing_type)
+		.any(|t| *t == Type::SyntaxPlaceholder);
+
+	if is_builtin || quotes_args {
+		frame.materialize();
+	}
+
+	let mut args = Vec::with_capacity(site.positional.len());
+	for (i, arg) in site.positional.iter().enumerate() {
+		args.push(eval_call_arg(frame, positional_param_type(sig, i), arg)?);
+	}
+
+	let mut named_args = Vec::with_capacity(site.named.len());
+	for (name, arg) in &site.named {
+		let named_trailing_type = sig.named_trailing_type.as_ref().unwrap();
+		named_args.push((
+			name.as_str(),
+			eval_call_arg(frame, named_trailing_type, arg)?,
+		));
+	}
+
+	call_with_args(&frame.ctx, callable, location, &args, &named_args)
+}
+
+fn make_function(
+	frame: &mut Frame,
+	chunk: &Chunk,
+	index: usize,
+	location: &SourceLocation,
+	stack: &mut Vec<Value>,
+) -> EvalResult<Value> {
+	let proto = &chunk.functions[index];
+	let impl_for = if proto.is_method_impl {
+		stack.pop()
+	} else {
+		None
+	};
+
+	let return_type = stack.pop().unwrap().as_type(&frame.ctx)?;
+	let param_types = stack
+		.split_off(stack.len() - proto.param_names.len())
+		.iter()
+		.map(|t| t.as_type(&frame.ctx))
+		.collect::<EvalResult<Vec<_>>>()?;
+
+	frame.materialize();
+
+	let function = Shared::new(Function {
+		body: proto.body.clone(),
+		compiled: Some(proto.compiled.clone()),
+		signature: function_signature(
+			&proto.param_names,
+			param_types,
+			return_type,
+			proto.doc.clone(),
+		),
+		env: frame.ctx.env.snapshot(),
+		name: proto.name.clone(),
+		location: location.clone(),
+	});
+
+	if let Some(impl_for) = impl_for {
+		expect_method(&frame.ctx, impl_for, location)?.register(&frame.ctx, function.clone())?;
+	}
+
+	Ok(Value::Function(function))
+}
+
+fn run_chunk(frame: &mut Frame, chunk: &Chunk) -> EvalResult<Value> {
+	let mut stack: Vec<Value> = Vec::new();
+	let mut ip = 0;
+
+	while ip < chunk.code.len() {
+		let location = &chunk.locations[ip];
+		let op = chunk.code[ip];
+		ip += 1;
+		frame.ctx.step(location)?;
+
+		match op {
+			Op::Constant(index) => stack.push(chunk.constants[index].clone()),
+			Op::Pop => {
+				stack.pop();
+			}
+			Op::Dup => {
+				let top = stack.last().unwrap().clone();
+				stack.push(top);
+			}
+			Op::LoadLocal(slot) => {
+				let val = match &frame.slots[slot] {
+					Some(val) => val.clone(),
+					None => frame.load_name(&frame.locals[slot], location)?,
+				};
+				stack.push(val);
+			}
+			Op::StoreLocal(slot) => {
+				let value = stack.last().unwrap().clone();
+				frame.ctx.emit(EvalEvent::Assign {
+					name: &frame.locals[slot],
+					value: &value,
+				});
+				frame.slots[slot] = Some(value);
+			}
+			Op::LoadName(index) => stack.push(frame.load_name(&chunk.names[index], location)?),
+			Op::StoreName(index) => {
+				assign_value(
+					&frame.ctx,
+					&chunk.names[index],
+					stack.last().unwrap().clone(),
+				)?;
+			}
+			Op::Access(index) => {
+				let name = &chunk.names[index];
+				let val = match stack.pop().unwrap() {
+					Value::Library(lib_env) => match lib_env.get(name) {
+						Some(val) => val,
+						None => {
+							return Err(
+								frame
+									.ctx
+									.exception(location, format!("not found: {}", name)),
+							)
+						}
+					},
+					_ => {
+						return Err(
+							frame
+								.ctx
+								.exception(location, "access operator requires a library"),
+						)
+					}
+				};
+				stack.push(val);
+			}
+			Op::Call(index) => {
+				let site = &chunk.call_sites[index];
+				let ret = match stack.pop().unwrap() {
+					Value::Builtin(builtin) => invoke(frame, &*builtin, site, location, true)?,
+					Value::Function(func) => invoke(frame, &*func, site, location, false)?,
+					Value::Method(method) => invoke(frame, &*method, site, location, false)?,
+					_ => return Err(frame.ctx.exception(location, "target is not callable")),
+				};
+				stack.push(ret);
+			}
+			Op::BinaryOp(op) => {
+				let rhs = stack.pop().unwrap();
+				let lhs = stack.pop().unwrap();
+				let method = frame.ctx.methods.binops[&op].clone();
+				let ret = call_with_args(&frame.ctx, &*method, location, &[lhs, rhs], &[]);
+				stack.push(ret?);
+			}
+			Op::UnaryOp(op) => {
+				let target = stack.pop().unwrap();
+				let method = frame.ctx.methods.unops[&op].clone();
+				stack.push(call_with_args(
+					&frame.ctx,
+					&*method,
+					location,
+					&[target],
+					&[],
+				)?);
+			}
+			Op::Index(num_keys) => {
+				let args = stack.split_off(stack.len() - num_keys - 1);
+				let index = frame.ctx.methods.index.clone();
+				stack.push(call_with_args(&frame.ctx, &*index, location, &args, &[])?);
+			}
+			Op::SwitchCase => {
+				let case_val = stack.pop().unwrap();
+				let target_val = stack.pop().unwrap();
+				let eq_method = frame.ctx.methods.binops[&qry_lang::BinaryOperator::Equal].clone();
+				let args = [target_val, case_val];
+				stack.push(call_with_args(
+					&frame.ctx,
+					&*eq_method,
+					location,
+					&args,
+					&[],
+				)?);
+			}
+			Op::Jump(target) => ip = target,
+			Op::JumpIfFalse(target) => {
+				let cond = stack.pop().unwrap().as_bool(&frame.ctx);
+				if !cond.map_err(|ex| ex.or_located_at(location))? {
+					ip = target;
+				}
+			}
+			Op::Import(index) => {
+				let spec = &chunk.imports[index];
+				frame.materialize();
+				stack.push(eval_import(&frame.ctx, location, &spec.from, &spec.import)?);
+			}
+			Op::Export(index) => frame.ctx.env.export(&chunk.names[index]),
+			Op::ExpectType => match stack.last().unwrap() {
+				Value::Type(_) => (),
+				_ => return Err(frame.ctx.exception(location, "expected a type")),
+			},
+			Op::MakeFunction(index) => {
+				let function = make_function(frame, chunk, index, location, &mut stack)?;
+				stack.push(function);
+			}
+			Op::Instantiate(num_args) => {
+				let type_args = stack
+					.split_off(stack.len() - num_args)
+					.iter()
+					.map(|t| t.as_type(&frame.ctx))
+					.collect::<EvalResult<Vec<_>>>()?;
+				let target = stack.pop().unwrap();
+				stack.push(instantiate_generic(
+					&frame.ctx, target, location, &type_args,
+				)?);
+			}
+			Op::MakeInterface { name, num_methods } => {
+				let methods = stack.split_off(stack.len() - num_methods);
+				stack.push(make_interface(
+					&frame.ctx,
+					&chunk.names[name],
+					methods,
+					location,
+				)?);
+			}
+			Op::Fail(message) => return Err(frame.ctx.exception(location, message)),
+		}
+	}
+
+	Ok(stack.pop().unwrap_or(Value::Null(())))
+}
+
+pub fn call_compiled(
+	ctx: &EvalContext,
+	env: &Shared<Environment>,
+	compiled: &CompiledBody,
+	args: &[Value],
+) -> EvalResult<Value> {
+	let mut slots = vec![None; compiled.locals.len()];
+	for (slot, arg) in slots.iter_mut().zip(args) {
+		*slot = Some(arg.clone());
+	}
+
+	let mut frame = Frame {
+		ctx: ctx.child(env.clone()),
+		slots,
+		locals: &compiled.locals,
+		owns_env: false,
+	};
+
+	run_chunk(&mut frame, &compiled.chunk)
+}
+
+pub fn run_compiled(ctx: &EvalContext, compiled: &CompiledBody) -> EvalResult<Value> {
+	ctx.begin_run();
+	let mut frame = Frame {
+		ctx: ctx.clone(),
+		slots: Vec::new(),
+		locals: &compiled.locals,
+		owns_env: true,
+	};
+
+	run_chunk(&mut frame, &compiled.chunk)
+}
@@ -0,0 +1,491 @@
+use super::{
+	eval_multi, identical, stdlib, BigInt, Capability, Decimal, Environment, EvalContext, EvalResult,
+	Exception, Function, Shared, Type, Value,
+};
+use qry_lang::{parse, FunctionHeader, ParameterDef, SourceLocation, SyntaxNode, SyntaxTree};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+// a workspace file is this header, the length of the manifest as a little
+// endian u64, the manifest as TOML, then the blobs it refers to
+const WORKSPACE_HEADER: &[u8] = b"qry-workspace\n";
+const WORKSPACE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SavedValue {
+	Null,
+	Int {
+		value: i64,
+	},
+	Float {
+		value: f64,
+	},
+	BigInt {
+		value: String,
+	},
+	Decimal {
+		value: String,
+	},
+	Bool {
+		value: bool,
+	},
+	String {
+		value: String,
+	},
+	List {
+		items: Vec<SavedValue>,
+	},
+	Syntax {
+		source: String,
+	},
+	Function {
+		source: String,
+	},
+	// index into the workspace's connections
+	Connection {
+		connection: usize,
+	},
+	// the calls that build the pipeline, evaluated against the connection
+	Pipeline {
+		connection: usize,
+		steps: Vec<String>,
+	},
+	// an Arrow IPC stream in the blob section
+	DataFrame {
+		offset: usize,
+		length: usize,
+		column_types: Vec<String>,
+	},
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedConnection {
+	pub driver: String,
+	pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedBinding {
+	name: String,
+	value: SavedValue,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkspaceManifest {
+	version: u32,
+	#[serde(default)]
+	connections: Vec<SavedConnection>,
+	#[serde(default)]
+	bindings: Vec<SavedBinding>,
+}
+
+fn workspace_error(ctx: &EvalContext, path: &Path, message: impl std::fmt::Display) -> Exception {
+	ctx.exception(
+		&SourceLocation::Unknown,
+		format!("workspace {}: {}", path.display(), message),
+	)
+}
+
+pub struct WorkspaceWriter<'a> {
+	pub ctx: &'a EvalContext,
+	pub connections: Vec<SavedConnection>,
+	pub blobs: Vec<u8>,
+	// what's being saved, which is all a restored function gets to see
+	env: &'a Environment,
+	// connections already saved, so values sharing one still share it once
+	// they're restored
+	connection_ids: Vec<usize>,
+}
+
+impl WorkspaceWriter<'_> {
+	pub fn add_blob(&mut self, blob: &[u8]) -> (usize, usize) {
+		let offset = self.blobs.len();
+		self.blobs.extend_from_slice(blob);
+		(offset, blob.len())
+	}
+
+	// id is anything unique to the connection, such as its address
+	pub fn add_connection(&mut self, id: usize, connection: SavedConnection) -> usize {
+		match self.connection_ids.iter().position(|&c| c == id) {
+			Some(index) => index,
+			None => {
+				self.connection_ids.push(id);
+				self.connections.push(connection);
+				self.connections.len() - 1
+			}
+		}
+	}
+
+	// a function is restored by evaluating its declaration next to the other
+	// bindings, so it can only be saved if whatever it refers to from what it
+	// captured is still bound to the same value. A closure returned from a call
+	// also sees the call's arguments and locals
+	fn captures_saved(&self, function: &Function) -> bool {
+		let mut names = Vec::new();
+		for node in &function.body {
+			collect_referenced_names(node, &mut names);
+		}
+		names
+			.into_iter()
+			.filter(|name| !function.signature.params.iter().any(|p| p.name == *name))
+			.all(|name| match (function.env.get(name), self.env.get(name)) {
+				(Some(captured), Some(saved)) => identical(&saved, &captured),
+				(Some(_), None) => false,
+				(None, _) => true,
+			})
+	}
+
+	// None for values that can't be saved, such as builtins and libraries, and
+	// functions that captured values which aren't saved
+	pub fn save(&mut self, value: &Value) -> EvalResult<Option<SavedValue>> {
+		let saved = match value {
+			Value::Null(_) => SavedValue::Null,
+			Value::Int(value) => SavedValue::Int { value: *value },
+			Value::Float(value) => SavedValue::Float { value: *value },
+			Value::BigInt(value) => SavedValue::BigInt {
+				value: value.to_string(),
+			},
+			Value::Decimal(value) => SavedValue::Decimal {
+				value: value.to_string(),
+			},
+			Value::Bool(value) => SavedValue::Bool { value: *value },
+			Value::String(value) => SavedValue::String {
+				value: value.to_string(),
+			},
+			Value::List(items) => {
+				let mut saved_items = Vec::new();
+				for item in items {
+					match self.save(item)? {
+						Some(item) => saved_items.push(item),
+						None => return Ok(None),
+					}
+				}
+				SavedValue::List { items: saved_items }
+			}
+			Value::Syntax(node) => SavedValue::Syntax {
+				source: node.to_string(),
+			},
+			Value::Function(function) if self.captures_saved(function) => SavedValue::Function {
+				source: function_source(function),
+			},
+			Value::Native(_) => return stdlib::data::save_native(self, value),
+			_ => return Ok(None),
+		};
+		Ok(Some(saved))
+	}
+}
+
+pub struct WorkspaceReader<'a> {
+	pub ctx: &'a EvalContext,
+	pub connections: Vec<Value>,
+	pub blobs: &'a [u8],
+}
+
+impl WorkspaceReader<'_> {
+	pub fn blob(&self, offset: usize, length: usize) -> EvalResult<&[u8]> {
+		let end = offset.checked_add(length);
+		end
+			.and_then(|end| self.blobs.get(offset..end))
+			.ok_or_else(|| {
+				self
+					.ctx
+					.exception(&SourceLocation::Unknown, "workspace blob out of range")
+			})
+	}
+
+	pub fn connection(&self, index: usize) -> EvalResult<Value> {
+		self.connections.get(index).cloned().ok_or_else(|| {
+			self.ctx.exception(
+				&SourceLocation::Unknown,
+				"workspace connection out of range",
+			)
+		})
+	}
+
+	fn eval_source(&self, source: &str) -> EvalResult<Value> {
+		let syntax = parse(source, "<workspace>").map_err(|err| {
+			self
+				.ctx
+				.exception(&SourceLocation::Unknown, format!("parser {}", err))
+		})?;
+		eval_multi(self.ctx, &syntax)
+	}
+
+	pub fn restore(&self, saved: &SavedValue) -> EvalResult<Value> {
+		let ctx = self.ctx;
+		let parse_error = |message: String| ctx.exception(&SourceLocation::Unknown, message);
+		Ok(match saved {
+			SavedValue::Null => Value::Null(()),
+			SavedValue::Int { value } => Value::Int(*value),
+			SavedValue::Float { value } => Value::Float(*value),
+			SavedValue::BigInt { value } => Value::BigInt(value.parse::<BigInt>().map_err(parse_error)?),
+			SavedValue::Decimal { value } => {
+				Value::Decimal(value.parse::<Decimal>().map_err(parse_error)?)
+			}
+			SavedValue::Bool { value } => Value::Bool(*value),
+			SavedValue::String { value } => Value::String(value.as_str().into()),
+			SavedValue::List { items } => Value::List(
+				items
+					.iter()
+					.map(|item| self.restore(item))
+					.collect::<EvalResult<_>>()?,
+			),
+			SavedValue::Syntax { source } => {
+				let mut syntax =
+					parse(source, "<workspace>").map_err(|err| parse_error(format!("parser {}", err)))?;
+				match syntax.len() {
+					1 => Value::Syntax(Box::new(syntax.remove(0))),
+					_ => return Err(parse_error("expected a single expression".into())),
+				}
+			}
+			SavedValue::Function { source } => self.eval_source(source)?,
+			SavedValue::Connection { connection } => self.connection(*connection)?,
+			_ => stdlib::data::restore_native(self, saved)?,
+		})
+	}
+}
+
+// every identifier in a function body, including names it assigns itself, so
+// it can only overestimate what the function reads from its environment
+fn collect_referenced_names<'a>(node: &'a SyntaxNode, names: &mut Vec<&'a str>) {
+	match &node.syntax {
+		SyntaxTree::Ident(name) => names.push(name),
+		SyntaxTree::BinaryOp { lhs, rhs, .. } => {
+			collect_referenced_names(lhs, names);
+			collect_referenced_names(rhs, names);
+		}
+		SyntaxTree::Function {
+			header,
+			params,
+			return_type,
+			body,
+			..
+		} => {
+			if let FunctionHeader::MethodImpl { impl_for } = header {
+				collect_referenced_names(impl_for, names);
+			}
+			for param in params {
+				collect_referenced_names(&param.param_type, names);
+			}
+			collect_referenced_names(return_type, names);
+			for node in body {
+				collect_referenced_names(node, names);
+			}
+		}
+		SyntaxTree::TypeAlias { target, .. }
+		| SyntaxTree::UnaryOp { target, .. }
+		| SyntaxTree::Interpolate(target)
+		| SyntaxTree::Export(target) => collect_referenced_names(target, names),
+		SyntaxTree::Interface { methods, .. } => {
+			for method in methods {
+				collect_referenced_names(method, names);
+			}
+		}
+		SyntaxTree::Call {
+			target,
+			positional_args,
+			named_args,
+		} => {
+			collect_referenced_names(target, names);
+			for arg in positional_args {
+				collect_referenced_names(arg, names);
+			}
+			for (_, arg) in named_args {
+				collect_referenced_names(arg, names);
+			}
+		}
+		SyntaxTree::Switch { target, cases } => {
+			collect_referenced_names(target, names);
+			for case in cases {
+				collect_referenced_names(&case.expr, names);
+				collect_referenced_names(&case.returns, names);
+			}
+		}
+		SyntaxTree::Index { target, keys } => {
+			collect_referenced_names(target, names);
+			for key in keys {
+				collect_referenced_names(key, names);
+			}
+		}
+		SyntaxTree::GenericInstantiation { target, type_args } => {
+			collect_referenced_names(target, names);
+			for arg in type_args {
+				collect_referenced_names(arg, names);
+			}
+		}
+		SyntaxTree::Null
+		| SyntaxTree::Int(_)
+		| SyntaxTree::Float(_)
+		| SyntaxTree::Bool(_)
+		| SyntaxTree::String(_)
+		| SyntaxTree::Use { .. } => (),
+	}
+}
+
+fn type_syntax(t: &Type) -> SyntaxNode {
+	let name = t.to_string();
+	match parse(&name, "<workspace>") {
+		Ok(mut syntax) if syntax.len() == 1 => syntax.remove(0),
+		_ => SyntaxNode {
+			syntax: SyntaxTree::Ident(name),
+			location: SourceLocation::Unknown,
+		},
+	}
+}
+
+// functions are saved as their declaration, with parameter types written out
+// as they were resolved
+fn function_source(function: &Function) -> String {
+	let header = match function.name.starts_with('<') {
+		true => FunctionHeader::Function(None),
+		false => FunctionHeader::Function(Some(function.name.clone())),
+	};
+	let params = function
+		.signature
+		.params
+		.iter()
+		.map(|p| ParameterDef {
+			name: p.name.clone(),
+			param_type: type_syntax(&p.param_type),
+		})
+		.collect();
+	let decl = SyntaxNode {
+		syntax: SyntaxTree::Function {
+			header,
+			params,
+			return_type: Box::new(type_syntax(&function.signature.return_type)),
+			body: function.body.clone(),
+			doc: function.signature.doc.clone(),
+		},
+		location: function.location.clone(),
+	};
+	decl.to_string()
+}
+
+// plain values first, then pipelines which can interpolate them, then
+// functions. A function captures the environment it was defined in, so the
+// more of the other functions it can see the later it was defined
+fn restore_order(value: &Value, bindings: &[(String, Value)]) -> (u8, usize) {
+	let function = match value {
+		Value::Native(_) => return (1, 0),
+		Value::Function(function) => function,
+		_ => return (0, 0),
+	};
+	let captured = function.env.bindings();
+	let seen = bindings
+		.iter()
+		.filter(|(_, other)| match other {
+			Value::Function(other) => captured
+				.iter()
+				.any(|(_, v)| matches!(v, Value::Function(f) if Shared::ptr_eq(f, other))),
+			_ => false,
+		})
+		.count();
+	(2, seen)
+}
+
+// saves every binding in env that can be saved, returning how many were
+pub fn save_workspace(ctx: &EvalContext, env: &Environment, path: &Path) -> EvalResult<usize> {
+	ctx.require(Capability::Filesystem, &SourceLocation::Unknown)?;
+
+	let bindings = env.bindings();
+	let mut bindings = bindings
+		.iter()
+		.map(|(name, value)| (restore_order(value, &bindings), name, value))
+		.collect::<Vec<_>>();
+	bindings.sort_by(|(a_order, a_name, _), (b_order, b_name, _)| {
+		a_order.cmp(b_order).then_with(|| a_name.cmp(b_name))
+	});
+
+	let mut writer = WorkspaceWriter {
+		ctx,
+		connections: Vec::new(),
+		blobs: Vec::new(),
+		env,
+		connection_ids: Vec::new(),
+	};
+	let mut saved = Vec::new();
+	for (_, name, value) in bindings {
+		if let Some(value) = writer.save(value)? {
+			saved.push(SavedBinding {
+				name: name.clone(),
+				value,
+			});
+		}
+	}
+
+	let count = saved.len();
+	let manifest = WorkspaceManifest {
+		version: WORKSPACE_VERSION,
+		connections: writer.connections,
+		bindings: saved,
+	};
+	let manifest = toml::to_string(&manifest).map_err(|err| workspace_error(ctx, path, err))?;
+
+	let mut contents = WORKSPACE_HEADER.to_vec();
+	contents.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+	contents.extend_from_slice(manifest.as_bytes());
+	contents.extend_from_slice(&writer.blobs);
+	fs::write(path, contents).map_err(|err| workspace_error(ctx, path, err))?;
+	Ok(count)
+}
+
+// restores the bindings saved in a workspace into ctx's environment, returning
+// their names
+pub fn load_workspace(ctx: &EvalContext, path: &Path) -> EvalResult<Vec<String>> {
+	ctx.require(Capability::Filesystem, &SourceLocation::Unknown)?;
+
+	let contents = fs::read(path).map_err(|err| workspace_error(ctx, path, err))?;
+	let invalid = || workspace_error(ctx, path, "not a workspace file");
+	if !contents.starts_with(WORKSPACE_HEADER) {
+		return Err(invalid());
+	}
+	let rest = &contents[WORKSPACE_HEADER.len()..];
+	let manifest_len = rest
+		.get(..8)
+		.and_then(|len| len.try_into().ok())
+		.map(|len| u64::from_le_bytes(len) as usize)
+		.ok_or_else(invalid)?;
+	let manifest_end = manifest_len.checked_add(8).ok_or_else(invalid)?;
+	let manifest = rest
+		.get(8..manifest_end)
+		.and_then(|manifest| std::str::from_utf8(manifest).ok())
+		.ok_or_else(invalid)?;
+	let manifest: WorkspaceManifest =
+		toml::from_str(manifest).map_err(|err| workspace_error(ctx, path, err))?;
+	if manifest.version != WORKSPACE_VERSION {
+		return Err(workspace_error(
+			ctx,
+			path,
+			format!("unsupported version {}", manifest.version),
+		));
+	}
+
+	let connections = manifest
+		.connections
+		.iter()
+		.map(|c| stdlib::data::Connection::open(ctx, &c.driver, &c.target))
+		.collect::<EvalResult<Vec<_>>>()?;
+	let reader = WorkspaceReader {
+		ctx,
+		connections,
+		blobs: &rest[manifest_end..],
+	};
+
+	let mut names = Vec::new();
+	for binding in manifest.bindings {
+		let value = reader.restore(&binding.value).map_err(|ex| {
+			workspace_error(
+				ctx,
+				path,
+				format!("couldn't restore {}: {}", binding.name, ex.message),
+			)
+		})?;
+		ctx.env.update(&binding.name, value);
+		names.push(binding.name);
+	}
+	Ok(names)
+}
@@ -0,0 +1,76 @@
+use qry_runtime::Value;
+
+pub mod helpers;
+
+#[test]
+fn test_checker_failures() {
+	helpers::check_expect_diagnostics(&[
+		("x", "unknown identifier: x"),
+		("1 + \"a\"", "no impl of method 'add' for (Int, String)"),
+		("-true", "no impl of method 'minus' for (Bool)"),
+		("1()", "Int is not callable"),
+		("typeof(1, 2)", "arg count mismatch"),
+		(
+			"fn f(a: Int) -> Int { a }\nf(\"a\")",
+			"expected Int, got String",
+		),
+		("fn f(a: Int) -> String { a }", "expected String, got Int"),
+		("fn f(a: 1) -> Int { a }", "expected a type, got Int"),
+		("use core::{blah}", "not found: blah"),
+		("use nonexistentmodule", "not found: nonexistentmodule"),
+		(
+			"use nonexistentmodule::{x}",
+			"unknown library: nonexistentmodule",
+		),
+		("core::blah", "not found: blah"),
+		("1::blah", "access operator requires a library"),
+		("1 |> 2", "must be a call"),
+		("1 <- 2", "assignment requires an identifier"),
+		(
+			"x <- 1\nx + \"a\"",
+			"no impl of method 'add' for (Int, String)",
+		),
+		("fn f() -> Int { y }", "unknown identifier: y"),
+		("core::Int<Int>", "unsupported type for generics"),
+		(
+			"impl ops::sub(a: Any, b: Bool) -> Int { 1 }
+			impl ops::sub(a: Bool, b: Any) -> Int { 2 }
+			true - true",
+			"ambiguous call to method 'sub'",
+		),
+		("data::Vector<String>", "unsupported vector type"),
+	]);
+}
+
+#[test]
+fn test_checker_switch_scopes() {
+	// only one case runs, so a later case's assignment doesn't decide the type
+	helpers::eval_expect_values(&[
+		(
+			"x <- 1
+			switch 1 {
+				1 => x <- 2
+				2 => x <- \"a\"
+			}
+			x + 1",
+			Value::Int(3),
+		),
+		(
+			"switch true {
+				true => y <- 1
+				false => 2
+			}
+			y",
+			Value::Int(1),
+		),
+	]);
+	helpers::check_expect_diagnostics(&[(
+		"x <- \"a\"
+		switch 1 {
+			1 => x <- \"b\"
+			2 => x <- \"c\"
+		}
+		x + 1",
+		"no impl of method 'add' for (String, Int)",
+	)]);
+}
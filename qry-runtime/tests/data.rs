@@ -1,4 +1,9 @@
-use qry_runtime::Value;
+use qry_lang::parse;
+use qry_runtime::{
+	eval_multi, BufferSink, Capability, Decimal, EvalContext, EvalContextBuilder, EvalEvent,
+	ExceptionKind, Observer, Output, Profiler, Shared, Value,
+};
+use std::sync::Mutex;
 
 pub mod helpers;
 
@@ -71,6 +76,17 @@ fn test_data_sqlite() {
 			&with_table_bootstrap(r#"test_table |> collect() |> num_cols()"#),
 			Value::Int(2),
 		),
+		(
+			&with_table_bootstrap(
+				r#"older <- true
+				step <- switch older {
+					true => filter
+					false => select
+				}
+				test_table |> step(age > 26) |> collect() |> num_rows()"#,
+			),
+			Value::Int(2),
+		),
 		(
 			&with_table_bootstrap(
 				r#"
@@ -81,7 +97,7 @@ fn test_data_sqlite() {
 					|> dimensions()
 				"#,
 			),
-			Value::List(vec![Value::Int(1), Value::Int(3)]),
+			Value::List(vec![Value::Int(1), Value::Int(3)].into()),
 		),
 		(
 			&with_table_bootstrap(
@@ -93,7 +109,7 @@ fn test_data_sqlite() {
 					|> dimensions()
 				"#,
 			),
-			Value::List(vec![Value::Int(2), Value::Int(2)]),
+			Value::List(vec![Value::Int(2), Value::Int(2)].into()),
 		),
 		(
 			&with_table_bootstrap(
@@ -105,7 +121,7 @@ fn test_data_sqlite() {
 					|> dimensions()
 				"#,
 			),
-			Value::List(vec![Value::Int(3), Value::Int(2)]),
+			Value::List(vec![Value::Int(3), Value::Int(2)].into()),
 		),
 		(
 			&with_table_bootstrap(
@@ -143,6 +159,46 @@ fn test_data_sqlite() {
 			),
 			Value::Int(26),
 		),
+		(
+			&with_table_bootstrap(
+				r#"
+				fn count_age(target: Int) -> Int {
+					test_table |> filter(age == {{target}}) |> collect() |> num_rows()
+				}
+				count_age(27)
+				"#,
+			),
+			Value::Int(2),
+		),
+	]);
+}
+
+const PRICES_BOOTSTRAP: &str = r#"
+use data::*
+conn <- connect_sqlite(":memory:")
+execute(conn, "create table prices (item text, price decimal(10, 2))")
+execute(conn, "insert into prices (item, price) values ('a', '19.99'), ('b', 0.1), ('c', null)")
+prices <- table(conn, "prices")
+"#;
+
+fn with_prices_bootstrap(query: &str) -> String {
+	format!("{}\n{}", PRICES_BOOTSTRAP, query)
+}
+
+#[test]
+fn test_data_decimals() {
+	let decimal = |s: &str| Value::Decimal(s.parse::<Decimal>().unwrap());
+	helpers::eval_expect_values(&[
+		(
+			&with_prices_bootstrap(r#"prices |> collect() |> col("price")"#),
+			Value::List(vec![decimal("19.99"), decimal("0.1"), Value::Null(())].into()),
+		),
+		(
+			&with_prices_bootstrap(
+				r#"prices |> filter(price > {{decimal("1.00")}}) |> collect() |> num_rows()"#,
+			),
+			Value::Int(1),
+		),
 	]);
 }
 
@@ -158,3 +214,238 @@ fn test_vectors() {
 		),
 	]);
 }
+
+#[test]
+fn test_dataframe_equality() {
+	helpers::eval_expect_values(&[
+		(
+			&with_table_bootstrap(
+				"a <- test_table |> collect()
+				b <- test_table |> collect()
+				list(a == b, hash(a) == hash(b), distinct(list(a, b)) == list(a))",
+			),
+			Value::List(vec![Value::Bool(true); 3].into()),
+		),
+		(
+			&with_table_bootstrap(
+				"(test_table |> collect()) != (test_table |> filter(age == 27) |> collect())",
+			),
+			Value::Bool(true),
+		),
+	]);
+}
+
+#[test]
+fn test_data_failures() {
+	helpers::eval_expect_errors(&[
+		(&with_table_bootstrap(
+			r#"test_table |> filter(missing == 1) |> render()"#,
+		),),
+		(&with_table_bootstrap(
+			r#"test_table |> filter(name == {{list(1)}}) |> render()"#,
+		),),
+		(&with_table_bootstrap(
+			r#"test_table |> filter(switch age {}) |> render()"#,
+		),),
+		(&with_table_bootstrap(
+			r#"test_table |> group_by(age + 1) |> aggregate(total_age = sum(age)) |> render()"#,
+		),),
+		(&with_table_bootstrap(
+			r#"test_table |> collect() |> col("name")"#,
+		),),
+		(&with_table_bootstrap(
+			r#"test_table |> collect() |> col("missing")"#,
+		),),
+		(&with_table_bootstrap(
+			r#"execute(conn, "create table reals (x real)")
+			execute(conn, "insert into reals (x) values (1.5)")
+			table(conn, "reals") |> collect()"#,
+		),),
+	]);
+}
+
+#[test]
+fn test_data_sandbox() {
+	let connect = |builder: EvalContextBuilder, target: &str| {
+		let src = format!("use data::*\nconnect_sqlite(\"{}\")", target);
+		eval_multi(&builder.build(), &parse(&src, "tests").unwrap())
+	};
+
+	let builder = EvalContext::builder().allow_connections(&[":memory:"]);
+	assert!(connect(builder, ":memory:").is_ok());
+
+	let builder = EvalContext::builder().allow_connections(&[":memory:"]);
+	let err = connect(builder, "other.db").unwrap_err();
+	assert_eq!(err.kind, ExceptionKind::Denied);
+	assert_eq!(err.message, "connection target not allowed: other.db");
+
+	let builder = EvalContext::builder().deny(Capability::Filesystem);
+	assert!(connect(builder, ":memory:").is_ok());
+	let builder = EvalContext::builder().deny(Capability::Filesystem);
+	assert_eq!(
+		connect(builder, "other.db").unwrap_err().message,
+		"capability denied: filesystem"
+	);
+
+	let builder = EvalContext::builder().deny(Capability::DatabaseConnect);
+	assert_eq!(
+		connect(builder, ":memory:").unwrap_err().message,
+		"capability denied: database connect"
+	);
+}
+
+#[test]
+fn test_named_connections() {
+	let ctx = EvalContext::builder()
+		.connection("scratch", ":memory:")
+		.build();
+	let eval = |src: &str| eval_multi(&ctx, &parse(src, "tests").unwrap());
+
+	let ret =
+		eval("use data::*\nconn <- connect(\"scratch\")\nexecute(conn, \"create table t (x int)\")");
+	assert_eq!(ret.unwrap(), Value::Int(0));
+	assert_eq!(
+		eval("data::connect(\"missing\")").unwrap_err().message,
+		"unknown connection: missing"
+	);
+}
+
+#[test]
+fn test_profile_queries() {
+	let profiler = Profiler::new();
+	let ctx = EvalContext::builder().profiler(profiler.clone()).build();
+	let src = with_table_bootstrap("test_table |> filter(age == 27) |> collect() |> num_rows()");
+	eval_multi(&ctx, &parse(&src, "tests").unwrap()).unwrap();
+
+	let queries = profiler.queries();
+	assert_eq!(queries.len(), 3);
+	assert!(queries
+		.iter()
+		.any(|(sql, entry)| sql.starts_with("create table test_table") && entry.calls == 1));
+	assert!(queries
+		.iter()
+		.any(|(sql, _)| sql.starts_with("select") && sql.contains("27")));
+
+	// query time is taken out of the builtin that ran it
+	let mut folded = Vec::new();
+	profiler.write_folded(&mut folded).unwrap();
+	let folded = String::from_utf8(folded).unwrap();
+	assert!(folded
+		.lines()
+		.any(|l| l.starts_with("data::execute;[sql] ")));
+	assert!(folded
+		.lines()
+		.any(|l| l.starts_with("data::collect;[sql] ")));
+}
+
+struct SqlRecorder {
+	events: Shared<Mutex<Vec<(String, Option<usize>)>>>,
+}
+
+impl Observer for SqlRecorder {
+	fn event(&self, _: &EvalContext, event: &EvalEvent) {
+		let event = match event {
+			EvalEvent::SqlRendered { sql } => (sql.to_string(), None),
+			EvalEvent::SqlExecuted { sql, rows, .. } => (sql.to_string(), Some(*rows)),
+			_ => return,
+		};
+		self.events.lock().unwrap().push(event);
+	}
+}
+
+#[test]
+fn test_observe_queries() {
+	let events = Shared::new(Mutex::new(Vec::new()));
+	let ctx = EvalContext::builder()
+		.observer(SqlRecorder {
+			events: events.clone(),
+		})
+		.build();
+	let src = with_table_bootstrap("test_table |> filter(age == 27) |> collect() |> num_rows()");
+	eval_multi(&ctx, &parse(&src, "tests").unwrap()).unwrap();
+
+	let events = events.lock().unwrap();
+	assert_eq!(events.len(), 4);
+	assert!(events[0].0.starts_with("create table test_table"));
+	assert!(events[1].0.starts_with("insert into test_table"));
+	assert_eq!(events[1].1, Some(4));
+
+	// the pipeline is rendered, then the same sql executed
+	let (rendered, executed) = (&events[2], &events[3]);
+	assert!(rendered.0.starts_with("select") && rendered.0.contains("27"));
+	assert_eq!(rendered.1, None);
+	assert_eq!(executed, &(rendered.0.clone(), Some(2)));
+}
+
+#[test]
+fn test_display_dataframe() {
+	let buffer = BufferSink::new();
+	let ctx = EvalContext::builder().output(buffer.clone()).build();
+	let src = with_table_bootstrap(
+		"df <- test_table |> select(name, age) |> collect()
+print(df)",
+	);
+	eval_multi(&ctx, &parse(&src, "tests").unwrap()).unwrap();
+
+	let outputs = buffer.outputs();
+	assert_eq!(outputs.len(), 1);
+	match &outputs[0] {
+		Output::Table {
+			columns,
+			rows,
+			text,
+		} => {
+			assert_eq!(columns, &vec!["name".to_string(), "age".to_string()]);
+			assert_eq!(
+				rows,
+				&vec![
+					vec![Some("ruan".to_string()), Some("26".to_string())],
+					vec![Some("ruanlater".to_string()), Some("27".to_string())],
+					vec![Some("thirdperson".to_string()), Some("27".to_string())],
+					vec![Some("ancient one".to_string()), None],
+				]
+			);
+			assert!(text.contains("thirdperson") && text.contains("<NULL>"));
+		}
+		other => panic!("expected a table, got {:?}", other),
+	}
+}
+
+#[test]
+fn test_workspace_data() {
+	let dir = std::env::temp_dir();
+	let db = dir.join(format!("qry-test-workspace-{}.db", std::process::id()));
+	let path = dir.join(format!("qry-test-workspace-{}.qws", std::process::id()));
+	let src = format!(
+		r#"use data::*
+conn <- connect_sqlite("{}")
+execute(conn, "create table people (name varchar(255), age integer)")
+execute(conn, "insert into people (name, age) values ('ruan', 26), ('ruanlater', 27), ('ancient one', null)")
+min_age <- 27
+adults <- table(conn, "people") |> filter(age >= {{{{min_age}}}}) |> select(name)
+df <- table(conn, "people") |> collect()
+save_workspace("{}")"#,
+		db.display(),
+		path.display()
+	);
+	let ctx = EvalContext::new_with_stdlib();
+	assert_eq!(
+		eval_multi(&ctx, &parse(&src, "tests").unwrap()).unwrap(),
+		Value::Int(4)
+	);
+
+	let restored = EvalContext::new_with_stdlib();
+	let src = format!(
+		r#"load_workspace("{}")
+use data::*
+list(num_rows(df), adults |> collect() |> num_rows(), conn |> table("people") |> collect() |> num_rows())"#,
+		path.display()
+	);
+	let ret = eval_multi(&restored, &parse(&src, "tests").unwrap());
+	std::fs::remove_file(&path).unwrap();
+	std::fs::remove_file(&db).unwrap();
+	assert_eq!(
+		ret.unwrap(),
+		Value::List(vec![Value::Int(3), Value::Int(1), Value::Int(3)].into())
+	);
+}
@@ -0,0 +1,182 @@
+use qry_lang::{parse, SourceLocation};
+use qry_runtime::{
+	compile, eval_multi, run_compiled, DebugCommand, DebugHandler, Debugger, EvalContext,
+	PauseReason, Shared, Value,
+};
+use std::sync::Mutex;
+
+const SRC: &str = "fn add(a: Int, b: Int) -> Int {
+	total <- a + b
+	total
+}
+x <- 1
+y <- add(x, 2)
+z <- y * 2";
+
+#[derive(Debug, PartialEq)]
+struct Pause {
+	line: usize,
+	depth: usize,
+	reason: PauseReason,
+	total: Option<Value>,
+}
+
+struct Recorder {
+	commands: Mutex<Vec<DebugCommand>>,
+	pauses: Shared<Mutex<Vec<Pause>>>,
+}
+
+impl DebugHandler for Recorder {
+	fn paused(
+		&self,
+		ctx: &EvalContext,
+		location: &SourceLocation,
+		reason: PauseReason,
+	) -> DebugCommand {
+		let line = match location {
+			SourceLocation::User { line, .. } => *line,
+			_ => panic!("paused outside user code: {:?}", location),
+		};
+		self.pauses.lock().unwrap().push(Pause {
+			line,
+			depth: ctx.callstack.borrow().len(),
+			reason,
+			total: ctx.env.get("total"),
+		});
+
+		let mut commands = self.commands.lock().unwrap();
+		match commands.is_empty() {
+			true => DebugCommand::Continue,
+			false => commands.remove(0),
+		}
+	}
+}
+
+fn debug(
+	commands: Vec<DebugCommand>,
+	setup: impl Fn(&Debugger),
+	compiled: bool,
+) -> Vec<(usize, usize, PauseReason)> {
+	let pauses = Shared::new(Mutex::new(Vec::new()));
+	let debugger = Debugger::new(Recorder {
+		commands: Mutex::new(commands),
+		pauses: pauses.clone(),
+	});
+	setup(&debugger);
+
+	let ctx = EvalContext::builder().debugger(debugger).build();
+	let syntax = parse(SRC, "script.qry").unwrap();
+	let result = match compiled {
+		true => run_compiled(&ctx, &compile(&syntax)),
+		false => eval_multi(&ctx, &syntax),
+	};
+	assert_eq!(result.unwrap(), Value::Int(6));
+
+	let pauses = pauses.lock().unwrap();
+	pauses.iter().map(|p| (p.line, p.depth, p.reason)).collect()
+}
+
+#[test]
+fn test_breakpoints() {
+	for &compiled in &[false, true] {
+		let pauses = debug(
+			vec![],
+			|d| d.set_breakpoints("script.qry", &[3, 7]),
+			compiled,
+		);
+		assert_eq!(
+			pauses,
+			vec![
+				(3, 1, PauseReason::Breakpoint),
+				(7, 0, PauseReason::Breakpoint)
+			]
+		);
+
+		let pauses = debug(
+			vec![],
+			|d| d.set_breakpoints("other.qry", &[3, 7]),
+			compiled,
+		);
+		assert!(pauses.is_empty());
+	}
+}
+
+#[test]
+fn test_stepping() {
+	use DebugCommand::*;
+
+	for &compiled in &[false, true] {
+		let pauses = debug(
+			vec![StepOver, StepOver, StepOver],
+			|d| d.stop_on_entry(),
+			compiled,
+		);
+		assert_eq!(
+			pauses,
+			vec![
+				(1, 0, PauseReason::Entry),
+				(5, 0, PauseReason::Step),
+				(6, 0, PauseReason::Step),
+				(7, 0, PauseReason::Step),
+			]
+		);
+
+		let pauses = debug(
+			vec![StepOver, StepOver, StepIn, StepOver, StepOut],
+			|d| d.stop_on_entry(),
+			compiled,
+		);
+		assert_eq!(
+			pauses,
+			vec![
+				(1, 0, PauseReason::Entry),
+				(5, 0, PauseReason::Step),
+				(6, 0, PauseReason::Step),
+				(2, 1, PauseReason::Step),
+				(3, 1, PauseReason::Step),
+				(7, 0, PauseReason::Step),
+			]
+		);
+	}
+}
+
+#[test]
+fn test_inspect_environment() {
+	let pauses = Shared::new(Mutex::new(Vec::new()));
+	let debugger = Debugger::new(Recorder {
+		commands: Mutex::new(vec![]),
+		pauses: pauses.clone(),
+	});
+	debugger.set_breakpoints("script.qry", &[3]);
+
+	let ctx = EvalContext::builder().debugger(debugger).build();
+	eval_multi(&ctx, &parse(SRC, "script.qry").unwrap()).unwrap();
+
+	assert_eq!(
+		*pauses.lock().unwrap(),
+		vec![Pause {
+			line: 3,
+			depth: 1,
+			reason: PauseReason::Breakpoint,
+			total: Some(Value::Int(3)),
+		}]
+	);
+}
+
+#[test]
+fn test_pause_handle() {
+	let pauses = Shared::new(Mutex::new(Vec::new()));
+	let debugger = Debugger::new(Recorder {
+		commands: Mutex::new(vec![]),
+		pauses: pauses.clone(),
+	});
+	debugger.pause_handle().pause();
+
+	let ctx = EvalContext::builder()
+		.debugger(Shared::clone(&debugger))
+		.build();
+	eval_multi(&ctx, &parse(SRC, "script.qry").unwrap()).unwrap();
+
+	assert_eq!(pauses.lock().unwrap()[0].reason, PauseReason::Pause);
+	assert_eq!(pauses.lock().unwrap().len(), 1);
+}
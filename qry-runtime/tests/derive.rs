@@ -0,0 +1,78 @@
+use qry_lang::parse;
+use qry_runtime::{eval_multi, qry_methods, Environment, EvalContext, NativeType, Value};
+
+#[derive(NativeType)]
+#[qry(name = "Counter")]
+struct Tally {
+	start: i64,
+}
+
+#[qry_methods]
+impl Tally {
+	#[qry]
+	fn value(&self) -> i64 {
+		self.start
+	}
+
+	#[qry(name = "plus")]
+	fn offset(&self, n: i64) -> i64 {
+		self.start + n
+	}
+
+	#[qry(name = "add")]
+	fn add_int(&self, n: i64) -> i64 {
+		self.start + n * 10
+	}
+
+	#[qry(name = "to_string")]
+	fn describe(&self) -> String {
+		format!("Counter({})", self.start)
+	}
+
+	#[allow(dead_code)]
+	fn not_exposed(&self) -> i64 {
+		0
+	}
+}
+
+#[test]
+fn test_derive() {
+	assert_eq!(Tally::name(), "Counter");
+
+	let ctx = EvalContext::builder()
+		.with_library(|methods| {
+			let env = Environment::new("tally");
+			Tally::define_qry_type(&env, methods);
+			env.update("start", Value::new_native(Tally { start: 5 }));
+			env.update("other", Value::new_native(Tally { start: 5 }));
+			env
+		})
+		.build();
+
+	let cases = &[
+		("value(start) + plus(start, 1)", Value::Int(11)),
+		("start + 2", Value::Int(25)),
+		("to_string(start)", Value::String("Counter(5)".into())),
+		("typeof(start) == Counter", Value::Bool(true)),
+		// natives without an impl of equal are only equal to themselves
+		(
+			"list(start == start, start == other, start != other)",
+			Value::List(vec![Value::Bool(true), Value::Bool(false), Value::Bool(true)].into()),
+		),
+		(
+			"distinct(list(start, start, other)) == list(start, other)",
+			Value::Bool(true),
+		),
+	];
+	for (src, expectation) in cases {
+		let src = format!("use tally::*\n{}", src);
+		let ret = eval_multi(&ctx, &parse(&src, "tests").unwrap());
+		assert_eq!(ret.unwrap(), *expectation, "src: {}", src);
+	}
+
+	let tally = ctx.library_env.get("tally").unwrap();
+	match tally {
+		Value::Library(env) => assert!(env.get("not_exposed").is_none()),
+		other => panic!("expected a library, got {:?}", other),
+	}
+}
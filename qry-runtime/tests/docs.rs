@@ -0,0 +1,144 @@
+use qry_lang::parse;
+use qry_runtime::{
+	compile, doc_entry, eval_multi, library_docs, markdown_reference, run_compiled, BufferSink,
+	Callable, Environment, EvalContext, Signature, Type, Value,
+};
+
+const DOCUMENTED: &str = r#"/// Halves x.
+///
+/// Rounds down.
+fn half(x: Int) -> Int { x / 2 }
+/// Doubles x.
+twice <- fn(x: Int) -> Int { x * 2 }
+// not a doc comment
+fn plain(x: Int) -> Int { x }
+"#;
+
+#[test]
+fn test_fn_docs() {
+	let syntax = parse(DOCUMENTED, "tests").unwrap();
+	let doc = |ctx: &EvalContext, name: &str| match ctx.env.get(name) {
+		Some(Value::Function(function)) => function.doc(),
+		other => panic!("expected a function, got {:?}", other),
+	};
+
+	let ctx = EvalContext::new_with_stdlib();
+	eval_multi(&ctx, &syntax).unwrap();
+	let compiled = EvalContext::new_with_stdlib();
+	run_compiled(&compiled, &compile(&syntax)).unwrap();
+	for ctx in &[ctx, compiled] {
+		assert_eq!(doc(ctx, "half"), Some("Halves x.\n\nRounds down.".into()));
+		assert_eq!(doc(ctx, "twice"), Some("Doubles x.".into()));
+		assert_eq!(doc(ctx, "plain"), None);
+	}
+}
+
+#[test]
+fn test_help() {
+	let buffer = BufferSink::new();
+	let ctx = EvalContext::builder().output(buffer.clone()).build();
+	let src = format!("{}help(half)\nhelp(list)\nhelp(ops::minus)", DOCUMENTED);
+	eval_multi(&ctx, &parse(&src, "tests").unwrap()).unwrap();
+	assert_eq!(
+		buffer.text(),
+		"function half
+  half(x: Int) -> Int
+
+  Halves x.
+
+  Rounds down.
+builtin core::list
+  core::list(...Any) -> List
+
+  A list of the values given.
+method minus
+  minus(a: BigInt) -> BigInt
+  minus(a: Decimal) -> Decimal
+  minus(a: Float) -> Float
+  minus(a: Int) -> Int
+
+  Implements `-a`.
+"
+	);
+
+	buffer.clear();
+	eval_multi(&ctx, &parse("help()", "tests").unwrap()).unwrap();
+	assert!(buffer.text().starts_with("libraries: core, "));
+}
+
+#[test]
+fn test_stdlib_documented() {
+	let ctx = EvalContext::new_with_stdlib();
+	for (name, library) in ctx.library_env.bindings() {
+		let env = match library {
+			Value::Library(env) => env,
+			_ => continue,
+		};
+		for entry in library_docs(&env) {
+			if let "builtin" | "method" = entry.kind {
+				assert!(entry.doc.is_some(), "{}::{} has no doc", name, entry.name);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_markdown_reference() {
+	let env = Environment::new("host");
+	env.define_builtin(
+		"shout",
+		Signature::returning(&Type::String)
+			.param("s", &Type::String)
+			.with_doc("The string in capitals."),
+		|ctx, args, _| Ok(Value::String(args[0].as_string(ctx)?.to_uppercase().into())),
+	);
+	let exclaim = env.define_method("exclaim", &["val"], Some(Type::String), None);
+	exclaim.set_doc("The value, excitedly.");
+	exclaim.register_builtin(
+		Signature::returning(&Type::String)
+			.param("val", &Type::Int)
+			.with_doc("Ints get one `!` per unit."),
+		|ctx, args, _| {
+			Ok(Value::String(
+				"!".repeat(args[0].as_int(ctx)? as usize).into(),
+			))
+		},
+	);
+	env.update("limit", Value::Int(3));
+
+	assert_eq!(
+		doc_entry("shout", &env.get("shout").unwrap()).signatures,
+		vec![("shout(s: String) -> String".to_string(), None)]
+	);
+	assert_eq!(
+		markdown_reference("host", &library_docs(&env)),
+		"# host
+
+## `exclaim`
+
+*method*
+
+```
+exclaim(val: Int) -> String
+```
+
+The value, excitedly.
+
+- `exclaim(val: Int) -> String`: Ints get one `!` per unit.
+
+## `limit`
+
+*value*
+
+## `shout`
+
+*builtin*
+
+```
+shout(s: String) -> String
+```
+
+The string in capitals.
+"
+	);
+}
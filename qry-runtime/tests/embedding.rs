@@ -0,0 +1,92 @@
+use qry_lang::{parse, BinaryOperator};
+use qry_runtime::{
+	check, eval_multi, Callable, Environment, EvalContext, EvalResult, NativeType, Shared, Value,
+};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+fn eval_in(ctx: &EvalContext, src: &str) -> EvalResult<Value> {
+	eval_multi(ctx, &parse(src, "tests").unwrap())
+}
+
+struct Pool {
+	size: i64,
+}
+
+impl NativeType for Pool {
+	fn name() -> &'static str {
+		"Pool"
+	}
+}
+
+#[test]
+fn test_closure_builtins() {
+	let calls = Shared::new(AtomicI64::new(0));
+	let counter = calls.clone();
+
+	let ctx = EvalContext::builder()
+		.with_library(move |_| {
+			let env = Environment::new("host");
+			env.define_fn("bump", move || counter.fetch_add(1, Ordering::SeqCst) + 1);
+			env
+		})
+		.build();
+
+	assert_eq!(
+		eval_in(&ctx, "host::bump()\nhost::bump()").unwrap(),
+		Value::Int(2)
+	);
+	assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_typed_builtins() {
+	let ctx = EvalContext::new_with_stdlib();
+	ctx.env.define_fn("add", |a: i64, b: i64| a + b);
+	ctx.env.define_fn("shout", |s: String| format!("{}!", s));
+	ctx
+		.env
+		.define_fn("or_zero", |x: Option<i64>| x.unwrap_or(0));
+	ctx.env.define_fn("halve", |x: i64| match x % 2 {
+		0 => Ok(x / 2),
+		_ => Err(format!("{} is odd", x)),
+	});
+	ctx
+		.env
+		.define_fn("pool_size", |pool: Shared<Pool>| pool.size);
+	ctx.env.update("pool", Value::new_native(Pool { size: 8 }));
+
+	let cases = &[
+		("add(1, 2)", Value::Int(3)),
+		("shout(\"hi\")", Value::String("hi!".into())),
+		("or_zero(null) + or_zero(4)", Value::Int(4)),
+		("halve(10)", Value::Int(5)),
+		("pool_size(pool)", Value::Int(8)),
+	];
+	for (src, expectation) in cases {
+		assert_eq!(eval_in(&ctx, src).unwrap(), *expectation, "src: {}", src);
+	}
+
+	assert_eq!(eval_in(&ctx, "halve(3)").unwrap_err().message, "3 is odd");
+
+	match ctx.env.get("add") {
+		Some(Value::Builtin(builtin)) => assert_eq!(
+			builtin.signature().to_string(),
+			"(arg0: Int, arg1: Int) -> Int"
+		),
+		other => panic!("expected a builtin, got {:?}", other),
+	}
+
+	// derived signatures are visible to the checker
+	let diagnostics = check(&ctx, &parse("add(\"x\", 1)", "tests").unwrap());
+	assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+}
+
+#[test]
+fn test_typed_method_impls() {
+	let ctx = EvalContext::new_with_stdlib();
+	let add = &ctx.methods.binops[&BinaryOperator::Add];
+	add.register_fn(|pool: Shared<Pool>, extra: i64| pool.size + extra);
+	ctx.env.update("pool", Value::new_native(Pool { size: 8 }));
+
+	assert_eq!(eval_in(&ctx, "pool + 2").unwrap(), Value::Int(10));
+}
@@ -0,0 +1,117 @@
+use qry_runtime::{hash_value, EvalContext, Value};
+
+pub mod helpers;
+
+#[test]
+fn test_equality() {
+	helpers::eval_expect_values(&[
+		(
+			"list(1, list(\"a\", null)) == list(1, list(\"a\", null))",
+			Value::Bool(true),
+		),
+		("list(1, 2) == list(1, 2, 3)", Value::Bool(false)),
+		("list(1, 2) != list(2, 1)", Value::Bool(true)),
+		// items compare with ==, so promotion applies inside lists
+		("list(1, bigint(\"2\")) == list(1.0, 2)", Value::Bool(true)),
+		// items that can't be compared make the lists unequal
+		("list(1, \"a\") == list(\"a\", 1)", Value::Bool(false)),
+		("null == null", Value::Bool(true)),
+		("typeof == typeof", Value::Bool(true)),
+		("typeof != print", Value::Bool(true)),
+		("typeof != fn(x: Int) -> Int { x }", Value::Bool(true)),
+		(
+			"fn f(x: Int) -> Int { x }
+			g <- f
+			list(f == g, f == fn(x: Int) -> Int { x })",
+			Value::List(vec![Value::Bool(true), Value::Bool(false)].into()),
+		),
+	]);
+}
+
+#[test]
+fn test_equality_failures() {
+	// only values that could be equal compare
+	helpers::eval_expect_errors(&[
+		("1 == \"1\"",),
+		("decimal(\"0.5\") == 0.5",),
+		("null != 1",),
+	]);
+	helpers::check_expect_diagnostics(&[
+		("1 == \"1\"", "no impl of method 'equal' for (Int, String)"),
+		(
+			"decimal(\"0.5\") != 0.5",
+			"no impl of method 'not_equal' for (Decimal, Float)",
+		),
+	]);
+}
+
+#[test]
+fn test_identical() {
+	helpers::eval_expect_values(&[
+		(
+			"identical(list(1, \"a\"), list(1, \"a\"))",
+			Value::Bool(true),
+		),
+		("identical(1, 1.0)", Value::Bool(false)),
+		("1 == 1.0", Value::Bool(true)),
+		(
+			"identical(decimal(\"1.50\"), decimal(\"1.5\"))",
+			Value::Bool(true),
+		),
+	]);
+}
+
+#[test]
+fn test_hash() {
+	helpers::eval_expect_values(&[
+		(
+			"hash(list(1, \"a\")) == hash(list(1, \"a\"))",
+			Value::Bool(true),
+		),
+		(
+			"hash(1) == hash(1.0) & hash(1) == hash(bigint(\"1\")) & hash(1) == hash(decimal(\"1.00\"))",
+			Value::Bool(true),
+		),
+		("hash(\"a\") == hash(\"b\")", Value::Bool(false)),
+		// past 2^53 an Int can equal a Float that isn't exactly the same number
+		("9007199254740993 == 9007199254740992.0", Value::Bool(true)),
+		(
+			"hash(9007199254740993) == hash(9007199254740992.0)",
+			Value::Bool(true),
+		),
+		("hash(0) == hash(-0.0)", Value::Bool(true)),
+	]);
+
+	// stable between contexts, not seeded per process
+	let ctx = EvalContext::new_with_stdlib();
+	let other = EvalContext::new_with_stdlib();
+	let value = Value::List(vec![Value::String("a".into()), Value::Float(0.5)].into());
+	assert_eq!(
+		hash_value(&ctx, &value).unwrap(),
+		hash_value(&other, &value).unwrap()
+	);
+}
+
+#[test]
+fn test_distinct() {
+	helpers::eval_expect_values(&[
+		(
+			"distinct(list(1, 2, 1.0, \"2\", list(1), list(1), null, null))",
+			Value::List(
+				vec![
+					Value::Int(1),
+					Value::Int(2),
+					Value::String("2".into()),
+					Value::List(vec![Value::Int(1)].into()),
+					Value::Null(()),
+				]
+				.into(),
+			),
+		),
+		(
+			"distinct(list(9007199254740993, 9007199254740992.0))",
+			Value::List(vec![Value::Int(9007199254740993)].into()),
+		),
+		("distinct(list())", Value::List(vec![].into())),
+	]);
+}
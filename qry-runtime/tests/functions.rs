@@ -1,3 +1,4 @@
+use qry_lang::SourceLocation;
 use qry_runtime::{Type, Value};
 
 pub mod helpers;
@@ -18,17 +19,56 @@ fn test_functions() {
 			getter(30)()",
 			Value::Int(31),
 		),
-		("list()", Value::List(vec![])),
+		("list()", Value::List(vec![].into())),
 		(
 			r#"list(1, 2.0, "test string")"#,
-			Value::List(vec![
-				Value::Int(1),
-				Value::Float(2.0),
-				Value::String("test string".into()),
-			]),
+			Value::List(
+				vec![
+					Value::Int(1),
+					Value::Float(2.0),
+					Value::String("test string".into()),
+				]
+				.into(),
+			),
 		),
 		(r#"list(1, 2.0, "test string")[0]"#, Value::Int(1)),
 		(r#"list(1, 2.0, "test string")[1]"#, Value::Float(2.)),
+		(
+			"fn f(a: Int) -> Int {
+				b <- a * 2
+				b + 1
+			}
+			f(3)",
+			Value::Int(7),
+		),
+		(
+			"fn adder(n: Int) -> Any { fn(x: Int) -> Int { x + n } }
+			adder(2)(5)",
+			Value::Int(7),
+		),
+		(
+			"b <- 10
+			fn f() -> Int {
+				c <- b
+				b <- 1
+				c + b
+			}
+			f()",
+			Value::Int(11),
+		),
+		(
+			"fn f() -> Any {
+				use core::{Int}
+				Int
+			}
+			f()",
+			Value::Type(Type::Int),
+		),
+		(
+			"fn f(a: Int) -> Any { parse(a + 1) }
+			typeof(f(1)) == core::typeof(parse(1))",
+			Value::Bool(true),
+		),
 	]);
 }
 
@@ -36,3 +76,44 @@ fn test_functions() {
 fn test_function_failures() {
 	helpers::eval_expect_errors(&[("1()",), ("typeof(1, 2)",)]);
 }
+
+#[test]
+fn test_stack_frames() {
+	let src = "fn inner(x: Int) -> Int { x / 0 }
+	fn outer(s: String, x: Int) -> Int {
+		inner(x)
+	}
+	outer(\"a\", 1)";
+	let at_line = |line| SourceLocation::User {
+		file: "tests".into(),
+		line,
+	};
+
+	for result in &[helpers::eval_src(src), helpers::eval_src_compiled(src)] {
+		let err = result.as_ref().unwrap_err();
+		let frames = err
+			.stack
+			.iter()
+			.map(|f| (f.name.as_str(), f.location.clone(), f.arg_types.clone()))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			&frames[..2],
+			&[
+				("outer", at_line(5), vec![Type::String, Type::Int]),
+				("inner", at_line(3), vec![Type::Int]),
+			]
+		);
+		assert_eq!(err.stack[0].definition, at_line(2));
+		assert_eq!(err.stack[1].definition, at_line(1));
+
+		// the native div impl points back at the script line that called it
+		let native_frame = err.stack.last().unwrap();
+		assert_eq!(native_frame.location, at_line(1));
+		assert!(matches!(
+			native_frame.definition,
+			SourceLocation::Native { .. }
+		));
+		assert_eq!(err.location, at_line(1));
+	}
+}
@@ -0,0 +1,190 @@
+use qry_lang::parse;
+use qry_runtime::{
+	collect_cycles, eval_multi, gc_stats, Environment, EvalContext, EvalResult, Function,
+	RuntimeMethods, Shared, Signature, Type, Value, Weak,
+};
+use std::sync::{Mutex, MutexGuard};
+
+pub mod helpers;
+
+lazy_static::lazy_static! {
+	static ref COLLECTING: Mutex<()> = Mutex::new(());
+}
+
+// with the sync feature collections cover every thread, so like a host these
+// tests only collect while nothing else is evaluating
+fn collecting() -> MutexGuard<'static, ()> {
+	COLLECTING.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// a host library that keeps the callbacks it's given
+fn callback_library(_: &RuntimeMethods) -> Shared<Environment> {
+	let env = Environment::new("host");
+	env.define_builtin(
+		"keep",
+		Signature::returning(&Type::Null).param("callback", &Type::Function),
+		|ctx, args, _| {
+			if let Some(Value::Library(host)) = ctx.library_env.get("host") {
+				host.update("kept", args[0].clone());
+			}
+			Ok(Value::Null(()))
+		},
+	);
+	env
+}
+
+// the callback captured lib, so the library and the callback it keeps refer to
+// each other
+fn keep_callback(ctx: &EvalContext) -> Weak<Function> {
+	let src = "lib <- host
+		callback <- fn(x: Int) -> Int { x + 1 }
+		host::keep(callback)
+		host::kept(1)";
+	assert_eq!(eval(ctx, src).unwrap(), Value::Int(2));
+	match ctx.env.get("callback") {
+		Some(Value::Function(callback)) => Shared::downgrade(&callback),
+		other => panic!("expected a function, got {:?}", other),
+	}
+}
+
+fn eval(ctx: &EvalContext, src: &str) -> EvalResult<Value> {
+	eval_multi(ctx, &parse(src, "tests").unwrap())
+}
+
+fn host_context() -> EvalContext {
+	EvalContext::builder()
+		.with_library(callback_library)
+		.build()
+}
+
+#[test]
+fn test_collects_closure_cycles() {
+	let _collecting = collecting();
+	let before = gc_stats();
+	let ctx = host_context();
+	let callback = keep_callback(&ctx);
+
+	// everything is still reachable from the context
+	collect_cycles();
+	assert!(callback.upgrade().is_some());
+
+	drop(ctx);
+	assert!(callback.upgrade().is_some());
+	assert!(collect_cycles() > 0);
+	assert!(callback.upgrade().is_none());
+	assert!(gc_stats().collected > before.collected);
+}
+
+#[test]
+fn test_collects_automatically() {
+	let _collecting = collecting();
+	let callback = keep_callback(&host_context());
+
+	// collections start once enough environments are tracked, except with the
+	// sync feature where they're left to the host
+	let before = gc_stats();
+	let _envs = (0..1000)
+		.map(|_| Environment::new("filler"))
+		.collect::<Vec<_>>();
+	let automatic = cfg!(not(feature = "sync"));
+	assert_eq!(gc_stats().collections > before.collections, automatic);
+	assert_eq!(callback.upgrade().is_none(), automatic);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_collects_cycles_from_other_threads() {
+	let _collecting = collecting();
+	// everything in the cycle is created on the worker, which is gone by the
+	// time the collection runs
+	let callback = std::thread::spawn(|| keep_callback(&host_context()))
+		.join()
+		.unwrap();
+
+	assert!(callback.upgrade().is_some());
+	assert!(collect_cycles() > 0);
+	assert!(callback.upgrade().is_none());
+}
+
+// forks collecting from a builtin, while the context calling it is part way
+// through evaluating
+fn fork_library(_: &RuntimeMethods) -> Shared<Environment> {
+	let env = Environment::new("fork");
+	env.define_builtin("gc", Signature::returning(&Type::Any), |ctx, _, _| {
+		eval(&ctx.fork(), "gc()")
+	});
+	env
+}
+
+#[test]
+fn test_gc_in_forks() {
+	let _collecting = collecting();
+	let ctx = EvalContext::builder().with_library(fork_library).build();
+	let result = eval(
+		&ctx,
+		"n <- 1
+		fn plus_n(x: Int) -> Int { x + n }
+		fork::gc()
+		plus_n(1)",
+	);
+	if cfg!(feature = "sync") {
+		assert_eq!(result.unwrap_err().message, "not found: gc");
+	} else {
+		assert_eq!(result.unwrap(), Value::Int(2));
+	}
+}
+
+// with the sync feature scripts can't collect at all, so one fork can't clear
+// environments another thread is using
+#[cfg(feature = "sync")]
+#[test]
+fn test_gc_while_forks_evaluate() {
+	let _collecting = collecting();
+	let ctx = EvalContext::new_with_stdlib();
+	eval(
+		&ctx,
+		"n <- 1
+		m <- 0
+		fn plus_n(x: Int) -> Int { x + n }",
+	)
+	.unwrap();
+	let forked = ctx.fork();
+	let worker = std::thread::spawn(move || {
+		for _ in 0..100 {
+			eval(&forked, "m <- plus_n(m)").unwrap();
+		}
+	});
+	for _ in 0..10 {
+		let err = eval(&ctx.fork(), "gc()").unwrap_err();
+		assert_eq!(err.message, "not found: gc");
+	}
+	worker.join().unwrap();
+	assert_eq!(eval(&ctx, "m").unwrap(), Value::Int(100));
+}
+
+#[test]
+fn test_gc_builtins() {
+	let _collecting = collecting();
+	#[cfg(not(feature = "sync"))]
+	helpers::eval_expect_values(&[(
+		"fn f(x: Int) -> Int {
+			gc()
+			x + 1
+		}
+		f(1)",
+		Value::Int(2),
+	)]);
+	helpers::eval_expect_values(&[(
+		"stats <- gc_stats()
+		list(stats[0][0], stats[1][0], stats[2][0], typeof(stats[0][1]))",
+		Value::List(
+			vec![
+				Value::String("environments".into()),
+				Value::String("collections".into()),
+				Value::String("collected".into()),
+				Value::Type(Type::Int),
+			]
+			.into(),
+		),
+	)]);
+}
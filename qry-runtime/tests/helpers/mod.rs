@@ -1,20 +1,61 @@
-use qry_lang::parse;
-use qry_runtime::{eval_multi, EvalContext, EvalResult, Value};
+use qry_lang::{parse, SyntaxNode};
+use qry_runtime::{
+	check, compile, eval_multi, run_compiled, Diagnostic, EvalContext, EvalResult, Value,
+};
+
+fn parse_src(src: &str) -> Vec<SyntaxNode> {
+	parse(src, "tests").unwrap_or_else(|err| panic!("parse failed ({}): {:?}", src, err))
+}
 
 pub fn eval_src(src: &str) -> EvalResult<Value> {
-	let exprs = parse(src, "tests").unwrap_or_else(|err| panic!("parse failed ({}): {:?}", src, err));
-	eval_multi(&EvalContext::new_with_stdlib(), &exprs)
+	eval_multi(&EvalContext::new_with_stdlib(), &parse_src(src))
+}
+
+pub fn eval_src_compiled(src: &str) -> EvalResult<Value> {
+	run_compiled(&EvalContext::new_with_stdlib(), &compile(&parse_src(src)))
+}
+
+pub fn check_src(src: &str) -> Vec<Diagnostic> {
+	check(&EvalContext::new_with_stdlib(), &parse_src(src))
 }
 
 pub fn eval_expect_values(cases: &[(&str, Value)]) {
 	for (src, expectation) in cases {
+		let diagnostics = check_src(src);
+		assert!(
+			diagnostics.is_empty(),
+			"unexpected diagnostics ({}): {:?}",
+			src,
+			diagnostics
+		);
+
 		let result = eval_src(src).unwrap_or_else(|err| panic!("eval failed ({}): {:?}", src, err));
 		assert_eq!(result, *expectation, "src: {}", src);
+
+		let result = eval_src_compiled(src)
+			.unwrap_or_else(|err| panic!("compiled eval failed ({}): {:?}", src, err));
+		assert_eq!(result, *expectation, "compiled src: {}", src);
 	}
 }
 
 pub fn eval_expect_errors(cases: &[(&str,)]) {
 	for (src,) in cases {
 		eval_src(src).expect_err(&format!("eval succeded unexpectedly for {}", src));
+		eval_src_compiled(src).expect_err(&format!("compiled eval succeded unexpectedly for {}", src));
+	}
+}
+
+pub fn check_expect_diagnostics(cases: &[(&str, &str)]) {
+	for (src, expected_message) in cases {
+		let diagnostics = check_src(src);
+		assert!(
+			diagnostics
+				.iter()
+				.any(|d| d.message.contains(expected_message)),
+			"expected diagnostic '{}' for {}, got {:?}",
+			expected_message,
+			src,
+			diagnostics
+		);
 	}
 }
@@ -0,0 +1,138 @@
+use qry_lang::parse;
+use qry_runtime::{
+	compile, eval_multi, run_compiled, EvalContext, EvalLimits, EvalResult, ExceptionKind, Value,
+};
+use std::thread;
+use std::time::Duration;
+
+pub mod helpers;
+
+// functions can't see their own name, so recursion goes through method
+// dispatch instead
+const FIB: &str = r#"impl ops::div(fib: String, n: Int) -> Int {
+	switch n < 2 {
+		true => n
+		false => fib / (n - 1) + fib / (n - 2)
+	}
+}
+"#;
+
+fn fib(n: i64) -> String {
+	format!("{}\"fib\" / {}", FIB, n)
+}
+
+fn eval_both(ctx: &EvalContext, src: &str) -> Vec<EvalResult<Value>> {
+	let syntax = parse(src, "tests").unwrap();
+	vec![
+		eval_multi(ctx, &syntax),
+		run_compiled(ctx, &compile(&syntax)),
+	]
+}
+
+fn expect_limit(limits: EvalLimits, src: &str, message: &str) {
+	let ctx = EvalContext::new_with_stdlib();
+	ctx.set_limits(limits);
+	for result in eval_both(&ctx, src) {
+		let err = result.unwrap_err();
+		assert_eq!(err.kind, ExceptionKind::LimitExceeded, "{}", err);
+		assert!(err.message.contains(message), "{}: {}", src, err.message);
+	}
+}
+
+#[test]
+fn test_limits() {
+	expect_limit(
+		EvalLimits {
+			max_call_depth: Some(200),
+			..EvalLimits::default()
+		},
+		r#"impl ops::minus(a: String) -> String { -a }
+		-"x""#,
+		"call depth limit exceeded (200 frames)",
+	);
+	expect_limit(
+		EvalLimits {
+			max_steps: Some(10),
+			..EvalLimits::default()
+		},
+		"1 + 2 + 3 + 4 + 5 + 6 + 7 + 8",
+		"step limit exceeded (10 steps)",
+	);
+	expect_limit(
+		EvalLimits {
+			timeout: Some(Duration::from_millis(0)),
+			..EvalLimits::default()
+		},
+		&fib(15),
+		"timed out",
+	);
+
+	// recursion is only limited when the host asks for it, otherwise it's up
+	// to the host to give evaluation enough stack
+	let deep = thread::Builder::new()
+		.stack_size(64 * 1024 * 1024)
+		.spawn(|| {
+			let src = r#"impl ops::div(depth: String, n: Int) -> Int {
+				switch n > 0 {
+					true => depth / (n - 1) + 1
+					false => 0
+				}
+			}
+			"depth" / 300"#;
+			for result in eval_both(&EvalContext::new_with_stdlib(), src) {
+				assert_eq!(result.unwrap(), Value::Int(300));
+			}
+		})
+		.unwrap();
+	deep.join().unwrap();
+
+	// budgets are per run, so a context can keep going after hitting one
+	let ctx = EvalContext::new_with_stdlib();
+	ctx.set_limits(EvalLimits {
+		max_steps: Some(1000),
+		..EvalLimits::default()
+	});
+	for _ in 0..20 {
+		for result in eval_both(&ctx, &fib(5)) {
+			assert_eq!(result.unwrap(), Value::Int(5));
+		}
+	}
+}
+
+#[test]
+fn test_interrupts() {
+	let ctx = EvalContext::new_with_stdlib();
+
+	// an interrupt with nothing running is dropped
+	ctx.interrupt_handle().interrupt();
+	assert_eq!(
+		eval_multi(&ctx, &parse("1 + 1", "tests").unwrap()).unwrap(),
+		Value::Int(2)
+	);
+
+	for compiled in &[false, true] {
+		let handle = ctx.interrupt_handle();
+		let interrupter = thread::spawn(move || {
+			thread::sleep(Duration::from_millis(20));
+			handle.interrupt();
+		});
+
+		let syntax = parse(&fib(40), "tests").unwrap();
+		let err = match compiled {
+			false => eval_multi(&ctx, &syntax),
+			true => run_compiled(&ctx, &compile(&syntax)),
+		}
+		.unwrap_err();
+		interrupter.join().unwrap();
+
+		assert_eq!(err.kind, ExceptionKind::Cancelled);
+		assert_eq!(err.message, "interrupted");
+		assert!(ctx.callstack.borrow().is_empty());
+	}
+}
+
+#[test]
+fn test_ordinary_exceptions_are_runtime_kind() {
+	let err = helpers::eval_src("1 / 0").unwrap_err();
+	assert_eq!(err.kind, ExceptionKind::Runtime);
+}
@@ -0,0 +1,70 @@
+use qry_runtime::{List, Value};
+
+pub mod helpers;
+
+fn ints(items: &[i64]) -> Value {
+	Value::List(items.iter().map(|i| Value::Int(*i)).collect())
+}
+
+fn to_ints(list: &List) -> Vec<i64> {
+	list
+		.iter()
+		.map(|v| match v {
+			Value::Int(i) => *i,
+			other => panic!("expected an int, got {:?}", other),
+		})
+		.collect()
+}
+
+#[test]
+fn test_persistent_list() {
+	// enough items for a trie three levels deep
+	let list = (0..2000).map(Value::Int).collect::<List>();
+	assert_eq!(list.len(), 2000);
+	assert_eq!(list.get(1999), Some(&Value::Int(1999)));
+	assert_eq!(list.get(2000), None);
+	assert_eq!(to_ints(&list), (0..2000).collect::<Vec<_>>());
+
+	// copies don't see each other's changes
+	let longer = list.append(Value::Int(2000));
+	assert_eq!((list.len(), longer.len()), (2000, 2001));
+	assert_eq!(longer.get(2000), Some(&Value::Int(2000)));
+
+	let slice = list.slice(1000, 1003);
+	assert_eq!(to_ints(&slice), vec![1000, 1001, 1002]);
+	let pushed = slice.append(Value::Int(-1));
+	assert_eq!(to_ints(&pushed), vec![1000, 1001, 1002, -1]);
+	assert_eq!(list.get(1003), Some(&Value::Int(1003)));
+	assert_eq!(to_ints(&slice.slice(1, 10)), vec![1001, 1002]);
+
+	let joined = slice.concat(&list.slice(0, 2));
+	assert_eq!(to_ints(&joined), vec![1000, 1001, 1002, 0, 1]);
+	assert!(List::new().is_empty());
+}
+
+#[test]
+fn test_list_builtins() {
+	helpers::eval_expect_values(&[
+		("append(list(1, 2), 3)", ints(&[1, 2, 3])),
+		(
+			"a <- list(1, 2)
+			b <- append(a, 3)
+			list(a, b)",
+			Value::List(vec![ints(&[1, 2]), ints(&[1, 2, 3])].into()),
+		),
+		("concat(list(1), list(2, 3))", ints(&[1, 2, 3])),
+		("concat(list(), list())", ints(&[])),
+		("slice(list(1, 2, 3, 4), 1, 3)", ints(&[2, 3])),
+		("slice(list(1, 2), 1, 10)", ints(&[2])),
+		("slice(list(1, 2, 3), 1, 2)[0]", Value::Int(2)),
+	]);
+}
+
+#[test]
+fn test_list_builtin_failures() {
+	helpers::eval_expect_errors(&[
+		("slice(list(1, 2), 2, 1)",),
+		("slice(list(1, 2), -1, 1)",),
+		("slice(list(1, 2), 0, 1)[1]",),
+	]);
+}
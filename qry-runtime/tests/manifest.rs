@@ -0,0 +1,66 @@
+use qry_lang::parse;
+use qry_runtime::{eval_multi, EvalContext, Manifest, ManifestError, Value};
+use std::path::PathBuf;
+
+fn projects() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/projects")
+}
+
+#[test]
+fn test_manifest() {
+	let root = projects().join("app");
+	let manifest = Manifest::load(&root.join("qry.toml")).unwrap();
+	assert_eq!(manifest.project.name, "app");
+	assert_eq!(manifest.search_path(), vec![root.join("lib")]);
+
+	let packages = manifest
+		.packages
+		.iter()
+		.map(|p| &p.name)
+		.collect::<Vec<_>>();
+	assert_eq!(packages, vec!["shared"]);
+
+	let warehouse = root
+		.join("data/warehouse.db")
+		.to_string_lossy()
+		.into_owned();
+	assert_eq!(
+		manifest.connection_targets(),
+		vec![
+			("scratch".to_string(), ":memory:".to_string()),
+			("warehouse".to_string(), warehouse),
+		]
+	);
+
+	// discovery walks up from nested directories
+	let discovered = Manifest::discover(&root.join("lib")).unwrap().unwrap();
+	assert_eq!(discovered.root, root);
+}
+
+#[test]
+fn test_manifest_modules() {
+	let manifest = Manifest::load(&projects().join("app/qry.toml")).unwrap();
+	let ctx = EvalContext::builder().manifest(&manifest).build();
+
+	let src = "use report::{headline}\nuse shared::text\nheadline(2) + text::shout(1)";
+	let ret = eval_multi(&ctx, &parse(src, "tests").unwrap());
+	assert_eq!(ret.unwrap(), Value::Int(31));
+	assert!(ctx.connections.contains_key("warehouse"));
+}
+
+#[test]
+fn test_manifest_failures() {
+	match Manifest::load(&projects().join("loop_a/qry.toml")) {
+		Err(ManifestError::DependencyCycle(names)) => {
+			assert_eq!(names, vec!["loop_a", "loop_b", "loop_a"])
+		}
+		other => panic!("expected a dependency cycle, got {:?}", other),
+	}
+
+	match Manifest::load(&projects().join("broken/qry.toml")) {
+		Err(err @ ManifestError::Parse(..)) => assert!(err.to_string().contains("project")),
+		other => panic!("expected a parse error, got {:?}", other),
+	}
+
+	assert!(Manifest::load(&projects().join("missing/qry.toml")).is_err());
+}
@@ -42,10 +42,62 @@ fn test_methods() {
 			null + null"#,
 			Value::String("why though".into()),
 		),
+		("1 + 2.5", Value::Float(3.5)),
+		("2.5 * 2", Value::Float(5.)),
+		("1 == 1.0", Value::Bool(true)),
+		(
+			r#"impl ops::add(a: Any, b: Int) -> String { "any + int" }
+			"x" + 1"#,
+			Value::String("any + int".into()),
+		),
+		(
+			r#"impl ops::add(a: Any, b: Int) -> String { "any + int" }
+			1 + 2"#,
+			Value::Int(3),
+		),
+		(
+			r#"impl ops::mul(a: Float, b: String) -> String { "promoted" }
+			2 * "x""#,
+			Value::String("promoted".into()),
+		),
+		(
+			r#"impl ops::sub(a: Any, b: Any) -> String { "fallback" }
+			impl ops::sub(a: Any, b: Bool) -> String { "bool rhs" }
+			list(null, 1 - true, null - null, 1 - 1)"#,
+			Value::List(
+				vec![
+					Value::Null(()),
+					Value::String("bool rhs".into()),
+					Value::String("fallback".into()),
+					Value::Int(0),
+				]
+				.into(),
+			),
+		),
 	]);
 }
 
 #[test]
 fn test_method_failures() {
-	helpers::eval_expect_errors(&[("ops::add(null, null)",), ("null + null",)]);
+	helpers::eval_expect_errors(&[
+		("ops::add(null, null)",),
+		("null + null",),
+		(r#"impl ops::sub(a: Any, b: Bool) -> Int { 1 }
+			impl ops::sub(a: Bool, b: Any) -> Int { 2 }
+			true - true"#,),
+	]);
+}
+
+#[test]
+fn test_method_ambiguity_message() {
+	let err = helpers::eval_src(
+		r#"impl ops::sub(a: Any, b: Bool) -> Int { 1 }
+		impl ops::sub(a: Bool, b: Any) -> Int { 2 }
+		true - true"#,
+	)
+	.unwrap_err();
+
+	assert!(err.message.contains("ambiguous call to method 'sub'"));
+	assert!(err.message.contains("(a: Any, b: Bool) -> Int"));
+	assert!(err.message.contains("(a: Bool, b: Any) -> Int"));
 }
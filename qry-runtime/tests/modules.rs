@@ -0,0 +1,107 @@
+use qry_lang::parse;
+use qry_runtime::{
+	check, compile, eval_multi, run_compiled, Capability, EvalContext, EvalContextBuilder,
+	EvalResult, ExceptionKind, Shared, Value,
+};
+use std::path::PathBuf;
+
+fn fixtures() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn builder() -> EvalContextBuilder {
+	EvalContext::builder().module_path(fixtures())
+}
+
+fn eval_both(src: &str) -> Vec<EvalResult<Value>> {
+	let syntax = parse(src, "tests").unwrap();
+	vec![
+		eval_multi(&builder().build(), &syntax),
+		run_compiled(&builder().build(), &compile(&syntax)),
+	]
+}
+
+fn expect_value(src: &str, expectation: Value) {
+	let syntax = parse(src, "tests").unwrap();
+	let diagnostics = check(&builder().build(), &syntax);
+	assert!(diagnostics.is_empty(), "{}: {:?}", src, diagnostics);
+
+	for result in eval_both(src) {
+		assert_eq!(result.unwrap(), expectation, "src: {}", src);
+	}
+}
+
+fn expect_error(src: &str, message: &str) {
+	for result in eval_both(src) {
+		let err = result.unwrap_err();
+		assert!(err.message.contains(message), "{}: {}", src, err.message);
+	}
+}
+
+#[test]
+fn test_modules() {
+	expect_value("use mylib::helpers\nhelpers::clean(3)", Value::Int(7));
+	expect_value(
+		"use mylib::helpers::{clean, limit}\nclean(limit)",
+		Value::Int(21),
+	);
+	expect_value("use mylib::helpers::*\nlimit", Value::Int(10));
+	expect_value("use mylib::util::twice\ntwice(4)", Value::Int(8));
+
+	// the script's own bindings aren't visible inside a module
+	expect_value(
+		"offset <- 100\nuse mylib::helpers::{clean}\nclean(0)",
+		Value::Int(1),
+	);
+
+	// pub works in scripts too, it only matters to importers
+	expect_value("pub x <- 2\nx", Value::Int(2));
+}
+
+#[test]
+fn test_module_cache() {
+	let ctx = builder().build();
+	let load = || match eval_multi(
+		&ctx,
+		&parse("use mylib::helpers\nhelpers", "tests").unwrap(),
+	) {
+		Ok(Value::Library(env)) => env,
+		other => panic!("expected a library, got {:?}", other),
+	};
+	assert!(Shared::ptr_eq(&load(), &load()));
+}
+
+#[test]
+fn test_module_failures() {
+	expect_error("use mylib::helpers::{offset}", "not found: offset");
+	expect_error("use mylib::missing", "module not found: mylib::missing");
+	expect_error("use nowhere::{x}", "module not found: nowhere");
+	expect_error(
+		"use cycle::a",
+		"import cycle: cycle::a -> cycle::b -> cycle::a",
+	);
+	expect_error("pub 1 + 1", "pub requires a named definition");
+
+	let syntax = parse("use mylib::helpers::{offset}\nuse nowhere::{x}", "tests").unwrap();
+	let diagnostics = check(&builder().build(), &syntax)
+		.into_iter()
+		.map(|d| d.message)
+		.collect::<Vec<_>>();
+	assert_eq!(
+		diagnostics,
+		vec!["not found: offset", "unknown library: nowhere"]
+	);
+
+	// denied whether or not the module exists, so the sandbox can't probe files
+	let ctx = builder().deny(Capability::Filesystem).build();
+	for src in &["use mylib::helpers", "use nowhere::{x}"] {
+		let err = eval_multi(&ctx, &parse(src, "tests").unwrap()).unwrap_err();
+		assert_eq!(err.kind, ExceptionKind::Denied, "{}", src);
+	}
+	let syntax = parse("use mylib::helpers::{clean}", "tests").unwrap();
+	let diagnostics = check(&ctx, &syntax)
+		.into_iter()
+		.map(|d| d.message)
+		.collect::<Vec<_>>();
+	assert_eq!(diagnostics, vec!["unknown library: mylib::helpers"]);
+}
@@ -0,0 +1,150 @@
+use qry_runtime::{BigInt, Decimal, Value};
+
+pub mod helpers;
+
+fn string(s: &str) -> Value {
+	Value::String(s.into())
+}
+
+#[test]
+fn test_checked_int_arithmetic() {
+	helpers::eval_expect_values(&[
+		("9223372036854775806 + 1", Value::Int(9223372036854775807)),
+		(
+			"(0 - 9223372036854775807) - 1",
+			Value::Int(-9223372036854775807 - 1),
+		),
+		("4611686018427387903 * 2", Value::Int(9223372036854775806)),
+		("0 - 7 / 2", Value::Int(-3)),
+	]);
+
+	let cases = &[
+		("9223372036854775807 + 1", "integer overflow"),
+		("(0 - 9223372036854775807) - 2", "integer overflow"),
+		("9223372036854775807 * 2", "integer overflow"),
+		("-(0 - 9223372036854775807 - 1)", "integer overflow"),
+		("1 / 0", "division by zero"),
+	];
+	for (src, message) in cases {
+		for result in &[helpers::eval_src(src), helpers::eval_src_compiled(src)] {
+			let err = result.as_ref().unwrap_err();
+			assert!(err.message.contains(message), "{}: {}", src, err.message);
+		}
+	}
+}
+
+#[test]
+fn test_bigint() {
+	helpers::eval_expect_values(&[
+		(
+			r#"to_string(bigint("9223372036854775807") + 1)"#,
+			string("9223372036854775808"),
+		),
+		(
+			r#"to_string(bigint("123456789012345678901234567890") * bigint("987654321098765432109876543210"))"#,
+			string("121932631137021795226185032733622923332237463801111263526900"),
+		),
+		(
+			r#"to_string(bigint("121932631137021795226185032733622923332237463801111263526900") / bigint("987654321098765432109876543210"))"#,
+			string("123456789012345678901234567890"),
+		),
+		(r#"to_string(bigint("-7") / 2)"#, string("-3")),
+		(r#"to_string(5 - bigint("8"))"#, string("-3")),
+		(r#"to_string(-bigint("0"))"#, string("0")),
+		(
+			r#"bigint("100000000000000000000") > bigint("99999999999999999999")"#,
+			Value::Bool(true),
+		),
+		(r#"bigint("-2") < bigint("1")"#, Value::Bool(true)),
+		(r#"bigint("42") == bigint(42)"#, Value::Bool(true)),
+		(r#"bigint("-0") == 0"#, Value::Bool(true)),
+		(r#"bigint(1)"#, Value::BigInt(BigInt::from(1))),
+	]);
+}
+
+#[test]
+fn test_decimal() {
+	helpers::eval_expect_values(&[
+		(
+			r#"to_string(decimal("0.1") + decimal("0.2"))"#,
+			string("0.3"),
+		),
+		(
+			r#"decimal("0.1") + decimal("0.2") == decimal("0.3")"#,
+			Value::Bool(true),
+		),
+		(r#"to_string(decimal("19.99") * 3)"#, string("59.97")),
+		(
+			r#"to_string(decimal("1.50") * decimal("1.5"))"#,
+			string("2.250"),
+		),
+		(r#"to_string(decimal("10.00") - 12)"#, string("-2.00")),
+		(r#"to_string(decimal("10.00") / 4)"#, string("2.50")),
+		(r#"to_string(decimal(1) / 3)"#, string("0.3333333333333333")),
+		(r#"to_string(decimal(2) / 3)"#, string("0.6666666666666667")),
+		// ties round to even
+		(
+			r#"to_string(decimal("0.25") / decimal("1000000000000000"))"#,
+			string("0.0000000000000002"),
+		),
+		(
+			r#"to_string(decimal("0.35") / decimal("1000000000000000"))"#,
+			string("0.0000000000000004"),
+		),
+		(
+			r#"to_string(decimal("100000000000000000000000") / decimal("2"))"#,
+			string("50000000000000000000000"),
+		),
+		(
+			r#"to_string(decimal("100000000000000000000000") / 3)"#,
+			string("33333333333333333333333.333333333333333"),
+		),
+		(r#"to_string(-decimal("0.05"))"#, string("-0.05")),
+		(r#"to_string(decimal(0.1))"#, string("0.1")),
+		(r#"to_string(decimal(2.50))"#, string("2.5")),
+		(r#"decimal("1.0") == decimal("1.00")"#, Value::Bool(true)),
+		(r#"decimal("1.05") > decimal("1.1")"#, Value::Bool(false)),
+		(r#"decimal("-1.5") < decimal("-1.25")"#, Value::Bool(true)),
+		(r#"decimal("2") >= 2"#, Value::Bool(true)),
+		(
+			r#"decimal("12.34")"#,
+			Value::Decimal("12.34".parse::<Decimal>().unwrap()),
+		),
+	]);
+}
+
+#[test]
+fn test_numeric_failures() {
+	helpers::eval_expect_errors(&[
+		(r#"bigint("12a")"#,),
+		(r#"bigint(1) / 0"#,),
+		(r#"decimal("1.2.3")"#,),
+		(r#"decimal("")"#,),
+		(r#"decimal(1) / decimal("0.00")"#,),
+		(r#"decimal("99999999999999999999999999999999999999") * 10"#,),
+		(r#"decimal(1.5) + 1.5"#,),
+		(r#"bigint(1) + decimal(1)"#,),
+	]);
+	helpers::check_expect_diagnostics(&[(
+		r#"decimal(1.5) + 1.5"#,
+		"no impl of method 'add' for (Decimal, Float)",
+	)]);
+
+	let cases = &[
+		(r#"bigint(1) / 0"#, "division by zero"),
+		(r#"decimal(1) / decimal("0.00")"#, "division by zero"),
+		(
+			r#"decimal("99999999999999999999999999999999999999") * 10"#,
+			"decimal overflow",
+		),
+		(r#"decimal("1.2.3")"#, "invalid decimal: 1.2.3"),
+		(
+			r#"decimal("0.00000000000000000000000000001")"#,
+			"too many decimal places",
+		),
+	];
+	for (src, message) in cases {
+		let err = helpers::eval_src(src).unwrap_err();
+		assert!(err.message.contains(message), "{}: {}", src, err.message);
+	}
+}
@@ -0,0 +1,86 @@
+use qry_lang::parse;
+use qry_runtime::{
+	compile, eval_multi, run_compiled, EvalContext, EvalEvent, ExceptionKind, Observer, Shared,
+};
+use std::sync::Mutex;
+
+struct Recorder {
+	events: Shared<Mutex<Vec<String>>>,
+}
+
+impl Observer for Recorder {
+	fn event(&self, ctx: &EvalContext, event: &EvalEvent) {
+		let depth = ctx.callstack.borrow().len();
+		let event = match event {
+			EvalEvent::CallEnter { name, args, .. } if *name == "half" => {
+				format!("enter {} {} {}", name, depth, args.len())
+			}
+			EvalEvent::CallExit { name, result } if *name == "half" => {
+				format!("exit {} {} {}", name, depth, result.is_ok())
+			}
+			EvalEvent::Assign { name, .. } => format!("assign {}", name),
+			EvalEvent::Exception(ex) if ex.kind == ExceptionKind::Runtime => {
+				format!("exception {}", ex.message)
+			}
+			_ => return,
+		};
+		self.events.lock().unwrap().push(event);
+	}
+}
+
+fn observe(src: &str, compiled: bool) -> Vec<String> {
+	let events = Shared::new(Mutex::new(Vec::new()));
+	let ctx = EvalContext::builder()
+		.observer(Recorder {
+			events: events.clone(),
+		})
+		.build();
+	let syntax = parse(src, "tests").unwrap();
+	let _ = match compiled {
+		true => run_compiled(&ctx, &compile(&syntax)),
+		false => eval_multi(&ctx, &syntax),
+	};
+	let events = events.lock().unwrap();
+	events.clone()
+}
+
+#[test]
+fn test_observe_calls_and_assignments() {
+	let src = "fn half(n: Int) -> Int {
+	h <- n / 2
+	h
+}
+x <- half(8)";
+	for &compiled in &[false, true] {
+		assert_eq!(
+			observe(src, compiled),
+			vec![
+				"assign half",
+				"enter half 1 1",
+				"assign h",
+				"exit half 1 true",
+				"assign x"
+			]
+		);
+	}
+}
+
+#[test]
+fn test_observe_exceptions() {
+	let src = "fn half(n: Int) -> Int {
+	n / 0
+}
+x <- half(8)";
+	for &compiled in &[false, true] {
+		let events = observe(src, compiled);
+		assert_eq!(
+			events,
+			vec![
+				"assign half",
+				"enter half 1 1",
+				"exception division by zero",
+				"exit half 1 false"
+			]
+		);
+	}
+}
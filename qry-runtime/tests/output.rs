@@ -0,0 +1,82 @@
+use qry_lang::parse;
+use qry_runtime::{
+	eval_multi, BufferSink, Capability, EvalContext, EvalContextBuilder, Output, Shared, Signature,
+	Type, Value,
+};
+use std::sync::Mutex;
+
+fn run(builder: EvalContextBuilder, src: &str) {
+	eval_multi(&builder.build(), &parse(src, "tests").unwrap()).unwrap();
+}
+
+#[test]
+fn test_buffer_sink() {
+	let buffer = BufferSink::new();
+	run(
+		EvalContext::builder().output(buffer.clone()),
+		"print(\"hello\")
+print(1 + 2)
+display(null)",
+	);
+
+	assert_eq!(
+		buffer.outputs(),
+		vec![
+			Output::Text("hello".into()),
+			Output::Text("3".into()),
+			Output::Text("null".into())
+		]
+	);
+	assert_eq!(buffer.text(), "hello\n3\nnull\n");
+
+	buffer.clear();
+	assert!(buffer.outputs().is_empty());
+}
+
+#[test]
+fn test_callback_sink() {
+	let lines = Shared::new(Mutex::new(Vec::new()));
+	let sink_lines = lines.clone();
+	run(
+		EvalContext::builder()
+			.output(move |output: &Output| sink_lines.lock().unwrap().push(output.text().to_string())),
+		"x <- \"a\"
+print(x)",
+	);
+	assert_eq!(*lines.lock().unwrap(), vec!["a"]);
+}
+
+#[test]
+fn test_output_needs_stdout() {
+	let buffer = BufferSink::new();
+	let ctx = EvalContext::builder()
+		.output(buffer.clone())
+		.deny(Capability::Stdout)
+		.build();
+	assert!(eval_multi(&ctx, &parse("display(1)", "tests").unwrap()).is_err());
+	assert!(buffer.outputs().is_empty());
+}
+
+#[test]
+fn test_display_impls() {
+	let buffer = BufferSink::new();
+	let ctx = EvalContext::builder().output(buffer.clone()).build();
+	ctx.methods.display.register_builtin(
+		Signature::returning(&Type::Null).param("obj", &Type::Int),
+		|ctx, args, _| {
+			let n = args[0].as_int(ctx)? as usize;
+			ctx.write_output(Output::Table {
+				columns: vec!["n".into()],
+				rows: vec![vec![Some(n.to_string())]; n],
+				text: format!("{} rows", n),
+			})?;
+			Ok(Value::Null(()))
+		},
+	);
+	eval_multi(&ctx, &parse("print(2)\nprint(\"2\")", "tests").unwrap()).unwrap();
+
+	let outputs = buffer.outputs();
+	assert!(matches!(&outputs[0], Output::Table { rows, .. } if rows.len() == 2));
+	assert_eq!(outputs[1], Output::Text("2".into()));
+	assert_eq!(buffer.text(), "2 rows\n2\n");
+}
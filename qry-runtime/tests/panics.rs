@@ -0,0 +1,67 @@
+use qry_lang::{parse, SourceLocation};
+
+pub mod helpers;
+
+// inputs that used to panic inside the runtime instead of raising an exception
+const PREVIOUSLY_PANICKING: &[&str] = &[
+	"list(1, 2)[5]",
+	"list(1, 2)[0 - 1]",
+	"list(1)[]",
+	"1 / 0",
+	"(0 - 9223372036854775807 - 1) / (0 - 1)",
+	"9223372036854775807 + 1",
+	"9223372036854775807 * 9223372036854775807",
+	"impl to_string(x: Bool) -> Int { 1 }",
+	"impl ops::add(a: Bool) -> Bool { a }",
+	"impl 1(a: Bool) -> Bool { a }",
+	r#"impl ops::equal(a: String, b: String) -> Int { 1 }
+	switch "a" {
+		"a" => 1
+	}"#,
+];
+
+#[test]
+fn test_previously_panicking_inputs() {
+	for src in PREVIOUSLY_PANICKING {
+		for result in &[helpers::eval_src(src), helpers::eval_src_compiled(src)] {
+			let err = result
+				.as_ref()
+				.expect_err(&format!("eval succeeded unexpectedly for {}", src));
+			assert_ne!(
+				err.location,
+				SourceLocation::Unknown,
+				"exception has no location for {}: {}",
+				src,
+				err
+			);
+		}
+	}
+}
+
+#[test]
+fn test_panic_messages() {
+	let cases = &[
+		("list(1, 2)[5]", "index out of bounds: 5 (list has 2 items)"),
+		("1 / 0", "division by zero"),
+		(
+			"impl to_string(x: Bool) -> Int { 1 }",
+			"impl of method 'to_string' must return String, got Int",
+		),
+		(
+			"impl ops::add(a: Bool) -> Bool { a }",
+			"impl of method 'add' must take at least 2 params, got 1",
+		),
+		("impl 1(a: Bool) -> Bool { a }", "expected a method"),
+	];
+
+	for (src, message) in cases {
+		let err = helpers::eval_src(src).unwrap_err();
+		assert!(err.message.contains(message), "{}: {}", src, err.message);
+	}
+}
+
+#[test]
+fn test_out_of_range_literals() {
+	assert!(parse("99999999999999999999", "tests").is_err());
+	assert!(parse("9223372036854775807", "tests").is_ok());
+}
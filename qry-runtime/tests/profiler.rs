@@ -0,0 +1,81 @@
+use qry_lang::parse;
+use qry_runtime::{compile, eval_multi, run_compiled, EvalContext, Profiler};
+
+const SRC: &str = "fn square(n: Int) -> Int { n * n }
+fn sum_squares(a: Int, b: Int) -> Int { square(a) + square(b) }
+sum_squares(3, 4)
+sum_squares(5, 6)";
+
+#[test]
+fn test_profile_calls() {
+	for &compiled in &[false, true] {
+		let profiler = Profiler::new();
+		let ctx = EvalContext::builder().profiler(profiler.clone()).build();
+		let syntax = parse(SRC, "tests").unwrap();
+		match compiled {
+			true => run_compiled(&ctx, &compile(&syntax)),
+			false => eval_multi(&ctx, &syntax),
+		}
+		.unwrap();
+
+		let functions = profiler.functions();
+		let calls = |name: &str| {
+			functions
+				.iter()
+				.find(|(n, _)| n == name)
+				.map(|(_, entry)| entry.calls)
+		};
+		assert_eq!(calls("sum_squares"), Some(2));
+		assert_eq!(calls("square"), Some(4));
+		assert_eq!(functions[0].0, "sum_squares");
+		for (_, entry) in &functions {
+			assert!(entry.exclusive <= entry.inclusive);
+		}
+
+		let mut folded = Vec::new();
+		profiler.write_folded(&mut folded).unwrap();
+		let stacks = String::from_utf8(folded)
+			.unwrap()
+			.lines()
+			.map(|l| l[..l.rfind(' ').unwrap()].to_string())
+			.collect::<Vec<_>>();
+		assert!(stacks.contains(&"sum_squares".to_string()));
+		assert!(stacks.contains(&"sum_squares;square".to_string()));
+		assert!(!stacks.contains(&"square".to_string()));
+	}
+}
+
+#[test]
+fn test_profile_recursion() {
+	let profiler = Profiler::new();
+	let ctx = EvalContext::builder().profiler(profiler.clone()).build();
+	// recursion goes through method dispatch, functions can't see their own name
+	let src = r#"impl ops::div(countdown: String, n: Int) -> Int {
+	switch n == 0 {
+		true => 0
+		false => countdown / (n - 1)
+	}
+}
+"countdown" / 5"#;
+	eval_multi(&ctx, &parse(src, "tests").unwrap()).unwrap();
+
+	let countdown = profiler
+		.functions()
+		.into_iter()
+		.find(|(name, _)| name == "div")
+		.unwrap()
+		.1;
+	assert_eq!(countdown.calls, 6);
+
+	// nested calls aren't counted again, so inclusive time stays within the
+	// time of the outermost call rather than adding up six times over
+	let total_exclusive = profiler
+		.functions()
+		.iter()
+		.map(|(_, entry)| entry.exclusive)
+		.sum::<std::time::Duration>();
+	assert!(countdown.inclusive <= total_exclusive);
+
+	profiler.reset();
+	assert!(profiler.functions().is_empty());
+}
@@ -0,0 +1,133 @@
+use qry_lang::parse;
+use qry_runtime::{eval_multi, Environment, EvalContext, Type, Value};
+
+pub mod helpers;
+
+fn strings(items: &[&str]) -> Value {
+	Value::List(items.iter().map(|s| Value::String((*s).into())).collect())
+}
+
+#[test]
+fn test_ls() {
+	helpers::eval_expect_values(&[
+		("ls()", strings(&[])),
+		(
+			"x <- 1
+			fn half(n: Int) -> Float { n / 2.0 }
+			ls()",
+			strings(&["half", "x"]),
+		),
+		// shadowing a core builtin makes it show up
+		("print <- 1\nls()", strings(&["print"])),
+	]);
+
+	let ctx = EvalContext::builder()
+		.with_library(|_| {
+			let env = Environment::new("host");
+			env.define_fn("double", |x: i64| x * 2);
+			env.define_fn("halve", |x: i64| x / 2);
+			env
+		})
+		.build();
+	let ret = eval_multi(
+		&ctx,
+		&parse("use host::{double}\nlist(ls(host), ls())", "tests").unwrap(),
+	);
+	assert_eq!(
+		ret.unwrap(),
+		Value::List(vec![strings(&["double", "halve"]), strings(&["double"])].into())
+	);
+}
+
+#[test]
+fn test_signatures() {
+	helpers::eval_expect_values(&[
+		(
+			"fn half(n: Int) -> Float { n / 2.0 }\nsignature(half)",
+			Value::List(
+				vec![
+					Value::List(
+						vec![Value::List(
+							vec![Value::String("n".into()), Value::Type(Type::Int)].into(),
+						)]
+						.into(),
+					),
+					Value::Type(Type::Float),
+				]
+				.into(),
+			),
+		),
+		(
+			"signature(typeof)",
+			Value::List(
+				vec![
+					Value::List(
+						vec![Value::List(
+							vec![Value::String("obj".into()), Value::Type(Type::Any)].into(),
+						)]
+						.into(),
+					),
+					Value::Type(Type::Type),
+				]
+				.into(),
+			),
+		),
+		// unions are normalised however they're written
+		(
+			"sig <- signature(location)\nlist(sig[0][0][1], sig[1] == (Null | String))",
+			Value::List(
+				vec![
+					Value::Type(Type::union(vec![
+						Type::Method,
+						Type::Builtin,
+						Type::Function,
+					])),
+					Value::Bool(true),
+				]
+				.into(),
+			),
+		),
+		(
+			"impls(decimal)",
+			Value::List(
+				[Type::Float, Type::Int, Type::String]
+					.iter()
+					.map(|t| {
+						Value::List(
+							vec![
+								Value::List(
+									vec![Value::List(
+										vec![Value::String("val".into()), Value::Type(t.clone())].into(),
+									)]
+									.into(),
+								),
+								Value::Type(Type::Decimal),
+							]
+							.into(),
+						)
+					})
+					.collect(),
+			),
+		),
+	]);
+}
+
+#[test]
+fn test_locations() {
+	helpers::eval_expect_values(&[
+		(
+			"x <- 1\n\nfn half(n: Int) -> Float { n / 2.0 }\nlocation(half)",
+			Value::String("tests:3".into()),
+		),
+		("location(to_string)", Value::Null(())),
+	]);
+
+	let ret = helpers::eval_src("location(typeof)").unwrap();
+	let location = ret.as_string(&EvalContext::new_with_stdlib()).unwrap();
+	assert!(location.starts_with("native: ") && location.contains("types.rs"));
+}
+
+#[test]
+fn test_reflection_failures() {
+	helpers::eval_expect_errors(&[("signature(1)",), ("ls(1)",), ("impls(typeof)",)]);
+}
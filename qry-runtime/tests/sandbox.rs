@@ -0,0 +1,80 @@
+use qry_lang::parse;
+use qry_runtime::{
+	eval_multi, Capability, Environment, EvalContext, EvalContextBuilder, EvalResult, ExceptionKind,
+	Signature, Type, Value,
+};
+
+fn eval_in(builder: EvalContextBuilder, src: &str) -> EvalResult<Value> {
+	eval_multi(&builder.build(), &parse(src, "tests").unwrap())
+}
+
+#[test]
+fn test_capabilities() {
+	let err = eval_in(EvalContext::builder().deny(Capability::Stdout), "print(1)").unwrap_err();
+	assert_eq!(err.kind, ExceptionKind::Denied);
+	assert_eq!(err.message, "capability denied: stdout");
+
+	let ret = eval_in(EvalContext::builder().deny(Capability::Filesystem), "1 + 1");
+	assert_eq!(ret.unwrap(), Value::Int(2));
+}
+
+#[test]
+fn test_library_selection() {
+	let ctx = EvalContext::builder().without_library("data").build();
+	assert!(ctx.library_env.get("data").is_none());
+	assert!(ctx.library_env.get("core").is_some());
+	assert!(ctx.library_env.get("ops").is_some());
+
+	let err = eval_in(EvalContext::builder().without_library("core"), "typeof(1)").unwrap_err();
+	assert_eq!(err.message, "not found: typeof");
+
+	// operators still work without core, they dispatch through ops
+	let ret = eval_in(EvalContext::builder().without_library("core"), "1 + 2");
+	assert_eq!(ret.unwrap(), Value::Int(3));
+}
+
+#[test]
+fn test_host_libraries() {
+	let builder = EvalContext::builder().with_library(|_| {
+		let env = Environment::new("host");
+		env.define_builtin(
+			"double",
+			Signature::returning(&Type::Int).param("x", &Type::Int),
+			|ctx, args, _| Ok(Value::Int(args[0].as_int(ctx)? * 2)),
+		);
+		env
+	});
+
+	let ret = eval_in(
+		builder,
+		"use host::{double}
+		double(21) + host::double(1)",
+	);
+	assert_eq!(ret.unwrap(), Value::Int(44));
+}
+
+#[test]
+fn test_sqlite_files_denied() {
+	let denied = || EvalContext::builder().deny(Capability::Filesystem);
+	let bootstrap = "use data::*
+		conn <- connect_sqlite(\":memory:\")
+		execute(conn, \"create table people (name text)\")";
+	for statement in &[
+		"attach database 'qry-denied.db' as other",
+		"vacuum into 'qry-denied.db'",
+	] {
+		let src = format!("{}\nexecute(conn, \"{}\")", bootstrap, statement);
+		let err = eval_in(denied(), &src).unwrap_err();
+		assert_eq!(err.kind, ExceptionKind::Denied, "{}", statement);
+		assert_eq!(err.message, "capability denied: filesystem");
+	}
+	assert!(!std::path::Path::new("qry-denied.db").exists());
+
+	// table names are quoted, not spliced into the query
+	let src = format!(
+		"{}\ntable(conn, \"people; attach database 'qry-denied.db' as other\") |> collect()",
+		bootstrap
+	);
+	let err = eval_in(denied(), &src).unwrap_err();
+	assert!(err.message.contains("no such table"), "{}", err);
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "sync")]
+
+use qry_lang::parse;
+use qry_runtime::{eval_multi, EvalContext, EvalResult, Value};
+use std::thread;
+
+fn eval_in(ctx: &EvalContext, src: &str) -> EvalResult<Value> {
+	eval_multi(ctx, &parse(src, "tests").unwrap())
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_runtime_is_send_sync() {
+	assert_send_sync::<EvalContext>();
+	assert_send_sync::<Value>();
+}
+
+#[test]
+fn test_forks_on_threads() {
+	let ctx = EvalContext::new_with_stdlib();
+	eval_in(
+		&ctx,
+		r#"impl ops::div(fib: String, n: Int) -> Int {
+	switch n < 2 {
+		true => n
+		false => fib / (n - 1) + fib / (n - 2)
+	}
+}"#,
+	)
+	.unwrap();
+
+	let handles = (10..14)
+		.map(|n| {
+			let ctx = ctx.fork();
+			thread::spawn(move || eval_in(&ctx, &format!("\"fib\" / {}", n)).unwrap())
+		})
+		.collect::<Vec<_>>();
+
+	let results = handles
+		.into_iter()
+		.map(|h| h.join().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		results,
+		vec![
+			Value::Int(55),
+			Value::Int(89),
+			Value::Int(144),
+			Value::Int(233)
+		]
+	);
+}
+
+#[test]
+fn test_forks_share_definitions() {
+	let ctx = EvalContext::new_with_stdlib();
+	let forked = ctx.fork();
+	thread::spawn(move || eval_in(&forked, "add <- fn(a: Int, b: Int) -> Int { a + b }").unwrap())
+		.join()
+		.unwrap();
+
+	assert_eq!(eval_in(&ctx, "add(2, 3)").unwrap(), Value::Int(5));
+}
@@ -1,3 +1,4 @@
+use qry_lang::parse;
 use qry_runtime::Value;
 
 pub mod helpers;
@@ -48,3 +49,55 @@ fn test_syntax() {
 fn test_syntax_failures() {
 	helpers::eval_expect_errors(&[("x",)]);
 }
+
+#[test]
+fn test_print_syntax() {
+	let print = |src: &str| {
+		parse(src, "tests")
+			.unwrap()
+			.iter()
+			.map(|node| node.to_string())
+			.collect::<Vec<_>>()
+			.join("\n")
+	};
+	let cases = [
+		("(1 + 2) * 3", "(1 + 2) * 3"),
+		("1 + (2 * 3)", "1 + 2 * 3"),
+		("1 - (2 - 3)", "1 - (2 - 3)"),
+		("x <- y <- -(1 + 2.0)", "x <- y <- -(1 + 2.0)"),
+		("!(a & b) | c", "!(a & b) | c"),
+		("f(1, x = g(2))[0]", "f(1, x = g(2))[0]"),
+		(
+			"pub fn add(a: Int, b: Int) -> Int { a + b }",
+			"pub fn add(a: Int, b: Int) -> Int {\n\ta + b\n}",
+		),
+		("use data::{table, filter}", "use data::{table, filter}"),
+		(
+			"switch x { 1 => \"one\" 2 => \"two\" }",
+			"switch x {\n\t1 => \"one\"\n\t2 => \"two\"\n}",
+		),
+		// comments are dropped, doc comments go with the function after them
+		("x <- 1 // one\n// two\nx", "x <- 1\nx"),
+		(
+			"/// Halves x.\n///\n/// Rounds down.\npub fn half(x: Int) -> Int { x / 2 }",
+			"/// Halves x.\n///\n/// Rounds down.\npub fn half(x: Int) -> Int {\n\tx / 2\n}",
+		),
+		(
+			"/// Doubles x.\ntwice <- fn(x: Int) -> Int { x * 2 }",
+			"/// Doubles x.\ntwice <- fn(x: Int) -> Int {\n\tx * 2\n}",
+		),
+		("//// not a doc comment\n/// nor this\n1 + 2", "1 + 2"),
+		// nor to anything when nothing comes after them
+		(
+			"fn one() -> Int {\n\t1\n\t/// the end of a block\n}\n/// the end of the file",
+			"fn one() -> Int {\n\t1\n}",
+		),
+		("x <- 1\n/// trailing\n/// lines", "x <- 1"),
+	];
+	for (src, expected) in cases.iter() {
+		let printed = print(src);
+		assert_eq!(printed, *expected);
+		// printing what was printed changes nothing
+		assert_eq!(print(&printed), printed);
+	}
+}
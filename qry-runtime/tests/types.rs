@@ -0,0 +1,210 @@
+use qry_runtime::{Type, Value};
+
+pub mod helpers;
+
+#[test]
+fn test_union_types() {
+	helpers::eval_expect_values(&[
+		(
+			"Int | Float",
+			Value::Type(Type::Union(vec![Type::Float, Type::Int])),
+		),
+		("(Int | Float) == (Float | Int)", Value::Bool(true)),
+		("(Int | Float) == (Int | Float | Int)", Value::Bool(true)),
+		("(Int | Any) == Any", Value::Bool(true)),
+		("Int | Int", Value::Type(Type::Int)),
+		(
+			"to_string(Int | Float)",
+			Value::String("Float | Int".into()),
+		),
+		(
+			"to_string(Int | (String | Null))",
+			Value::String("Int | Null | String".into()),
+		),
+		(
+			"fn f(x: Int | Float) -> Int | Float { x }
+			list(f(1), f(2.5))",
+			Value::List(vec![Value::Int(1), Value::Float(2.5)].into()),
+		),
+		(
+			"fn f(x: Int | Float) -> String { to_string(x) }
+			f(1)",
+			Value::String("1".into()),
+		),
+	]);
+}
+
+#[test]
+fn test_union_failures() {
+	helpers::eval_expect_errors(&[
+		(r#"fn f(x: Int | Float) -> Null { null }
+			f("one")"#,),
+		("Int | 1",),
+	]);
+	helpers::check_expect_diagnostics(&[
+		(
+			r#"fn f(x: Int | Float) -> Null { null }
+			f("one")"#,
+			"type mismatch: expected Float | Int, got String",
+		),
+		(
+			"fn f(x: Int | Float) -> String { x }",
+			"return type mismatch: expected String, got Float | Int",
+		),
+		(
+			"fn f(x: Int | String) -> Any { -x }",
+			"no impl of method 'minus' for (String)",
+		),
+	]);
+}
+
+#[test]
+fn test_type_aliases() {
+	helpers::eval_expect_values(&[
+		(
+			"type Number = Int | Float",
+			Value::Type(Type::Union(vec![Type::Float, Type::Int])),
+		),
+		("type Count = Int", Value::Type(Type::Int)),
+		(
+			"type Number = Int | Float
+			fn double(x: Number) -> Number { x * 2 }
+			list(double(2), double(1.5))",
+			Value::List(vec![Value::Int(4), Value::Float(3.)].into()),
+		),
+		(
+			"type Number = Int | Float
+			type Scalar = Number | String | Bool
+			Scalar == (Bool | Float | Int | String)",
+			Value::Bool(true),
+		),
+		(
+			"fn f() -> Any {
+				type Local = Int | Null
+				Local
+			}
+			f() == (Null | Int)",
+			Value::Bool(true),
+		),
+	]);
+}
+
+#[test]
+fn test_type_alias_failures() {
+	helpers::eval_expect_errors(&[("type Number = 1",)]);
+	helpers::check_expect_diagnostics(&[
+		("type Number = 1", "expected a type, got Int"),
+		(
+			r#"type Number = Int | Float
+			fn f(x: Number) -> Null { null }
+			f(true)"#,
+			"type mismatch: expected Float | Int, got Bool",
+		),
+	]);
+}
+
+#[test]
+fn test_union_dispatch() {
+	helpers::eval_expect_values(&[
+		(
+			r#"impl ops::sub(a: String, b: Int | Bool) -> String { "union" }
+			list("x" - 1, "x" - true)"#,
+			Value::List(vec![Value::String("union".into()), Value::String("union".into())].into()),
+		),
+		(
+			r#"impl ops::sub(a: String, b: Int | Bool) -> String { "union" }
+			impl ops::sub(a: String, b: Int) -> String { "int" }
+			impl ops::sub(a: String, b: Any) -> String { "any" }
+			list("x" - 1, "x" - true, "x" - null)"#,
+			Value::List(
+				vec![
+					Value::String("int".into()),
+					Value::String("union".into()),
+					Value::String("any".into()),
+				]
+				.into(),
+			),
+		),
+		(
+			r#"impl ops::sub(a: String, b: Int | String) -> String { to_string(b) }
+			impl ops::sub(a: String, b: Float) -> String { "float" }
+			"x" - 1"#,
+			Value::String("1".into()),
+		),
+	]);
+}
+
+#[test]
+fn test_interfaces() {
+	helpers::eval_expect_values(&[
+		(
+			"interface Arithmetic { ops::add ops::sub }
+			fn twice(x: Arithmetic) -> Any { x + x }
+			list(twice(1), twice(2.5))",
+			Value::List(vec![Value::Int(2), Value::Float(5.)].into()),
+		),
+		(
+			"interface Arithmetic { ops::add ops::sub }
+			to_string(Arithmetic)",
+			Value::String("Arithmetic".into()),
+		),
+		(
+			r#"interface Arithmetic { ops::add ops::sub }
+			impl ops::sub(a: String, b: String) -> String { a }
+			fn twice(x: Arithmetic) -> Any { x + x }
+			twice("ab")"#,
+			Value::String("abab".into()),
+		),
+		(
+			r#"interface Negatable { ops::minus }
+			impl ops::mul(a: String, b: Negatable) -> String { "negatable" }
+			list("x" * 1, "x" * 2.5)"#,
+			Value::List(
+				vec![
+					Value::String("negatable".into()),
+					Value::String("negatable".into()),
+				]
+				.into(),
+			),
+		),
+		(
+			"interface Negatable { ops::minus }
+			fn f(x: Negatable | Bool) -> Any { x }
+			list(f(1), f(true))",
+			Value::List(vec![Value::Int(1), Value::Bool(true)].into()),
+		),
+		(
+			"interface A { ops::minus }
+			interface B { ops::minus }
+			A == B",
+			Value::Bool(false),
+		),
+	]);
+}
+
+#[test]
+fn test_interface_failures() {
+	helpers::eval_expect_errors(&[
+		(r#"interface Arithmetic { ops::add ops::sub }
+			fn twice(x: Arithmetic) -> Any { x + x }
+			twice("ab")"#,),
+		(r#"interface Negatable { ops::minus }
+			impl ops::mul(a: String, b: Negatable) -> String { "negatable" }
+			"x" * true"#,),
+		("interface Broken { ops::minus 1 }",),
+	]);
+	helpers::check_expect_diagnostics(&[(
+		"interface Broken { ops::minus 1 }",
+		"interfaces can only require methods, got Int",
+	)]);
+
+	let err = helpers::eval_src(
+		r#"interface Arithmetic { ops::add ops::sub ops::div }
+		fn twice(x: Arithmetic) -> Any { x + x }
+		twice("ab")"#,
+	)
+	.unwrap_err();
+	assert!(err
+		.message
+		.contains("String does not implement Arithmetic, missing impls: sub, div"));
+}
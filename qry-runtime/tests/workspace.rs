@@ -0,0 +1,145 @@
+use qry_lang::parse;
+use qry_runtime::{
+	eval_multi, load_workspace, save_workspace, BigInt, Capability, Decimal, EvalContext, EvalResult,
+	ExceptionKind, Value,
+};
+use std::fs;
+use std::path::PathBuf;
+
+fn workspace_file(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("qry-test-{}-{}.qws", name, std::process::id()))
+}
+
+fn eval(ctx: &EvalContext, src: &str) -> EvalResult<Value> {
+	eval_multi(ctx, &parse(src, "tests").unwrap())
+}
+
+#[test]
+fn test_workspace_round_trip() {
+	let path = workspace_file("round-trip");
+	let ctx = EvalContext::new_with_stdlib();
+	eval(
+		&ctx,
+		r#"n <- 1
+f <- 2.5
+big <- bigint("123456789012345678901234567890")
+d <- decimal("0.1")
+s <- "some text"
+items <- list(1, "two", null, list(true))
+fn half(x: Int) -> Float {
+	x / 2.0
+}
+fn quarter(x: Int) -> Float {
+	half(x) / 2.0
+}
+twice <- fn(x: Int) -> Int { x * 2 }"#,
+	)
+	.unwrap();
+	assert_eq!(save_workspace(&ctx, &ctx.env, &path).unwrap(), 9);
+
+	let restored = EvalContext::new_with_stdlib();
+	let mut names = load_workspace(&restored, &path).unwrap();
+	fs::remove_file(&path).unwrap();
+	names.sort();
+	assert_eq!(
+		names,
+		vec!["big", "d", "f", "half", "items", "n", "quarter", "s", "twice"]
+	);
+
+	let value = |name: &str| restored.env.get(name).unwrap();
+	assert_eq!(value("n"), Value::Int(1));
+	assert_eq!(value("f"), Value::Float(2.5));
+	assert_eq!(
+		value("big"),
+		Value::BigInt("123456789012345678901234567890".parse::<BigInt>().unwrap())
+	);
+	assert_eq!(
+		value("d"),
+		Value::Decimal("0.1".parse::<Decimal>().unwrap())
+	);
+	assert_eq!(value("s"), Value::String("some text".into()));
+	assert_eq!(
+		value("items"),
+		Value::List(
+			vec![
+				Value::Int(1),
+				Value::String("two".into()),
+				Value::Null(()),
+				Value::List(vec![Value::Bool(true)].into()),
+			]
+			.into()
+		)
+	);
+	assert_eq!(
+		eval(&restored, "quarter(6) + twice(2)").unwrap(),
+		Value::Float(5.5)
+	);
+}
+
+#[test]
+fn test_workspace_closures() {
+	let path = workspace_file("closures");
+	let ctx = EvalContext::new_with_stdlib();
+	eval(
+		&ctx,
+		"fn getter(a: Int) -> Any { fn() -> Int { a + 1 } }
+		next <- getter(30)
+		n <- 1
+		fn plus_n(x: Int) -> Int { x + n }
+		y <- 1
+		fn one() -> Int { 1 }
+		fn get_y() -> Int { y }
+		y <- 2",
+	)
+	.unwrap();
+	// next captured getter's argument, which there's no binding to save, and
+	// get_y the y that's since been rebound. one doesn't refer to y at all
+	assert_eq!(save_workspace(&ctx, &ctx.env, &path).unwrap(), 5);
+
+	let restored = EvalContext::new_with_stdlib();
+	let mut names = load_workspace(&restored, &path).unwrap();
+	fs::remove_file(&path).unwrap();
+	names.sort();
+	assert_eq!(names, vec!["getter", "n", "one", "plus_n", "y"]);
+	assert_eq!(
+		eval(&restored, "list(plus_n(2), getter(1)(), one())").unwrap(),
+		Value::List(vec![Value::Int(3), Value::Int(2), Value::Int(1)].into())
+	);
+}
+
+#[test]
+fn test_workspace_failures() {
+	let path = workspace_file("not-a-workspace");
+	fs::write(&path, "n <- 1").unwrap();
+	let err = load_workspace(&EvalContext::new_with_stdlib(), &path).unwrap_err();
+	fs::remove_file(&path).unwrap();
+	assert!(err.message.ends_with("not a workspace file"), "{}", err);
+
+	// a truncated file, and a manifest length past the end of memory
+	let path = workspace_file("corrupt");
+	for length in &[&[1u8, 0][..], &u64::MAX.to_le_bytes()[..]] {
+		fs::write(&path, [&b"qry-workspace\n"[..], length].concat()).unwrap();
+		let err = load_workspace(&EvalContext::new_with_stdlib(), &path).unwrap_err();
+		assert!(err.message.ends_with("not a workspace file"), "{}", err);
+	}
+	fs::remove_file(&path).unwrap();
+
+	let ctx = EvalContext::builder().deny(Capability::Filesystem).build();
+	let err = eval(&ctx, "save_workspace(\"denied.qws\")").unwrap_err();
+	assert_eq!(err.kind, ExceptionKind::Denied);
+}
+
+#[test]
+fn test_workspace_builtins() {
+	let path = workspace_file("builtins");
+	let src = format!("x <- 42\ny <- save_workspace(\"{}\")", path.display());
+	let ctx = EvalContext::new_with_stdlib();
+	assert_eq!(eval(&ctx, &src).unwrap(), Value::Int(1));
+
+	let restored = EvalContext::new_with_stdlib();
+	let src = format!("load_workspace(\"{}\")", path.display());
+	let names = eval(&restored, &src).unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(names, Value::List(vec![Value::String("x".into())].into()));
+	assert_eq!(eval(&restored, "x").unwrap(), Value::Int(42));
+}
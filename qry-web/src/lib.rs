@@ -1,10 +1,13 @@
 use qry_lang::parse;
-use qry_runtime::{eval_multi, EvalContext};
+use qry_runtime::{eval_multi, EvalContext, Output};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 extern "C" {
 	fn alert(s: &str);
+
+	#[wasm_bindgen(js_namespace = console)]
+	fn log(s: &str);
 }
 
 #[wasm_bindgen]
@@ -19,7 +22,9 @@ impl Interpreter {
 		console_error_panic_hook::set_once();
 
 		Interpreter {
-			ctx: EvalContext::new_with_stdlib(),
+			ctx: EvalContext::builder()
+				.output(|output: &Output| log(output.text()))
+				.build(),
 		}
 	}
 
@@ -0,0 +1,458 @@
+// a Debug Adapter Protocol server so editors can debug qry scripts, started
+// with `qry --dap` and spoken over stdin/stdout
+
+use crate::context_builder;
+use qry_lang::{parse, SourceLocation};
+use qry_runtime::{
+	check, eval, eval_multi, DebugCommand, DebugHandler, Debugger, EvalContext, Output, PauseHandle,
+	PauseReason, Shared, Value,
+};
+use serde_json::{json, Value as Json};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// scripts run on a single thread
+const THREAD_ID: i64 = 1;
+// only the innermost frame's environment is kept around, so it's the only
+// scope with variables
+const LOCALS_REFERENCE: i64 = 1;
+
+struct Connection {
+	output: Mutex<Box<dyn Write + Send>>,
+	seq: AtomicI64,
+}
+
+impl Connection {
+	fn send(&self, mut message: Json) {
+		message["seq"] = json!(self.seq.fetch_add(1, Ordering::SeqCst));
+		let body = message.to_string();
+
+		let mut output = self.output.lock().unwrap();
+		let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+		let _ = output.flush();
+	}
+
+	fn respond(&self, request: &Json, body: Json) {
+		self.send(json!({
+			"type": "response",
+			"request_seq": request["seq"],
+			"command": request["command"],
+			"success": true,
+			"body": body,
+		}));
+	}
+
+	fn fail(&self, request: &Json, message: &str) {
+		self.send(json!({
+			"type": "response",
+			"request_seq": request["seq"],
+			"command": request["command"],
+			"success": false,
+			"message": message,
+		}));
+	}
+
+	fn event(&self, event: &str, body: Json) {
+		self.send(json!({
+			"type": "event",
+			"event": event,
+			"body": body,
+		}));
+	}
+
+	fn output(&self, category: &str, text: &str) {
+		self.event("output", json!({ "category": category, "output": text }));
+	}
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Json> {
+	let mut length = None;
+	loop {
+		let mut header = String::new();
+		if input.read_line(&mut header).ok()? == 0 {
+			return None;
+		}
+		let header = header.trim();
+		if header.is_empty() {
+			break;
+		}
+		let mut parts = header.splitn(2, ':');
+		if let (Some("Content-Length"), Some(value)) = (parts.next(), parts.next()) {
+			length = value.trim().parse::<usize>().ok();
+		}
+	}
+
+	let mut body = vec![0; length?];
+	input.read_exact(&mut body).ok()?;
+	serde_json::from_slice(&body).ok()
+}
+
+// pause has to be answered while the script is running, everything else
+// waits for the evaluating thread to pick it up
+fn read_requests(connection: Arc<Connection>, pause: PauseHandle, requests: Sender<Json>) {
+	let stdin = io::stdin();
+	let mut input = stdin.lock();
+	while let Some(message) = read_message(&mut input) {
+		if message["command"] == "pause" {
+			pause.pause();
+			connection.respond(&message, json!({}));
+		} else if requests.send(message).is_err() {
+			break;
+		}
+	}
+}
+
+struct Session {
+	connection: Arc<Connection>,
+	requests: Mutex<Receiver<Json>>,
+}
+
+impl Session {
+	// None once the editor has gone away
+	fn next_request(&self) -> Option<Json> {
+		self.requests.lock().unwrap().recv().ok()
+	}
+
+	// requests that get the same answer whether or not the script is running
+	fn handle_common(&self, debugger: Option<&Debugger>, request: &Json) -> bool {
+		match request["command"].as_str().unwrap_or("") {
+			"threads" => self.connection.respond(
+				request,
+				json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+			),
+			"setBreakpoints" => {
+				let args = &request["arguments"];
+				let path = args["source"]["path"].as_str().unwrap_or("");
+				let lines = args["breakpoints"]
+					.as_array()
+					.map(|bps| bps.iter().filter_map(|bp| bp["line"].as_u64()))
+					.into_iter()
+					.flatten()
+					.map(|line| line as usize)
+					.collect::<Vec<_>>();
+
+				if let Some(debugger) = debugger {
+					debugger.set_breakpoints(&canonical_name(path), &lines);
+				}
+				let verified = lines
+					.iter()
+					.map(|line| json!({ "verified": true, "line": line }))
+					.collect::<Vec<_>>();
+				self
+					.connection
+					.respond(request, json!({ "breakpoints": verified }));
+			}
+			"setExceptionBreakpoints" => self.connection.respond(request, json!({})),
+			"disconnect" => {
+				self.connection.respond(request, json!({}));
+				std::process::exit(0);
+			}
+			_ => return false,
+		}
+		true
+	}
+}
+
+// breakpoints are matched against the name a script was parsed with, so both
+// go through the same path
+fn canonical_name(path: &str) -> String {
+	fs::canonicalize(path)
+		.map(|p| p.to_string_lossy().into_owned())
+		.unwrap_or_else(|_| path.to_string())
+}
+
+fn display(ctx: &EvalContext, value: &Value) -> String {
+	ctx
+		.methods
+		.to_string
+		.resolve(&[value.runtime_type()])
+		.ok()
+		.and_then(|to_string| to_string.call(ctx, std::slice::from_ref(value), &[]).ok())
+		.and_then(|s| s.as_string(ctx).ok().map(|s| s.to_string()))
+		.unwrap_or_else(|| format!("<{}>", value.runtime_type().name()))
+}
+
+fn source(location: &SourceLocation) -> Option<(Json, usize)> {
+	match location {
+		SourceLocation::User { file, line } => {
+			let name = Path::new(&**file)
+				.file_name()
+				.map_or_else(|| file.to_string(), |n| n.to_string_lossy().into_owned());
+			Some((json!({ "name": name, "path": &**file }), *line))
+		}
+		_ => None,
+	}
+}
+
+fn stack_frame(id: usize, name: &str, location: &SourceLocation) -> Json {
+	match source(location) {
+		Some((source, line)) => {
+			json!({ "id": id, "name": name, "source": source, "line": line, "column": 1 })
+		}
+		None => json!({ "id": id, "name": name, "line": 0, "column": 0 }),
+	}
+}
+
+// the paused location, then the call site of each frame on the callstack
+fn stack_trace(ctx: &EvalContext, location: &SourceLocation) -> Json {
+	let callstack = ctx.callstack.borrow();
+	let name_at = |depth: usize| match depth {
+		0 => "<main>",
+		_ => &callstack[depth - 1].name,
+	};
+
+	let frames = std::iter::once(stack_frame(0, name_at(callstack.len()), location))
+		.chain(
+			(0..callstack.len())
+				.rev()
+				.map(|i| stack_frame(callstack.len() - i, name_at(i), &callstack[i].location)),
+		)
+		.collect::<Vec<_>>();
+	json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+// environments hold the whole stdlib too, which would drown out the script's
+// own bindings
+fn variables(ctx: &EvalContext) -> Json {
+	let mut bindings = ctx
+		.env
+		.bindings()
+		.into_iter()
+		.filter(|(_, v)| {
+			!matches!(
+				v,
+				Value::Builtin(_) | Value::Method(_) | Value::Library(_) | Value::Type(_)
+			)
+		})
+		.collect::<Vec<_>>();
+	bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+	let variables = bindings
+		.iter()
+		.map(|(name, value)| {
+			json!({
+				"name": name,
+				"value": display(ctx, value),
+				"type": value.runtime_type().name(),
+				"variablesReference": 0,
+			})
+		})
+		.collect::<Vec<_>>();
+	json!({ "variables": variables })
+}
+
+// runs nodes one at a time rather than through eval_multi, which would
+// start a new run and reset the budget of the paused one
+fn evaluate(ctx: &EvalContext, expression: &str) -> Result<String, String> {
+	let syntax = parse(expression, "<eval>").map_err(|err| err.to_string())?;
+	let mut result = Value::Null(());
+	for node in &syntax {
+		result = eval(ctx, node).map_err(|ex| ex.message)?;
+	}
+	Ok(display(ctx, &result))
+}
+
+struct DapHandler {
+	session: Arc<Session>,
+}
+
+impl DebugHandler for DapHandler {
+	fn paused(
+		&self,
+		ctx: &EvalContext,
+		location: &SourceLocation,
+		reason: PauseReason,
+	) -> DebugCommand {
+		let connection = &self.session.connection;
+		connection.event(
+			"stopped",
+			json!({ "reason": reason.name(), "threadId": THREAD_ID, "allThreadsStopped": true }),
+		);
+
+		while let Some(request) = self.session.next_request() {
+			let command = match request["command"].as_str().unwrap_or("") {
+				"continue" => DebugCommand::Continue,
+				"next" => DebugCommand::StepOver,
+				"stepIn" => DebugCommand::StepIn,
+				"stepOut" => DebugCommand::StepOut,
+				"stackTrace" => {
+					connection.respond(&request, stack_trace(ctx, location));
+					continue;
+				}
+				"scopes" => {
+					let scopes = match request["arguments"]["frameId"].as_i64() {
+						Some(0) => vec![json!({
+							"name": "Locals",
+							"variablesReference": LOCALS_REFERENCE,
+							"expensive": false,
+						})],
+						_ => vec![],
+					};
+					connection.respond(&request, json!({ "scopes": scopes }));
+					continue;
+				}
+				"variables" => {
+					connection.respond(&request, variables(ctx));
+					continue;
+				}
+				"evaluate" => {
+					let expression = request["arguments"]["expression"].as_str().unwrap_or("");
+					match evaluate(ctx, expression) {
+						Ok(result) => connection.respond(
+							&request,
+							json!({ "result": result, "variablesReference": 0 }),
+						),
+						Err(message) => connection.fail(&request, &message),
+					}
+					continue;
+				}
+				_ => {
+					let debugger = ctx.debugger.as_deref();
+					if !self.session.handle_common(debugger, &request) {
+						connection.fail(&request, "not supported while paused");
+					}
+					continue;
+				}
+			};
+
+			connection.respond(&request, json!({ "allThreadsContinued": true }));
+			return command;
+		}
+		DebugCommand::Continue
+	}
+}
+
+// script output goes to the client as output events, anything else printed
+// would corrupt the protocol on stdout, so it's sent to stderr and the
+// protocol gets its own copy of stdout
+#[cfg(unix)]
+fn protocol_output() -> Box<dyn Write + Send> {
+	use std::os::unix::io::FromRawFd;
+	unsafe {
+		let protocol_fd = libc::dup(1);
+		libc::dup2(2, 1);
+		Box::new(fs::File::from_raw_fd(protocol_fd))
+	}
+}
+
+#[cfg(not(unix))]
+fn protocol_output() -> Box<dyn Write + Send> {
+	Box::new(io::stdout())
+}
+
+fn launch(session: &Session, debugger: Shared<Debugger>, program: &str) -> i64 {
+	let connection = &session.connection;
+	let fail = |message: String| {
+		connection.output("stderr", &format!("{}\n", message));
+		1
+	};
+
+	let program = canonical_name(program);
+	let src = match fs::read_to_string(&program) {
+		Ok(src) => src,
+		Err(err) => return fail(format!("error reading {}: {}", program, err)),
+	};
+	let syntax = match parse(&src, &program) {
+		Ok(syntax) => syntax,
+		Err(err) => return fail(format!("parser {}", err)),
+	};
+
+	let script_dir = Path::new(&program)
+		.parent()
+		.unwrap_or_else(|| Path::new("."));
+	let output_connection = connection.clone();
+	let ctx = match context_builder(script_dir) {
+		Some(builder) => builder
+			.debugger(debugger)
+			.output(move |output: &Output| {
+				output_connection.output("stdout", &format!("{}\n", output.text()))
+			})
+			.build(),
+		None => return 1,
+	};
+
+	// as when running a script, what the checker finds is only a warning
+	for diagnostic in check(&ctx, &syntax) {
+		connection.output("stderr", &format!("warning: {}\n", diagnostic));
+	}
+
+	// the tree-walker keeps every binding in an Environment, where the vm
+	// keeps locals in slots the debugger can't see
+	match eval_multi(&ctx, &syntax) {
+		Ok(_) => 0,
+		Err(ex) => fail(ex.to_string()),
+	}
+}
+
+pub fn serve() {
+	let connection = Arc::new(Connection {
+		output: Mutex::new(protocol_output()),
+		seq: AtomicI64::new(1),
+	});
+	let (sender, receiver) = channel();
+	let session = Arc::new(Session {
+		connection: connection.clone(),
+		requests: Mutex::new(receiver),
+	});
+
+	let debugger = Debugger::new(DapHandler {
+		session: session.clone(),
+	});
+	let pause = debugger.pause_handle();
+	let reader_connection = connection.clone();
+	thread::spawn(move || read_requests(reader_connection, pause, sender));
+
+	// breakpoints and launch arguments arrive before configurationDone, which
+	// is the go-ahead to start running
+	let mut program = None;
+	while let Some(request) = session.next_request() {
+		match request["command"].as_str().unwrap_or("") {
+			"initialize" => {
+				connection.respond(
+					&request,
+					json!({ "supportsConfigurationDoneRequest": true }),
+				);
+				connection.event("initialized", json!({}));
+			}
+			"launch" => {
+				let args = &request["arguments"];
+				program = args["program"].as_str().map(|p| p.to_string());
+				if args["stopOnEntry"].as_bool().unwrap_or(false) {
+					debugger.stop_on_entry();
+				}
+				connection.respond(&request, json!({}));
+			}
+			"configurationDone" => {
+				connection.respond(&request, json!({}));
+				break;
+			}
+			_ => {
+				if !session.handle_common(Some(&debugger), &request) {
+					connection.fail(&request, "not supported before launch");
+				}
+			}
+		}
+	}
+
+	let exit_code = match program {
+		Some(program) => launch(&session, debugger.clone(), &program),
+		None => {
+			connection.output("stderr", "no program to launch\n");
+			1
+		}
+	};
+	connection.event("exited", json!({ "exitCode": exit_code }));
+	connection.event("terminated", json!({}));
+
+	// the editor still sends a disconnect once it sees the script finish
+	while let Some(request) = session.next_request() {
+		if !session.handle_common(Some(&debugger), &request) {
+			connection.fail(&request, "program has exited");
+		}
+	}
+}
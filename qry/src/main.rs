@@ -1,16 +1,77 @@
+mod dap;
+
 use qry_lang::parse;
-use qry_runtime::{eval_multi, EvalContext, Value};
+use qry_runtime::{
+	check, compile, eval_multi, html_index, html_reference, library_docs, load_workspace,
+	markdown_index, markdown_reference, run_compiled, save_workspace, EvalContext,
+	EvalContextBuilder, InterruptHandle, Manifest, Profiler, Value,
+};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_: libc::c_int) {
+	SIGINT_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+// ctrl-c cancels the running evaluation instead of killing the process, the
+// handler only sets a flag and a watcher thread forwards it
+fn forward_interrupts(handle: InterruptHandle) {
+	#[cfg(unix)]
+	unsafe {
+		let handler: extern "C" fn(libc::c_int) = on_sigint;
+		libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+	}
+
+	thread::spawn(move || loop {
+		if SIGINT_RECEIVED.swap(false, Ordering::SeqCst) {
+			handle.interrupt();
+		}
+		thread::sleep(Duration::from_millis(50));
+	});
+}
+
+// modules resolve against the script's directory first, then the paths from a
+// qry.toml in the working directory (or a parent of it), then QRY_PATH
+fn context_builder(script_dir: &Path) -> Option<EvalContextBuilder> {
+	let mut builder = EvalContext::builder().module_path(script_dir);
+
+	let working_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+	match Manifest::discover(&working_dir) {
+		Ok(Some(manifest)) => builder = builder.manifest(&manifest),
+		Ok(None) => (),
+		Err(err) => {
+			println!("{}", err);
+			return None;
+		}
+	}
+
+	if let Some(paths) = env::var_os("QRY_PATH") {
+		for dir in env::split_paths(&paths) {
+			builder = builder.module_path(dir);
+		}
+	}
+	Some(builder)
+}
 
 fn print_value(ctx: &EvalContext, value: Value) {
 	print!("({})", value.runtime_type().name());
 
-	if let Some(to_string_func) = ctx.methods.to_string.resolve(&[value.runtime_type()]) {
-		match to_string_func.call(&ctx, &[value], &[]) {
-			Ok(value_str) => print!(" {}", value_str.as_string()),
+	if let Ok(to_string_func) = ctx.methods.to_string.resolve(&[value.runtime_type()]) {
+		let value_str = to_string_func
+			.call(&ctx, &[value], &[])
+			.and_then(|s| Ok(s.as_string(ctx)?.to_string()));
+
+		match value_str {
+			Ok(value_str) => print!(" {}", value_str),
 			Err(err) => print!(" error in to_string: {}", err),
 		}
 	}
@@ -18,9 +79,22 @@ fn print_value(ctx: &EvalContext, value: Value) {
 	println!();
 }
 
-fn repl() {
+// with a workspace file the session picks up where the last one left off, and
+// is saved back to it on exit
+fn repl(workspace: Option<&Path>) {
 	let mut rl = Editor::<()>::new();
-	let ctx = EvalContext::new_with_stdlib();
+	let ctx = match context_builder(Path::new(".")) {
+		Some(builder) => builder.build(),
+		None => return,
+	};
+	forward_interrupts(ctx.interrupt_handle());
+
+	if let Some(path) = workspace.filter(|path| path.exists()) {
+		match load_workspace(&ctx, path) {
+			Ok(names) => println!("restored {} from {}", names.join(", "), path.display()),
+			Err(err) => println!("{}", err),
+		}
+	}
 
 	loop {
 		match rl.readline("> ") {
@@ -38,19 +112,155 @@ fn repl() {
 			}
 		}
 	}
+
+	if let Some(path) = workspace {
+		if let Err(err) = save_workspace(&ctx, &ctx.env, path) {
+			println!("{}", err);
+		}
+	}
+}
+
+// `qry doc [--html] [dir]` writes a reference page for each library, and an
+// index linking them, to dir (doc by default)
+fn write_docs(args: &[String]) {
+	let html = args.iter().any(|a| a == "--html");
+	let dir = args
+		.iter()
+		.find(|a| *a != "--html")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| PathBuf::from("doc"));
+	let ctx = match context_builder(Path::new(".")) {
+		Some(builder) => builder.build(),
+		None => return,
+	};
+
+	let mut libraries = ctx
+		.library_env
+		.bindings()
+		.into_iter()
+		.filter_map(|(name, value)| match value {
+			Value::Library(env) => Some((name, env)),
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+	libraries.sort_by(|(a, _), (b, _)| a.cmp(b));
+	let names = libraries
+		.iter()
+		.map(|(name, _)| name.clone())
+		.collect::<Vec<_>>();
+
+	let extension = if html { "html" } else { "md" };
+	let mut pages = vec![(
+		"index".to_string(),
+		match html {
+			true => html_index(&names),
+			false => markdown_index(&names),
+		},
+	)];
+	for (name, env) in &libraries {
+		let entries = library_docs(env);
+		let page = match html {
+			true => html_reference(name, &entries),
+			false => markdown_reference(name, &entries),
+		};
+		pages.push((name.clone(), page));
+	}
+
+	let written = fs::create_dir_all(&dir).and_then(|_| {
+		pages
+			.iter()
+			.try_for_each(|(name, page)| fs::write(dir.join(format!("{}.{}", name, extension)), page))
+	});
+	match written {
+		Ok(()) => println!("wrote {} libraries to {}", names.len(), dir.display()),
+		Err(err) => println!("error writing docs to {}: {}", dir.display(), err),
+	}
+}
+
+fn write_profile(profiler: &Profiler, folded_file: &str) {
+	eprint!("{}", profiler.report());
+	let written = fs::File::create(folded_file).and_then(|mut f| profiler.write_folded(&mut f));
+	if let Err(err) = written {
+		println!("error writing {}: {}", folded_file, err);
+	}
 }
 
 fn main() {
-	let args = env::args().skip(1).collect::<Vec<_>>();
+	let mut args = env::args().skip(1).collect::<Vec<_>>();
 	if args.is_empty() {
-		repl();
+		repl(None);
+		return;
+	}
+	if args[0] == "--workspace" {
+		match args.get(1) {
+			Some(path) => repl(Some(Path::new(path))),
+			None => println!("usage: qry --workspace <file>"),
+		}
+		return;
+	}
+	if args[0] == "--dap" {
+		dap::serve();
 		return;
 	}
+	if args[0] == "doc" {
+		write_docs(&args[1..]);
+		return;
+	}
+
+	// `qry --profile out.folded script.qry` prints timings once the script
+	// finishes and writes folded stacks for a flamegraph
+	let profile = match args[0].as_str() {
+		"--profile" if args.len() > 2 => {
+			let folded_file = args.remove(1);
+			args.remove(0);
+			Some((Profiler::new(), folded_file))
+		}
+		"--profile" => {
+			println!("usage: qry --profile <folded-file> <script>");
+			return;
+		}
+		_ => None,
+	};
+
+	let script_contents = match fs::read_to_string(&args[0]) {
+		Ok(contents) => contents,
+		Err(err) => {
+			println!("error reading {}: {}", args[0], err);
+			return;
+		}
+	};
 
-	let script_contents = fs::read_to_string(&args[0]).unwrap();
-	let ast = parse(&script_contents, &args[0]).unwrap();
-	let ctx = EvalContext::new_with_stdlib();
-	if let Err(ex) = eval_multi(&ctx, &ast) {
+	let ast = match parse(&script_contents, &args[0]) {
+		Ok(ast) => ast,
+		Err(err) => {
+			println!("parser {}", err);
+			return;
+		}
+	};
+	let script_dir = Path::new(&args[0])
+		.parent()
+		.unwrap_or_else(|| Path::new("."));
+	let ctx = match context_builder(script_dir) {
+		Some(builder) => match &profile {
+			Some((profiler, _)) => builder.profiler(profiler.clone()).build(),
+			None => builder.build(),
+		},
+		None => return,
+	};
+	forward_interrupts(ctx.interrupt_handle());
+
+	// the checker doesn't follow which branches run, so what it finds may not
+	// happen and the script runs regardless
+	for diagnostic in check(&ctx, &ast) {
+		println!("warning: {}", diagnostic);
+	}
+
+	let program = compile(&ast);
+	if let Err(ex) = run_compiled(&ctx, &program) {
 		println!("{}", ex);
 	}
+
+	if let Some((profiler, folded_file)) = profile {
+		write_profile(&profiler, &folded_file);
+	}
 }